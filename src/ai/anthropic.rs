@@ -0,0 +1,303 @@
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+
+use crate::{
+    ai::{
+        debug_log,
+        provider::{
+            HttpStatusError, InvalidResponse, Provider,
+            UnparsedOutput, is_event_stream, parse_schema, preamble,
+            read_sse, response_schema, sampling_params, with_headers,
+        },
+        response::{ResponseEvent, ResponseSchema},
+    },
+    config::ProviderConfig,
+    consts::{ANTHROPIC_URL, ANTHROPIC_VERSION},
+};
+
+const SUBMIT_TOOL: &str = "submit_commits";
+
+/// claude through the messages api, base_url in the provider
+/// config wins over ANTHROPIC_URL. with tx it's streamed
+pub async fn messages(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    prompt_cache: bool,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> Result<ResponseSchema> {
+    let api_key = match &provider_cfg.api_key {
+        Some(key) => key.to_owned(),
+        None => std::env::var("ANTHROPIC_API_KEY").context(
+            "Claude needs an api_key in its provider config or ANTHROPIC_API_KEY",
+        )?,
+    };
+
+    let base_url = provider_cfg
+        .base_url
+        .as_deref()
+        .unwrap_or(ANTHROPIC_URL)
+        .trim_end_matches('/');
+
+    let mut request_body =
+        messages_body(prompt, provider_cfg, diffs, prompt_cache)?;
+    request_body["model"] = provider_cfg.model.as_str().into();
+    if tx.is_some() {
+        request_body["stream"] = true.into();
+    }
+
+    debug_log::record(
+        Provider::Claude,
+        "request",
+        request_body.clone(),
+    );
+
+    let request = reqwest::Client::new()
+        .post(format!("{}/messages", base_url))
+        .header("x-api-key", api_key)
+        .header("anthropic-version", ANTHROPIC_VERSION)
+        .json(&request_body);
+    let response = with_headers(request, provider_cfg)?
+        .send()
+        .await
+        .context("failed to send request")?;
+
+    if !response.status().is_success() {
+        return Err(HttpStatusError::from_response(response)
+            .await
+            .into());
+    }
+
+    if let Some(tx) = tx
+        && is_event_stream(&response)
+    {
+        return streamed(response, tx).await;
+    }
+
+    let raw = response
+        .text()
+        .await
+        .context("failed to read the response")?;
+    debug_log::record_raw(Provider::Claude, "response", &raw);
+
+    tool_input(Provider::Claude, &raw)
+}
+
+/// the tool call's input comes in as input_json_delta
+/// pieces, text_delta when it answered in text instead
+async fn streamed(
+    response: reqwest::Response,
+    tx: &mpsc::Sender<ResponseEvent>,
+) -> Result<ResponseSchema> {
+    let mut input: Option<String> = None;
+    let mut text = String::new();
+
+    let raw = read_sse(response, tx, |data| {
+        let event: Value =
+            serde_json::from_str(data).map_err(|e| {
+                InvalidResponse(format!(
+                    "Failed to parse streamed JSON: {}",
+                    e
+                ))
+            })?;
+
+        match event.get("type").and_then(|t| t.as_str()) {
+            Some("content_block_delta") => {
+                let delta = &event["delta"];
+                if let Some(json) =
+                    delta.get("partial_json").and_then(|j| j.as_str())
+                {
+                    input.get_or_insert_default().push_str(json);
+                    Ok(Some(json.to_owned()))
+                } else if let Some(piece) =
+                    delta.get("text").and_then(|t| t.as_str())
+                {
+                    text.push_str(piece);
+                    Ok(Some(piece.to_owned()))
+                } else {
+                    Ok(None)
+                }
+            }
+            Some("error") => Err(InvalidResponse(format!(
+                "Claude sent an error while streaming: {}",
+                event["error"]
+            ))
+            .into()),
+            _ => Ok(None),
+        }
+    })
+    .await?;
+    debug_log::record_raw(Provider::Claude, "response", &raw);
+
+    match input {
+        Some(input) => {
+            serde_json::from_str(&input).map_err(|error| {
+                UnparsedOutput { text: input, error }.into()
+            })
+        }
+        None if text.trim().is_empty() => Err(InvalidResponse(
+            "Invalid response format from Claude".to_owned(),
+        )
+        .into()),
+        None => parse_schema(&text),
+    }
+}
+
+/// the messages body without the model, bedrock takes it too.
+/// the schema is a forced tool call, with prompt_cache the
+/// system prompt and the diffs each end a cache breakpoint,
+/// the tool is cached along with the system prompt since
+/// it comes first
+pub fn messages_body(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    prompt_cache: bool,
+) -> Result<Value> {
+    let system = preamble(prompt, provider_cfg);
+
+    let (system, content) = if prompt_cache {
+        (
+            json!([{
+                "type": "text",
+                "text": system,
+                "cache_control": { "type": "ephemeral" }
+            }]),
+            json!([{
+                "type": "text",
+                "text": diffs,
+                "cache_control": { "type": "ephemeral" }
+            }]),
+        )
+    } else {
+        (json!(system), json!(diffs))
+    };
+
+    let mut request_body = json!({
+        "max_tokens": provider_cfg.max_tokens,
+        "system": system,
+        "messages": [
+            { "role": "user", "content": content }
+        ],
+        "tools": [{
+            "name": SUBMIT_TOOL,
+            "description": "Submit the commits",
+            "input_schema": response_schema()?
+        }],
+        "tool_choice": { "type": "tool", "name": SUBMIT_TOOL }
+    });
+
+    if let Some(body) = request_body.as_object_mut() {
+        body.extend(sampling_params(provider_cfg, "top_p"));
+    }
+
+    Ok(request_body)
+}
+
+/// the forced tool call's input out of a raw messages response
+pub fn tool_input(
+    provider: Provider,
+    raw: &str,
+) -> Result<ResponseSchema> {
+    let response_json: Value =
+        serde_json::from_str(raw).map_err(|e| {
+            InvalidResponse(format!(
+                "Failed to parse response JSON: {}",
+                e
+            ))
+        })?;
+
+    let invalid = || {
+        InvalidResponse(format!(
+            "Invalid response format from {}",
+            provider
+        ))
+    };
+
+    let blocks = response_json
+        .get("content")
+        .and_then(|c| c.as_array())
+        .ok_or_else(invalid)?;
+    let block_type = |b: &Value, kind: &str| {
+        b.get("type").and_then(|t| t.as_str()) == Some(kind)
+    };
+
+    let Some(input) = blocks
+        .iter()
+        .find(|b| block_type(b, "tool_use"))
+        .and_then(|b| b.get("input"))
+    else {
+        // it answered in text instead of calling the tool
+        let text: String = blocks
+            .iter()
+            .filter(|b| block_type(b, "text"))
+            .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+            .collect();
+
+        if text.trim().is_empty() {
+            return Err(invalid().into());
+        }
+
+        return parse_schema(&text);
+    };
+
+    serde_json::from_value(input.clone()).map_err(|error| {
+        UnparsedOutput {
+            text: input.to_string(),
+            error,
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompt_cache_marks_the_prompt_and_diffs() {
+        let cfg = ProviderConfig::new("claude");
+        let body =
+            messages_body("prompt", &cfg, "diffs", true).unwrap();
+
+        let ephemeral = json!({ "type": "ephemeral" });
+        assert_eq!(body["system"][0]["cache_control"], ephemeral);
+        assert_eq!(body["system"][0]["text"], "prompt");
+        assert_eq!(
+            body["messages"][0]["content"][0]["cache_control"],
+            ephemeral
+        );
+        assert_eq!(
+            body["messages"][0]["content"][0]["text"],
+            "diffs"
+        );
+    }
+
+    #[test]
+    fn no_prompt_cache_sends_plain_strings() {
+        let cfg = ProviderConfig::new("claude");
+        let body =
+            messages_body("prompt", &cfg, "diffs", false).unwrap();
+
+        assert_eq!(body["system"], "prompt");
+        assert_eq!(body["messages"][0]["content"], "diffs");
+    }
+
+    #[test]
+    fn messages_sends_the_system_prompt() {
+        let mut cfg = ProviderConfig::new("claude");
+        cfg.system_prompt = Some("never use emoji".to_owned());
+
+        let body =
+            messages_body("prompt", &cfg, "diffs", false).unwrap();
+        assert_eq!(body["system"], "never use emoji\n\nprompt");
+
+        let body =
+            messages_body("prompt", &cfg, "diffs", true).unwrap();
+        assert_eq!(
+            body["system"][0]["text"],
+            "never use emoji\n\nprompt"
+        );
+    }
+}
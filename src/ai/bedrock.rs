@@ -0,0 +1,380 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{Datelike, Timelike};
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{
+    ai::{
+        anthropic, debug_log,
+        provider::{HttpStatusError, Provider, extra_headers},
+        response::ResponseSchema,
+    },
+    config::ProviderConfig,
+    consts::BEDROCK_ANTHROPIC_VERSION,
+};
+
+const SERVICE: &str = "bedrock";
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+/// claude through bedrock's invoke-model, the anthropic
+/// messages body with a forced tool call for the schema
+pub async fn invoke(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+) -> Result<ResponseSchema> {
+    let region = provider_cfg
+        .region
+        .to_owned()
+        .or_else(|| std::env::var("AWS_REGION").ok())
+        .or_else(|| std::env::var("AWS_DEFAULT_REGION").ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "Bedrock needs a region in its provider config or AWS_REGION"
+            )
+        })?;
+
+    let credentials = load_credentials()?;
+
+    // bedrock only caches on some claude models,
+    // and errors on the rest
+    let mut request_body =
+        anthropic::messages_body(prompt, provider_cfg, diffs, false)?;
+    request_body["anthropic_version"] =
+        BEDROCK_ANTHROPIC_VERSION.into();
+
+    debug_log::record(
+        Provider::Bedrock,
+        "request",
+        request_body.clone(),
+    );
+
+    let body = serde_json::to_vec(&request_body)?;
+
+    let host = format!("bedrock-runtime.{}.amazonaws.com", region);
+    // model ids have a ':' in them, ex. ...-v1:0
+    let path =
+        format!("/model/{}/invoke", uri_encode(&provider_cfg.model));
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let timestamp =
+        chrono::DateTime::from_timestamp(now.as_secs() as i64, 0)
+            .ok_or_else(|| anyhow!("system time is out of range"))?;
+    // chrono is built without its formatting
+    let amz_date = format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        timestamp.year(),
+        timestamp.month(),
+        timestamp.day(),
+        timestamp.hour(),
+        timestamp.minute(),
+        timestamp.second()
+    );
+
+    let mut headers = vec![
+        ("content-type".to_owned(), "application/json".to_owned()),
+        ("host".to_owned(), host.to_owned()),
+        ("x-amz-date".to_owned(), amz_date.to_owned()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push((
+            "x-amz-security-token".to_owned(),
+            token.to_owned(),
+        ));
+    }
+
+    // the provider's headers are signed along with ours, sent
+    // unsigned they'd break the signature. ours are never
+    // replaced, override_auth_headers or not
+    for (name, value) in &extra_headers(provider_cfg)? {
+        if name == "authorization"
+            || headers.iter().any(|(n, _)| n == name.as_str())
+        {
+            continue;
+        }
+
+        let value = value.to_str().with_context(|| {
+            format!("the {} header isn't ascii", name)
+        })?;
+        headers.push((name.as_str().to_owned(), value.to_owned()));
+    }
+
+    let authorization = sign(
+        &credentials,
+        &region,
+        SERVICE,
+        &path,
+        &amz_date,
+        &headers,
+        &body,
+    );
+
+    let mut request = reqwest::Client::new()
+        .post(format!("https://{}{}", host, path))
+        .header("authorization", authorization);
+    // host comes from the url
+    for (name, value) in headers.iter().filter(|(n, _)| n != "host") {
+        request = request.header(name, value);
+    }
+
+    let response = request
+        .body(body)
+        .send()
+        .await
+        .context("failed to send request")?;
+
+    if !response.status().is_success() {
+        return Err(HttpStatusError::from_response(response)
+            .await
+            .into());
+    }
+
+    let raw = response
+        .text()
+        .await
+        .context("failed to read the response")?;
+    debug_log::record_raw(Provider::Bedrock, "response", &raw);
+
+    anthropic::tool_input(Provider::Bedrock, &raw)
+}
+
+pub fn has_credentials() -> bool {
+    load_credentials().is_ok()
+}
+
+/// env vars first, then the shared credentials
+/// file under AWS_PROFILE (or default)
+fn load_credentials() -> Result<Credentials> {
+    if let (Ok(access_key_id), Ok(secret_access_key)) = (
+        std::env::var("AWS_ACCESS_KEY_ID"),
+        std::env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Ok(Credentials {
+            access_key_id,
+            secret_access_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        });
+    }
+
+    let path = match std::env::var("AWS_SHARED_CREDENTIALS_FILE") {
+        Ok(path) => path.into(),
+        Err(_) => directories::BaseDirs::new()
+            .ok_or_else(|| {
+                anyhow!("Cannot find a valid home directory.")
+            })?
+            .home_dir()
+            .join(".aws")
+            .join("credentials"),
+    };
+
+    let profile = std::env::var("AWS_PROFILE")
+        .unwrap_or_else(|_| "default".to_owned());
+
+    let content =
+        std::fs::read_to_string(&path).with_context(|| {
+            format!(
+                "no AWS credentials in the environment or {}",
+                path.display()
+            )
+        })?;
+
+    let mut in_profile = false;
+    let mut access_key_id = None;
+    let mut secret_access_key = None;
+    let mut session_token = None;
+
+    for line in content.lines().map(str::trim) {
+        if let Some(section) =
+            line.strip_prefix('[').and_then(|l| l.strip_suffix(']'))
+        {
+            in_profile = section.trim() == profile;
+            continue;
+        }
+
+        if !in_profile {
+            continue;
+        }
+
+        if let Some((key, value)) = line.split_once('=') {
+            let value = Some(value.trim().to_owned());
+            match key.trim() {
+                "aws_access_key_id" => access_key_id = value,
+                "aws_secret_access_key" => secret_access_key = value,
+                "aws_session_token" => session_token = value,
+                _ => {}
+            }
+        }
+    }
+
+    match (access_key_id, secret_access_key) {
+        (Some(access_key_id), Some(secret_access_key)) => {
+            Ok(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token,
+            })
+        }
+        _ => Err(anyhow!(
+            "AWS profile '{}' in {} has no access keys",
+            profile,
+            path.display()
+        )),
+    }
+}
+
+/// SigV4 of a POST, returns the authorization header. headers
+/// are lowercase and have to include host and x-amz-date
+/// https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv-create-signed-request.html
+fn sign(
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+    path: &str,
+    amz_date: &str,
+    headers: &[(String, String)],
+    body: &[u8],
+) -> String {
+    let date = &amz_date[..8];
+
+    // canonical headers are sorted by name
+    let mut headers: Vec<(&str, &str)> = headers
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.trim()))
+        .collect();
+    headers.sort();
+
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    // everything but s3 encodes the path twice
+    let canonical_request = format!(
+        "POST\n{}\n\n{}\n{}\n{}",
+        uri_encode(path).replace("%2F", "/"),
+        canonical_headers,
+        signed_headers,
+        hex(&Sha256::digest(body))
+    );
+
+    let scope =
+        format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hex(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = signing_key(
+        &credentials.secret_access_key,
+        date,
+        region,
+        service,
+    );
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    )
+}
+
+fn signing_key(
+    secret_access_key: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+) -> Vec<u8> {
+    [date, region, service, "aws4_request"].iter().fold(
+        format!("AWS4{}", secret_access_key).into_bytes(),
+        |key, part| hmac(&key, part.as_bytes()),
+    )
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // any key length works for hmac
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .expect("hmac takes keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// percent encodes everything but the unreserved characters
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'_'
+            | b'.'
+            | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the example credentials from aws' docs and sigv4 test suite
+    const SECRET: &str = "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY";
+
+    #[test]
+    fn signing_key_matches_the_aws_example() {
+        let key = signing_key(SECRET, "20120215", "us-east-1", "iam");
+
+        assert_eq!(
+            hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn sign_matches_the_post_vanilla_example() {
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_owned(),
+            secret_access_key: SECRET.to_owned(),
+            session_token: None,
+        };
+        let amz_date = "20150830T123600Z";
+        // out of order, sign sorts them
+        let headers = vec![
+            ("x-amz-date".to_owned(), amz_date.to_owned()),
+            ("host".to_owned(), "example.amazonaws.com".to_owned()),
+        ];
+
+        let authorization = sign(
+            &credentials,
+            "us-east-1",
+            "service",
+            "/",
+            amz_date,
+            &headers,
+            b"",
+        );
+
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::ai::{
+    provider::Provider, request::Request, response::Response,
+};
+
+/// what gets written to disk per response
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    /// unix seconds
+    created_at: u64,
+    response: Response,
+}
+
+/// hash of everything that changes the response,
+/// DefaultHasher isn't stable across rust releases
+/// but the worst case is a cache miss
+pub fn cache_key(
+    req: &Request,
+    provider: Provider,
+    model: &str,
+    system_prompt: Option<&str>,
+) -> String {
+    let mut hasher = DefaultHasher::new();
+    req.diffs.hash(&mut hasher);
+    req.prompt.hash(&mut hasher);
+    for file in &req.large_files {
+        file.path.hash(&mut hasher);
+        file.diff.hash(&mut hasher);
+    }
+    provider.to_string().hash(&mut hasher);
+    model.hash(&mut hasher);
+    system_prompt.hash(&mut hasher);
+
+    format!("{:016x}", hasher.finish())
+}
+
+/// a cached response for the key,
+/// only if it's younger than ttl_secs
+pub fn load(key: &str, ttl_secs: u64) -> Option<Response> {
+    if ttl_secs == 0 {
+        return None;
+    }
+
+    let path = cache_dir().ok()?.join(format!("{}.json", key));
+    let content = fs::read_to_string(&path).ok()?;
+    let entry: CacheEntry = serde_json::from_str(&content).ok()?;
+
+    if now().saturating_sub(entry.created_at) > ttl_secs {
+        let _ = fs::remove_file(&path);
+        return None;
+    }
+
+    Some(entry.response)
+}
+
+/// only successful responses are worth keeping,
+/// expired entries get cleaned up along the way
+pub fn store(
+    key: &str,
+    response: &Response,
+    ttl_secs: u64,
+) -> Result<()> {
+    if ttl_secs == 0 || response.result.is_err() {
+        return Ok(());
+    }
+
+    // edits aren't serialized, an unparsed
+    // answer would come back empty
+    if let Ok(res) = &response.result
+        && res.commits.iter().any(|c| c.message.edited.is_some())
+    {
+        return Ok(());
+    }
+
+    let dir = cache_dir()?;
+    fs::create_dir_all(&dir)?;
+    prune(&dir, ttl_secs);
+
+    let entry = CacheEntry {
+        created_at: now(),
+        response: Response {
+            result: response.result.clone(),
+            provider: response.provider,
+            error_kind: None,
+            raw: response.raw.clone(),
+        },
+    };
+
+    fs::write(
+        dir.join(format!("{}.json", key)),
+        serde_json::to_string(&entry)?,
+    )?;
+
+    Ok(())
+}
+
+fn prune(dir: &PathBuf, ttl_secs: u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+
+        let expired = fs::read_to_string(&path)
+            .ok()
+            .and_then(|c| serde_json::from_str::<CacheEntry>(&c).ok())
+            .is_none_or(|e| {
+                now().saturating_sub(e.created_at) > ttl_secs
+            });
+
+        if expired {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    let dirs =
+        directories::ProjectDirs::from("com", "nuttycream", "gai")
+            .ok_or_else(|| {
+                anyhow!("Can't find the cache directory")
+            })?;
+
+    Ok(dirs.cache_dir().join("responses"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,163 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Value, json};
+
+use crate::{
+    ai::{
+        debug_log,
+        provider::{
+            HttpStatusError, InvalidResponse, Provider, parse_schema,
+            preamble, response_schema, sampling_params, with_headers,
+        },
+        response::ResponseSchema,
+    },
+    config::ProviderConfig,
+    consts::COHERE_URL,
+};
+
+/// cohere's own chat api, base_url in the
+/// provider config wins over COHERE_URL
+/// {base_url}/v2/chat
+pub async fn chat(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+) -> Result<ResponseSchema> {
+    if provider_cfg.model.is_empty() {
+        return Err(anyhow!(
+            "Cohere needs a model in its provider config"
+        ));
+    }
+
+    let api_key = api_key(provider_cfg)?;
+    let request_body = chat_body(prompt, provider_cfg, diffs)?;
+
+    debug_log::record(
+        Provider::Cohere,
+        "request",
+        request_body.clone(),
+    );
+
+    let request = reqwest::Client::new()
+        .post(format!("{}/v2/chat", base_url(provider_cfg)))
+        .header("Authorization", format!("Bearer {}", api_key))
+        .json(&request_body);
+    let response = with_headers(request, provider_cfg)?
+        .send()
+        .await
+        .context("failed to send request")?;
+
+    if !response.status().is_success() {
+        return Err(HttpStatusError::from_response(response)
+            .await
+            .into());
+    }
+
+    let raw = response
+        .text()
+        .await
+        .context("failed to read the response")?;
+    debug_log::record_raw(Provider::Cohere, "response", &raw);
+
+    let response_json: Value =
+        serde_json::from_str(&raw).map_err(|e| {
+            InvalidResponse(format!(
+                "Failed to parse response JSON: {}",
+                e
+            ))
+        })?;
+
+    let generated_text = response_json
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(message_text)
+        .ok_or_else(|| {
+            InvalidResponse(
+                "Invalid response format from Cohere".to_owned(),
+            )
+        })?;
+
+    parse_schema(&generated_text)
+}
+
+/// the v2/chat request, the preamble is the system message
+pub fn chat_body(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+) -> Result<Value> {
+    let mut request_body = json!({
+        "model": provider_cfg.model,
+        "messages": [
+            {
+                "role": "system",
+                "content": preamble(prompt, provider_cfg)
+            },
+            { "role": "user", "content": diffs }
+        ],
+        "response_format": {
+            "type": "json_object",
+            "json_schema": response_schema()?
+        },
+        "max_tokens": provider_cfg.max_tokens
+    });
+
+    if let Some(body) = request_body.as_object_mut() {
+        body.extend(sampling_params(provider_cfg, "p"));
+    }
+
+    Ok(request_body)
+}
+
+pub fn base_url(provider_cfg: &ProviderConfig) -> &str {
+    provider_cfg
+        .base_url
+        .as_deref()
+        .unwrap_or(COHERE_URL)
+        .trim_end_matches('/')
+}
+
+/// the provider config's api_key, then CO_API_KEY
+pub fn api_key(provider_cfg: &ProviderConfig) -> Result<String> {
+    match &provider_cfg.api_key {
+        Some(key) => Ok(key.to_owned()),
+        None => std::env::var("CO_API_KEY").context(
+            "Cohere needs an api_key in its provider config or CO_API_KEY",
+        ),
+    }
+}
+
+/// the text blocks of message.content, the
+/// rest (ex. tool plans) is skipped
+fn message_text(content: &Value) -> Option<String> {
+    let text: String = content
+        .as_array()?
+        .iter()
+        .filter(|block| {
+            block.get("type").and_then(|t| t.as_str()) == Some("text")
+        })
+        .filter_map(|block| {
+            block.get("text").and_then(|t| t.as_str())
+        })
+        .collect();
+
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_sends_the_system_prompt() {
+        let mut cfg = ProviderConfig::new("command-r");
+        cfg.system_prompt = Some("never use emoji".to_owned());
+        let body = chat_body("prompt", &cfg, "diffs").unwrap();
+
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(
+            body["messages"][0]["content"],
+            "never use emoji\n\nprompt"
+        );
+        assert_eq!(body["messages"][1]["content"], "diffs");
+    }
+}
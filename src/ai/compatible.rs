@@ -0,0 +1,407 @@
+use anyhow::{Context, Result, anyhow};
+use tokio::sync::mpsc;
+
+use crate::{
+    ai::{
+        debug_log,
+        provider::{
+            HttpStatusError, InvalidResponse, Provider,
+            is_event_stream, parse_schema, preamble, read_sse,
+            response_schema, sampling_params, with_headers,
+        },
+        response::{ResponseEvent, ResponseSchema},
+    },
+    config::ProviderConfig,
+    consts::{
+        DEEPSEEK_URL, GROQ_URL, MISTRAL_URL, OPENAI_URL,
+        OPENROUTER_URL,
+    },
+};
+
+/// what the endpoint supports for structured output
+#[derive(Clone, Copy)]
+pub enum ResponseFormat {
+    JsonSchema,
+    /// any json, the schema is described in the prompt
+    JsonObject,
+}
+
+/// an openai style /chat/completions api, base_url
+/// and api_key in the provider config win over these
+pub struct Endpoint {
+    /// None when there's no sensible default,
+    /// ex. a local vLLM or LM Studio server
+    pub base_url: Option<&'static str>,
+    /// None makes the key optional, local
+    /// servers usually don't check it
+    pub api_key_env: Option<&'static str>,
+    pub format: ResponseFormat,
+    /// openai's reasoning models only take
+    /// max_completion_tokens
+    pub max_tokens_key: &'static str,
+}
+
+impl Endpoint {
+    /// the presets, None for providers
+    /// that don't speak chat completions
+    pub fn for_provider(provider: Provider) -> Option<Self> {
+        let endpoint = match provider {
+            Provider::OpenAI => Endpoint {
+                base_url: Some(OPENAI_URL),
+                api_key_env: Some("OPENAI_API_KEY"),
+                format: ResponseFormat::JsonSchema,
+                max_tokens_key: "max_completion_tokens",
+            },
+            // the model picks the vendor,
+            // ex. anthropic/claude-3.5-sonnet
+            Provider::OpenRouter => Endpoint {
+                base_url: Some(OPENROUTER_URL),
+                api_key_env: Some("OPENROUTER_API_KEY"),
+                format: ResponseFormat::JsonSchema,
+                max_tokens_key: "max_tokens",
+            },
+            // fast but with tight rate
+            // limits, 429s carry a retry-after
+            Provider::Groq => Endpoint {
+                base_url: Some(GROQ_URL),
+                api_key_env: Some("GROQ_API_KEY"),
+                format: ResponseFormat::JsonSchema,
+                max_tokens_key: "max_tokens",
+            },
+            // only has json mode
+            Provider::DeepSeek => Endpoint {
+                base_url: Some(DEEPSEEK_URL),
+                api_key_env: Some("DEEPSEEK_API_KEY"),
+                format: ResponseFormat::JsonObject,
+                max_tokens_key: "max_tokens",
+            },
+            // safe_prompt isn't sent, it prepends mistral's
+            // own guardrail prompt ahead of ours
+            Provider::Mistral => Endpoint {
+                base_url: Some(MISTRAL_URL),
+                api_key_env: Some("MISTRAL_API_KEY"),
+                format: ResponseFormat::JsonSchema,
+                max_tokens_key: "max_tokens",
+            },
+            Provider::OpenAICompatible => Endpoint {
+                base_url: None,
+                api_key_env: None,
+                format: ResponseFormat::JsonSchema,
+                max_tokens_key: "max_tokens",
+            },
+            _ => return None,
+        };
+
+        Some(endpoint)
+    }
+
+    /// the provider config's base_url, then the preset
+    pub fn base_url<'a>(
+        &self,
+        provider: Provider,
+        provider_cfg: &'a ProviderConfig,
+    ) -> Result<&'a str> {
+        provider_cfg
+            .base_url
+            .as_deref()
+            .or(self.base_url)
+            .map(|url| url.trim_end_matches('/'))
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} needs a base_url in its provider config, ex. http://localhost:1234/v1",
+                    provider
+                )
+            })
+    }
+
+    /// the provider config's api_key, then the env var
+    pub fn api_key(
+        &self,
+        provider: Provider,
+        provider_cfg: &ProviderConfig,
+    ) -> Result<Option<String>> {
+        match (&provider_cfg.api_key, self.api_key_env) {
+            (Some(key), _) => Ok(Some(key.to_owned())),
+            (None, Some(env)) => {
+                Ok(Some(std::env::var(env).with_context(|| {
+                    format!(
+                        "{} needs an api_key in its provider config or {}",
+                        provider, env
+                    )
+                })?))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// {base_url}/chat/completions with a bearer
+/// key, when there is one
+pub async fn complete(
+    provider: Provider,
+    endpoint: &Endpoint,
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> Result<ResponseSchema> {
+    let base_url = endpoint.base_url(provider, provider_cfg)?;
+
+    if provider_cfg.model.is_empty() {
+        return Err(anyhow!(
+            "{} needs a model in its provider config",
+            provider
+        ));
+    }
+
+    let api_key = endpoint.api_key(provider, provider_cfg)?;
+
+    let url = format!("{}/chat/completions", base_url);
+
+    let mut request = reqwest::Client::new().post(url);
+    if let Some(api_key) = api_key {
+        request = request
+            .header("Authorization", format!("Bearer {}", api_key));
+    }
+
+    chat_completion(
+        provider,
+        request,
+        prompt,
+        provider_cfg,
+        diffs,
+        endpoint.format,
+        endpoint.max_tokens_key,
+        tx,
+    )
+    .await
+}
+
+/// openai style /chat/completions, url and auth are up
+/// to the caller. with tx the response is streamed
+#[allow(clippy::too_many_arguments)]
+pub async fn chat_completion(
+    provider: Provider,
+    request: reqwest::RequestBuilder,
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    format: ResponseFormat,
+    max_tokens_key: &str,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> Result<ResponseSchema> {
+    let request_body = chat_completion_body(
+        prompt,
+        provider_cfg,
+        diffs,
+        format,
+        max_tokens_key,
+        tx.is_some(),
+    )?;
+
+    debug_log::record(provider, "request", request_body.clone());
+
+    let response =
+        with_headers(request.json(&request_body), provider_cfg)?
+            .send()
+            .await
+            .context("failed to send request")?;
+
+    if !response.status().is_success() {
+        return Err(HttpStatusError::from_response(response)
+            .await
+            .into());
+    }
+
+    if let Some(tx) = tx
+        && is_event_stream(&response)
+    {
+        let mut generated = String::new();
+        let raw = read_sse(response, tx, |data| {
+            let text = stream_delta(provider, data)?;
+            if let Some(text) = &text {
+                generated.push_str(text);
+            }
+
+            Ok(text)
+        })
+        .await?;
+        debug_log::record_raw(provider, "response", &raw);
+
+        return parse_schema(&generated);
+    }
+
+    let raw = response
+        .text()
+        .await
+        .context("failed to read the response")?;
+    debug_log::record_raw(provider, "response", &raw);
+
+    let response_json: serde_json::Value = serde_json::from_str(&raw)
+        .map_err(|e| {
+            InvalidResponse(format!(
+                "Failed to parse response JSON: {}",
+                e
+            ))
+        })?;
+
+    let generated_text = response_json
+        .get("choices")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("message"))
+        .and_then(|m| m.get("content"))
+        .and_then(message_text)
+        .ok_or_else(|| {
+            InvalidResponse(
+                "Invalid chat completion response format".to_owned(),
+            )
+        })?;
+
+    parse_schema(&generated_text)
+}
+
+/// the chat completions request, JsonObject puts the
+/// schema in the system message instead
+fn chat_completion_body(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    format: ResponseFormat,
+    max_tokens_key: &str,
+    stream: bool,
+) -> Result<serde_json::Value> {
+    let mut system = preamble(prompt, provider_cfg).into_owned();
+
+    let response_format = match format {
+        ResponseFormat::JsonSchema => serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": "response_schema",
+                // strict mode wants additionalProperties
+                // on everything, which schemars doesn't add
+                "strict": false,
+                "schema": response_schema()?
+            }
+        }),
+        ResponseFormat::JsonObject => {
+            system.push_str(&format!(
+                "\n\nRespond with a single JSON object matching this JSON schema:\n{}",
+                response_schema()?
+            ));
+
+            serde_json::json!({ "type": "json_object" })
+        }
+    };
+
+    let mut request_body = serde_json::json!({
+        "model": provider_cfg.model,
+        "messages": [
+            { "role": "system", "content": system },
+            { "role": "user", "content": diffs }
+        ],
+        "response_format": response_format
+    });
+
+    if let Some(body) = request_body.as_object_mut() {
+        body.insert(
+            max_tokens_key.to_owned(),
+            provider_cfg.max_tokens.into(),
+        );
+        body.extend(sampling_params(provider_cfg, "top_p"));
+        if stream {
+            body.insert("stream".to_owned(), true.into());
+        }
+    }
+
+    Ok(request_body)
+}
+
+/// the text of one streamed chunk, None for the
+/// ones without any, ex. the role or [DONE]
+fn stream_delta(
+    provider: Provider,
+    data: &str,
+) -> Result<Option<String>> {
+    if data == "[DONE]" {
+        return Ok(None);
+    }
+
+    let chunk: serde_json::Value = serde_json::from_str(data)
+        .map_err(|e| {
+            InvalidResponse(format!(
+                "Failed to parse streamed JSON: {}",
+                e
+            ))
+        })?;
+
+    // some servers report a failure mid stream
+    if let Some(error) = chunk.get("error") {
+        return Err(InvalidResponse(format!(
+            "{} sent an error while streaming: {}",
+            provider, error
+        ))
+        .into());
+    }
+
+    Ok(chunk
+        .get("choices")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("delta"))
+        .and_then(|d| d.get("content"))
+        .and_then(message_text))
+}
+
+/// content is usually a string, but mistral's reasoning
+/// models send a list of chunks, only the text ones count
+fn message_text(content: &serde_json::Value) -> Option<String> {
+    if let Some(text) = content.as_str() {
+        return Some(text.to_owned());
+    }
+
+    let text: String = content
+        .as_array()?
+        .iter()
+        .filter(|chunk| {
+            chunk.get("type").and_then(|t| t.as_str()) == Some("text")
+        })
+        .filter_map(|chunk| {
+            chunk.get("text").and_then(|t| t.as_str())
+        })
+        .collect();
+
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chat_completion_sends_the_system_prompt() {
+        let mut cfg = ProviderConfig::new("gpt");
+        cfg.system_prompt = Some("never use emoji".to_owned());
+
+        for format in
+            [ResponseFormat::JsonSchema, ResponseFormat::JsonObject]
+        {
+            let body = chat_completion_body(
+                "prompt",
+                &cfg,
+                "diffs",
+                format,
+                "max_tokens",
+                false,
+            )
+            .unwrap();
+
+            assert_eq!(body["messages"][0]["role"], "system");
+            assert!(
+                body["messages"][0]["content"]
+                    .as_str()
+                    .unwrap()
+                    .starts_with("never use emoji\n\nprompt")
+            );
+            assert_eq!(body["messages"][1]["content"], "diffs");
+        }
+    }
+}
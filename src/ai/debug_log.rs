@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use serde_json::{Value, json};
+use std::{
+    cell::RefCell,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ai::{provider::Provider, response::ResponseSchema},
+    auth::get_token,
+    config::{Config, ProviderConfig},
+};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// keys whose values never make it into the log,
+/// compared case insensitively
+const SENSITIVE_KEYS: &[&str] = &[
+    "api_key",
+    "api-key",
+    "apikey",
+    "authorization",
+    "x-api-key",
+    "token",
+    "access_token",
+    "secret_access_key",
+    "session_token",
+    "password",
+];
+
+/// env vars the providers read their keys from
+const SECRET_ENV_VARS: &[&str] = &[
+    "OPENAI_API_KEY",
+    "ANTHROPIC_API_KEY",
+    "GEMINI_API_KEY",
+    "AZURE_OPENAI_API_KEY",
+    "OPENROUTER_API_KEY",
+    "GROQ_API_KEY",
+    "DEEPSEEK_API_KEY",
+    "MISTRAL_API_KEY",
+    "CO_API_KEY",
+    "AWS_ACCESS_KEY_ID",
+    "AWS_SECRET_ACCESS_KEY",
+    "AWS_SESSION_TOKEN",
+];
+
+struct DebugLog {
+    file: File,
+    /// longest first, so a key that contains
+    /// another is replaced whole
+    secrets: Vec<String>,
+}
+
+impl DebugLog {
+    fn add_secret(&mut self, secret: &str) {
+        let secret = secret.trim();
+        if secret.is_empty()
+            || self.secrets.iter().any(|s| s == secret)
+        {
+            return;
+        }
+
+        self.secrets.push(secret.to_owned());
+        self.secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+static LOG: OnceLock<Mutex<DebugLog>> = OnceLock::new();
+
+tokio::task_local! {
+    /// the last response body inside capture_raw
+    static RAW: RefCell<Option<String>>;
+}
+
+/// appends to path for the rest of the run, every
+/// secret we know of is collected up front for redaction
+pub fn init(path: &Path, cfg: &Config) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| {
+            format!("can't open the debug log {}", path.display())
+        })?;
+
+    let mut log = DebugLog {
+        file,
+        secrets: Vec::new(),
+    };
+
+    for secret in cfg
+        .ai
+        .providers
+        .values()
+        .filter_map(|p| p.api_key.to_owned())
+        .chain(
+            SECRET_ENV_VARS
+                .iter()
+                .filter_map(|var| std::env::var(var).ok()),
+        )
+        .chain(get_token().ok())
+    {
+        log.add_secret(&secret);
+    }
+
+    let _ = LOG.set(Mutex::new(log));
+
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    LOG.get().is_some()
+}
+
+/// one json line, ex. {"time":..,"provider":"OpenAI",
+/// "kind":"request","body":{..}}, a no-op without --debug-log
+pub fn record(provider: Provider, kind: &str, body: Value) {
+    let Some(log) = LOG.get() else {
+        return;
+    };
+
+    let Ok(mut log) = log.lock() else {
+        return;
+    };
+
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let mut entry = json!({
+        "time": time,
+        "provider": provider,
+        "kind": kind,
+        "body": body,
+    });
+    redact(&mut entry, &log.secrets);
+
+    // the walk covers values, this catches
+    // anything that only shows up once serialized
+    let mut line = entry.to_string();
+    for secret in &log.secrets {
+        line = line.replace(secret.as_str(), REDACTED);
+    }
+
+    // a failed write shouldn't fail the request
+    let _ = writeln!(log.file, "{}", line);
+}
+
+/// the prompt as the provider gets it, rig builds the body
+/// itself for gemini so this is all it logs.
+/// the key in use is added to the secrets first, it
+/// may have come from a -c override after init
+pub fn record_prompt(
+    provider: Provider,
+    system: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+) {
+    let Some(log) = LOG.get() else {
+        return;
+    };
+
+    if let Some(api_key) = provider_cfg.api_key.as_deref()
+        && let Ok(mut log) = log.lock()
+    {
+        log.add_secret(api_key);
+    }
+
+    record(
+        provider,
+        "prompt",
+        json!({
+            "model": provider_cfg.model,
+            "system": system,
+            "diffs": diffs,
+        }),
+    );
+}
+
+/// raw response bodies are logged as json when
+/// they parse, otherwise as the plain text.
+/// responses are kept for capture_raw either way
+pub fn record_raw(provider: Provider, kind: &str, raw: &str) {
+    if kind == "response" {
+        let _ = RAW.try_with(|last| {
+            *last.borrow_mut() = Some(raw.to_owned())
+        });
+    }
+
+    if !enabled() {
+        return;
+    }
+
+    let body = serde_json::from_str(raw)
+        .unwrap_or_else(|_| Value::String(raw.to_owned()));
+    record(provider, kind, body);
+}
+
+/// runs fut along with the last response body it got,
+/// retries overwrite it, futures running side by side
+/// each keep their own
+pub async fn capture_raw<F: Future>(
+    fut: F,
+) -> (F::Output, Option<String>) {
+    RAW.scope(RefCell::new(None), async {
+        let output = fut.await;
+        (output, RAW.with(|last| last.take()))
+    })
+    .await
+}
+
+/// what the request ended with, parsed
+/// commits or the full error chain
+pub fn record_result(
+    provider: Provider,
+    result: &Result<ResponseSchema>,
+) {
+    if !enabled() {
+        return;
+    }
+
+    match result {
+        Ok(schema) => record(
+            provider,
+            "result",
+            serde_json::to_value(schema).unwrap_or_default(),
+        ),
+        Err(e) => {
+            record(provider, "error", json!(format!("{:#}", e)))
+        }
+    }
+}
+
+fn redact(value: &mut Value, secrets: &[String]) {
+    match value {
+        Value::String(s) => {
+            for secret in secrets {
+                if s.contains(secret.as_str()) {
+                    *s = s.replace(secret.as_str(), REDACTED);
+                }
+            }
+        }
+        Value::Array(values) => {
+            values.iter_mut().for_each(|v| redact(v, secrets))
+        }
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if SENSITIVE_KEYS
+                    .iter()
+                    .any(|k| k.eq_ignore_ascii_case(key))
+                {
+                    *v = Value::String(REDACTED.to_owned());
+                } else {
+                    redact(v, secrets);
+                }
+            }
+        }
+        _ => {}
+    }
+}
@@ -1,3 +1,10 @@
+pub mod anthropic;
+pub mod bedrock;
+pub mod cache;
+pub mod cohere;
+pub mod compatible;
+pub mod debug_log;
+pub mod models;
 pub mod provider;
 pub mod request;
 pub mod response;
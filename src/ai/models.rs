@@ -0,0 +1,187 @@
+use anyhow::{Context, Result, anyhow};
+use std::time::Duration;
+
+use crate::{
+    ai::{
+        cohere,
+        compatible::Endpoint,
+        provider::{
+            HttpStatusError, InvalidResponse, Provider, with_headers,
+        },
+    },
+    config::ProviderConfig,
+    consts::{
+        AZURE_MODELS, BEDROCK_MODELS, CLAUDE_MODELS, GEMINI_MODELS,
+        OLLAMA_HOST,
+    },
+};
+
+/// models the provider says it serves, sorted, the
+/// ones we can't ask get the curated list instead
+pub async fn list_models(
+    provider: Provider,
+    provider_cfg: &ProviderConfig,
+) -> Result<Vec<String>> {
+    let mut models = match provider {
+        Provider::Ollama => ollama_tags(provider_cfg).await?,
+        Provider::Cohere => cohere_models(provider_cfg).await?,
+        _ => match Endpoint::for_provider(provider) {
+            Some(endpoint) => {
+                endpoint_models(provider, &endpoint, provider_cfg)
+                    .await?
+            }
+            None => curated_models(provider)
+                .iter()
+                .map(|m| m.to_string())
+                .collect(),
+        },
+    };
+
+    models.sort();
+    models.dedup();
+
+    if models.is_empty() {
+        return Err(anyhow!("{} didn't list any models", provider));
+    }
+
+    Ok(models)
+}
+
+pub fn curated_models(provider: Provider) -> &'static [&'static str] {
+    match provider {
+        Provider::Gemini | Provider::Gai => GEMINI_MODELS,
+        Provider::Claude => CLAUDE_MODELS,
+        Provider::AzureOpenAI => AZURE_MODELS,
+        Provider::Bedrock => BEDROCK_MODELS,
+        _ => &[],
+    }
+}
+
+/// GET {base_url}/models, the openai style list
+async fn endpoint_models(
+    provider: Provider,
+    endpoint: &Endpoint,
+    provider_cfg: &ProviderConfig,
+) -> Result<Vec<String>> {
+    let base_url = endpoint.base_url(provider, provider_cfg)?;
+
+    let mut request = reqwest::Client::new()
+        .get(format!("{}/models", base_url))
+        .timeout(Duration::from_secs(provider_cfg.timeout_secs));
+    if let Some(api_key) = endpoint.api_key(provider, provider_cfg)? {
+        request = request
+            .header("Authorization", format!("Bearer {}", api_key));
+    }
+
+    let json = get_json(with_headers(request, provider_cfg)?).await?;
+
+    json.get("data")
+        .and_then(|d| d.as_array())
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| {
+                    m.get("id").and_then(|id| id.as_str())
+                })
+                .map(str::to_owned)
+                .collect()
+        })
+        .ok_or_else(|| {
+            InvalidResponse(format!(
+                "Invalid model list format from {}",
+                provider
+            ))
+            .into()
+        })
+}
+
+/// the models pulled locally, GET /api/tags
+async fn ollama_tags(
+    provider_cfg: &ProviderConfig,
+) -> Result<Vec<String>> {
+    let host = provider_cfg
+        .base_url
+        .as_deref()
+        .unwrap_or(OLLAMA_HOST)
+        .trim_end_matches('/');
+
+    let request = reqwest::Client::new()
+        .get(format!("{}/api/tags", host))
+        .timeout(Duration::from_secs(provider_cfg.timeout_secs));
+
+    let json = get_json(with_headers(request, provider_cfg)?).await?;
+
+    json.get("models")
+        .and_then(|m| m.as_array())
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| {
+                    m.get("name").and_then(|n| n.as_str())
+                })
+                .map(str::to_owned)
+                .collect()
+        })
+        .ok_or_else(|| {
+            InvalidResponse(
+                "Invalid model list format from Ollama".to_owned(),
+            )
+            .into()
+        })
+}
+
+/// the ones that can chat,
+/// GET {base_url}/v1/models?endpoint=chat
+async fn cohere_models(
+    provider_cfg: &ProviderConfig,
+) -> Result<Vec<String>> {
+    let request = reqwest::Client::new()
+        .get(format!("{}/v1/models", cohere::base_url(provider_cfg)))
+        .query(&[("endpoint", "chat")])
+        .header(
+            "Authorization",
+            format!("Bearer {}", cohere::api_key(provider_cfg)?),
+        )
+        .timeout(Duration::from_secs(provider_cfg.timeout_secs));
+
+    let json = get_json(with_headers(request, provider_cfg)?).await?;
+
+    json.get("models")
+        .and_then(|m| m.as_array())
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| {
+                    m.get("name").and_then(|n| n.as_str())
+                })
+                .map(str::to_owned)
+                .collect()
+        })
+        .ok_or_else(|| {
+            InvalidResponse(
+                "Invalid model list format from Cohere".to_owned(),
+            )
+            .into()
+        })
+}
+
+async fn get_json(
+    request: reqwest::RequestBuilder,
+) -> Result<serde_json::Value> {
+    let response =
+        request.send().await.context("failed to list models")?;
+
+    if !response.status().is_success() {
+        return Err(HttpStatusError::from_response(response)
+            .await
+            .into());
+    }
+
+    response.json().await.map_err(|e| {
+        InvalidResponse(format!(
+            "Failed to parse response JSON: {}",
+            e
+        ))
+        .into()
+    })
+}
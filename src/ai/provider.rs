@@ -1,27 +1,41 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
+use reqwest::{
+    RequestBuilder,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
 use rig::{
-    client::{CompletionClient, ProviderClient},
-    providers::{
-        anthropic,
-        gemini::{
-            self,
-            completion::gemini_api_types::{
-                AdditionalParameters, GenerationConfig,
-            },
+    client::CompletionClient,
+    providers::gemini::{
+        self,
+        completion::gemini_api_types::{
+            AdditionalParameters, GenerationConfig, ThinkingConfig,
         },
-        openai,
     },
 };
 use schemars::generate::SchemaSettings;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, fmt, time::Duration};
 use strum::{Display, EnumIter, IntoEnumIterator};
+use tokio::sync::mpsc;
 
 use crate::{
-    ai::response::ResponseSchema,
+    ai::{
+        anthropic, bedrock, cohere,
+        compatible::{
+            self, Endpoint, ResponseFormat, chat_completion,
+        },
+        debug_log,
+        response::{ResponseEvent, ResponseSchema},
+    },
     auth::get_token,
     config::ProviderConfig,
-    consts::{CHATGPT_DEFAULT, CLAUDE_DEFAULT, GEMINI_DEFAULT},
+    consts::{
+        AZURE_API_VERSION, BEDROCK_DEFAULT, CHATGPT_DEFAULT,
+        CLAUDE_DEFAULT, COHERE_DEFAULT, DEEPSEEK_DEFAULT,
+        GEMINI_DEFAULT, GEMINI_THINKING_BUDGET, GROQ_DEFAULT,
+        MISTRAL_DEFAULT, OLLAMA_DEFAULT, OLLAMA_HOST,
+        OPENROUTER_DEFAULT,
+    },
 };
 
 #[derive(
@@ -35,13 +49,26 @@ use crate::{
     Display,
     Serialize,
     Deserialize,
-    clap::ValueEnum,
 )]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
 pub enum Provider {
     OpenAI,
     Gemini,
     Claude,
     Gai,
+    Ollama,
+    #[cfg_attr(feature = "cli", value(alias = "azure"))]
+    AzureOpenAI,
+    OpenRouter,
+    Bedrock,
+    Groq,
+    DeepSeek,
+    Mistral,
+    Cohere,
+    /// any openai style endpoint, ex. LM Studio,
+    /// vLLM or Together, needs a base_url
+    #[cfg_attr(feature = "cli", value(alias = "compatible"))]
+    OpenAICompatible,
 }
 
 impl Provider {
@@ -49,6 +76,37 @@ impl Provider {
         format!("{} ({})", self, model)
     }
 
+    /// where the key comes from without an api_key
+    /// in the provider config, None when there's no key
+    /// or it's not an env var, ex. gai's token
+    pub fn api_key_env(&self) -> Option<&'static str> {
+        match self {
+            Provider::Gemini => Some("GEMINI_API_KEY"),
+            Provider::Claude => Some("ANTHROPIC_API_KEY"),
+            Provider::AzureOpenAI => Some("AZURE_OPENAI_API_KEY"),
+            Provider::Cohere => Some("CO_API_KEY"),
+            _ => Endpoint::for_provider(*self)?.api_key_env,
+        }
+    }
+
+    /// whether a request would have something to
+    /// authenticate with, nothing is sent to check
+    pub fn has_credentials(
+        &self,
+        provider_cfg: &ProviderConfig,
+    ) -> bool {
+        match self {
+            Provider::Gai => get_token().is_ok(),
+            Provider::Bedrock => bedrock::has_credentials(),
+            _ => {
+                provider_cfg.api_key.is_some()
+                    || self
+                        .api_key_env()
+                        .is_none_or(|env| std::env::var(env).is_ok())
+            }
+        }
+    }
+
     pub fn create_defaults() -> HashMap<Provider, ProviderConfig> {
         let mut providers = HashMap::new();
         for provider in Provider::iter() {
@@ -69,6 +127,45 @@ impl Provider {
                     provider,
                     ProviderConfig::new(GEMINI_DEFAULT),
                 ),
+                Provider::Ollama => {
+                    let mut cfg = ProviderConfig::new(OLLAMA_DEFAULT);
+                    cfg.base_url = Some(OLLAMA_HOST.to_owned());
+                    providers.insert(provider, cfg)
+                }
+                Provider::AzureOpenAI => {
+                    let mut cfg =
+                        ProviderConfig::new(CHATGPT_DEFAULT);
+                    cfg.api_version =
+                        Some(AZURE_API_VERSION.to_owned());
+                    providers.insert(provider, cfg)
+                }
+                Provider::OpenRouter => providers.insert(
+                    provider,
+                    ProviderConfig::new(OPENROUTER_DEFAULT),
+                ),
+                Provider::Bedrock => providers.insert(
+                    provider,
+                    ProviderConfig::new(BEDROCK_DEFAULT),
+                ),
+                Provider::Groq => providers.insert(
+                    provider,
+                    ProviderConfig::new(GROQ_DEFAULT),
+                ),
+                Provider::DeepSeek => providers.insert(
+                    provider,
+                    ProviderConfig::new(DEEPSEEK_DEFAULT),
+                ),
+                Provider::Mistral => providers.insert(
+                    provider,
+                    ProviderConfig::new(MISTRAL_DEFAULT),
+                ),
+                Provider::Cohere => providers.insert(
+                    provider,
+                    ProviderConfig::new(COHERE_DEFAULT),
+                ),
+                // the model depends on whatever is being served
+                Provider::OpenAICompatible => providers
+                    .insert(provider, ProviderConfig::new("")),
             };
         }
 
@@ -78,10 +175,63 @@ impl Provider {
     pub async fn extract(
         &self,
         prompt: &str,
-        model: &str,
-        max_tokens: u64,
+        provider_cfg: &ProviderConfig,
         diffs: &str,
+        prompt_cache: bool,
     ) -> Result<ResponseSchema> {
+        self.send(prompt, provider_cfg, diffs, prompt_cache, None)
+            .await
+    }
+
+    /// same as extract, but forwards partial text over tx.
+    /// ollama, claude and the chat completions providers
+    /// stream, the rest send it all at the end (gemini
+    /// goes through rig's extractor, which can't)
+    pub async fn extract_streamed(
+        &self,
+        prompt: &str,
+        provider_cfg: &ProviderConfig,
+        diffs: &str,
+        prompt_cache: bool,
+        tx: &mpsc::Sender<ResponseEvent>,
+    ) -> Result<ResponseSchema> {
+        self.send(prompt, provider_cfg, diffs, prompt_cache, Some(tx))
+            .await
+    }
+
+    async fn send(
+        &self,
+        prompt: &str,
+        provider_cfg: &ProviderConfig,
+        diffs: &str,
+        prompt_cache: bool,
+        tx: Option<&mpsc::Sender<ResponseEvent>>,
+    ) -> Result<ResponseSchema> {
+        debug_log::record_prompt(
+            *self,
+            &preamble(prompt, provider_cfg),
+            provider_cfg,
+            diffs,
+        );
+        let result = self
+            .request(prompt, provider_cfg, diffs, prompt_cache, tx)
+            .await;
+        debug_log::record_result(*self, &result);
+
+        result
+    }
+
+    async fn request(
+        &self,
+        prompt: &str,
+        provider_cfg: &ProviderConfig,
+        diffs: &str,
+        prompt_cache: bool,
+        tx: Option<&mpsc::Sender<ResponseEvent>>,
+    ) -> Result<ResponseSchema> {
+        let model = provider_cfg.model.as_str();
+        let max_tokens = provider_cfg.max_tokens;
+
         match self {
             Provider::Gai => {
                 // atm rig-core doesn't seem to let us build our own client
@@ -100,71 +250,53 @@ impl Provider {
                 // but whether or not we generate it with schemars
                 // is going to be up to decide later
 
-                let generator = SchemaSettings::draft2020_12()
-                    .with(|s| {
-                        s.meta_schema = None;
-                        s.inline_subschemas = true;
-                    })
-                    .into_generator();
-
-                let schema = generator
-                    .into_root_schema_for::<ResponseSchema>();
-
-                let schema_value = serde_json::to_value(&schema)?;
-
-                let content_text = format!("{}\n\n{}", prompt, diffs);
-
-                let request_body = serde_json::json!({
-                    "contents": [{
-                        "parts": [{
-                            "text": content_text
-                        }]
-                    }],
-                    "generationConfig": {
-                        "responseMimeType": "application/json",
-                        "responseSchema": schema_value,
-                        "maxOutputTokens": max_tokens
-                    }
-                });
+                let request_body =
+                    gai_body(prompt, provider_cfg, diffs)?;
 
                 let auth_token = get_token()?;
 
+                debug_log::record(
+                    *self,
+                    "request",
+                    request_body.clone(),
+                );
+
                 let endpoint = "https://cli.gai.fyi/generate";
 
                 let client = reqwest::Client::new();
-                let response = client
+                let request = client
                     .post(endpoint)
                     .header(
                         "Authorization",
                         format!("Bearer {}", auth_token),
                     )
                     .header("Content-Type", "application/json")
-                    .json(&request_body)
+                    .json(&request_body);
+                let response = with_headers(request, provider_cfg)?
                     .send()
                     .await
-                    .map_err(|e| {
-                        anyhow!("failed to send request: {}", e)
-                    })?;
+                    .context("failed to send request")?;
 
                 if !response.status().is_success() {
-                    let status = response.status();
-                    let error_text =
-                        response.text().await.unwrap_or_else(|_| {
-                            "Unknown error".to_string()
-                        });
-                    return Err(anyhow!(
-                        "request failed with status {}: {}",
-                        status,
-                        error_text
-                    ));
+                    return Err(HttpStatusError::from_response(
+                        response,
+                    )
+                    .await
+                    .into());
                 }
 
+                let raw = response
+                    .text()
+                    .await
+                    .context("failed to read the response")?;
+                debug_log::record_raw(*self, "response", &raw);
+
                 let response_json: serde_json::Value =
-                    response.json().await.map_err(|e| {
-                        anyhow!(
+                    serde_json::from_str(&raw).map_err(|e| {
+                        InvalidResponse(format!(
                             "Failed to parse response JSON: {}",
                             e
-                        )
+                        ))
                     })?;
 
                 let generated_text = response_json
@@ -172,51 +304,59 @@ impl Provider {
                     .and_then(|c| c.get(0))
                     .and_then(|c| c.get("content"))
                     .and_then(|c| c.get("parts"))
-                    .and_then(|p| p.get(0))
-                    .and_then(|p| p.get("text"))
-                    .and_then(|t| t.as_str())
+                    .and_then(gemini_text)
                     .ok_or_else(|| {
-                        anyhow!(
+                        InvalidResponse(
                             "Invalid response format from Gemini API"
+                                .to_owned(),
                         )
                     })?;
 
-                let result: ResponseSchema = serde_json::from_str(
-                    generated_text,
-                )
-                .map_err(|e| {
-                    anyhow!(
-                        "faield to parse JSON into vlaid schema: {}",
-                        e
-                    )
-                })?;
+                let result = parse_schema(&generated_text)?;
 
                 Ok(result)
             }
-            Provider::OpenAI => {
-                let client = openai::Client::from_env();
-
-                let extractor = client
-                    .extractor::<ResponseSchema>(model)
-                    .max_tokens(max_tokens)
-                    .preamble(prompt)
-                    .build();
-
-                Ok(extractor.extract(diffs).await?)
-            }
             Provider::Gemini => {
-                let client = gemini::Client::from_env();
-                let gen_cfg = GenerationConfig {
+                // rig only takes extra headers
+                // as the http client's defaults
+                let api_key = match &provider_cfg.api_key {
+                    Some(key) => key.to_owned(),
+                    None => std::env::var("GEMINI_API_KEY").context(
+                        "Gemini needs an api_key in its provider config or GEMINI_API_KEY",
+                    )?,
+                };
+                let http_client = reqwest::Client::builder()
+                    .default_headers(extra_headers(provider_cfg)?)
+                    .build()?;
+                let client = gemini::Client::builder(&api_key)
+                    .custom_client(http_client)
+                    .build()?;
+                let mut gen_cfg = GenerationConfig {
                     max_output_tokens: Some(max_tokens),
                     ..Default::default()
                 };
+                // the default config has its own temperature
+                if let Some(temperature) = provider_cfg.temperature {
+                    gen_cfg.temperature = Some(temperature);
+                }
+                gen_cfg.top_p = provider_cfg.top_p;
+
+                // rig leaves any thought parts out of
+                // what the extractor sees
+                if provider_cfg.reasoning {
+                    gen_cfg.thinking_config = Some(ThinkingConfig {
+                        thinking_budget: thinking_budget(max_tokens)
+                            as u32,
+                        include_thoughts: Some(false),
+                    });
+                }
 
                 let cfg = AdditionalParameters::default()
                     .with_config(gen_cfg);
 
                 let extractor = client
                     .extractor::<ResponseSchema>(model)
-                    .preamble(prompt)
+                    .preamble(&preamble(prompt, provider_cfg))
                     .additional_params(
                         serde_json::to_value(cfg).unwrap(),
                     )
@@ -225,16 +365,729 @@ impl Provider {
                 Ok(extractor.extract(diffs).await?)
             }
             Provider::Claude => {
-                let client = anthropic::Client::from_env();
+                anthropic::messages(
+                    prompt,
+                    provider_cfg,
+                    diffs,
+                    prompt_cache,
+                    tx,
+                )
+                .await
+            }
+            Provider::Ollama => {
+                ollama_generate(prompt, provider_cfg, diffs, tx).await
+            }
+            Provider::AzureOpenAI => {
+                azure_chat_completion(prompt, provider_cfg, diffs, tx)
+                    .await
+            }
+            Provider::Bedrock => {
+                bedrock::invoke(prompt, provider_cfg, diffs).await
+            }
+            Provider::Cohere => {
+                cohere::chat(prompt, provider_cfg, diffs).await
+            }
+            Provider::OpenAI
+            | Provider::OpenRouter
+            | Provider::Groq
+            | Provider::DeepSeek
+            | Provider::Mistral
+            | Provider::OpenAICompatible => {
+                let endpoint = Endpoint::for_provider(*self)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "{} has no chat completions endpoint",
+                            self
+                        )
+                    })?;
 
-                let extractor = client
-                    .extractor::<ResponseSchema>(model)
-                    .max_tokens(max_tokens)
-                    .preamble(prompt)
-                    .build();
+                compatible::complete(
+                    *self,
+                    &endpoint,
+                    prompt,
+                    provider_cfg,
+                    diffs,
+                    tx,
+                )
+                .await
+            }
+        }
+    }
+}
 
-                Ok(extractor.extract(diffs).await?)
+/// the provider's system_prompt goes in front of
+/// the prompt, both end up as the system message
+pub fn preamble<'a>(
+    prompt: &'a str,
+    provider_cfg: &ProviderConfig,
+) -> Cow<'a, str> {
+    match &provider_cfg.system_prompt {
+        Some(system_prompt) => {
+            Cow::Owned(format!("{}\n\n{}", system_prompt, prompt))
+        }
+        None => Cow::Borrowed(prompt),
+    }
+}
+
+/// gemini's generateContent body for the gai proxy, the prompt
+/// goes with the diffs and the system_prompt in system_instruction
+fn gai_body(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+) -> Result<serde_json::Value> {
+    let max_tokens = provider_cfg.max_tokens;
+    let content_text = format!("{}\n\n{}", prompt, diffs);
+
+    let mut request_body = serde_json::json!({
+        "contents": [{
+            "parts": [{
+                "text": content_text
+            }]
+        }],
+        "generationConfig": {
+            "responseMimeType": "application/json",
+            "responseSchema": response_schema()?,
+            "maxOutputTokens": max_tokens
+        }
+    });
+
+    if let Some(gen_cfg) =
+        request_body["generationConfig"].as_object_mut()
+    {
+        gen_cfg.extend(sampling_params(provider_cfg, "topP"));
+
+        if provider_cfg.reasoning {
+            gen_cfg.insert(
+                "thinkingConfig".to_owned(),
+                serde_json::json!({
+                    "thinkingBudget": thinking_budget(max_tokens),
+                    "includeThoughts": false
+                }),
+            );
+        }
+    }
+
+    if let Some(system_prompt) = &provider_cfg.system_prompt {
+        request_body["system_instruction"] = serde_json::json!({
+            "parts": [{ "text": system_prompt }]
+        });
+    }
+
+    Ok(request_body)
+}
+
+fn thinking_budget(max_tokens: u64) -> u64 {
+    GEMINI_THINKING_BUDGET.min(max_tokens / 2)
+}
+
+/// the text parts of a gemini candidate, thought
+/// parts (reasoning) are skipped
+fn gemini_text(parts: &serde_json::Value) -> Option<String> {
+    let text: String = parts
+        .as_array()?
+        .iter()
+        .filter(|part| {
+            part.get("thought").and_then(|t| t.as_bool())
+                != Some(true)
+        })
+        .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+        .collect();
+
+    (!text.is_empty()).then_some(text)
+}
+
+/// temperature and top_p, whichever are set, top_p
+/// goes under the provider's own key (gemini uses topP)
+pub fn sampling_params(
+    provider_cfg: &ProviderConfig,
+    top_p_key: &str,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut params = serde_json::Map::new();
+
+    if let Some(temperature) = provider_cfg.temperature {
+        params.insert("temperature".to_owned(), temperature.into());
+    }
+    if let Some(top_p) = provider_cfg.top_p {
+        params.insert(top_p_key.to_owned(), top_p.into());
+    }
+
+    params
+}
+
+/// headers the key or its signature travel in,
+/// kept over a provider's own unless they override
+const AUTH_HEADERS: &[&str] = &[
+    "authorization",
+    "x-api-key",
+    "api-key",
+    "x-goog-api-key",
+    "x-amz-date",
+    "x-amz-security-token",
+];
+
+/// the provider's headers, checked up front so a typo
+/// fails the request instead of being sent mangled
+pub fn extra_headers(
+    provider_cfg: &ProviderConfig,
+) -> Result<HeaderMap> {
+    let mut headers = HeaderMap::new();
+
+    for (name, value) in &provider_cfg.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| {
+                format!("{} isn't a valid header name", name)
+            })?;
+        let value =
+            HeaderValue::from_str(value).with_context(|| {
+                format!("the {} header has an invalid value", name)
+            })?;
+
+        headers.insert(name, value);
+    }
+
+    Ok(headers)
+}
+
+/// adds the provider's headers to request, replacing ours
+/// of the same name except for the auth ones
+pub fn with_headers(
+    request: RequestBuilder,
+    provider_cfg: &ProviderConfig,
+) -> Result<RequestBuilder> {
+    if provider_cfg.headers.is_empty() {
+        return Ok(request);
+    }
+
+    let headers = extra_headers(provider_cfg)?;
+    let (client, request) = request.build_split();
+    let mut request = request.context("failed to build request")?;
+
+    for (name, value) in headers {
+        let Some(name) = name else {
+            continue;
+        };
+
+        if AUTH_HEADERS.contains(&name.as_str())
+            && request.headers().contains_key(&name)
+            && !provider_cfg.override_auth_headers
+        {
+            continue;
+        }
+
+        request.headers_mut().insert(name, value);
+    }
+
+    Ok(RequestBuilder::from_parts(client, request))
+}
+
+/// ollama's /api/generate, when tx is passed we
+/// leave streaming on and read the ndjson line by line,
+/// otherwise it's a single object with the whole response.
+/// `format` takes a json schema, same as gemini
+async fn ollama_generate(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> Result<ResponseSchema> {
+    let host = provider_cfg
+        .base_url
+        .as_deref()
+        .unwrap_or(OLLAMA_HOST)
+        .trim_end_matches('/');
+
+    let endpoint = format!("{}/api/generate", host);
+
+    let request_body =
+        ollama_body(prompt, provider_cfg, diffs, tx.is_some())?;
+
+    debug_log::record(
+        Provider::Ollama,
+        "request",
+        request_body.clone(),
+    );
+
+    let client = reqwest::Client::new();
+    let request = client.post(&endpoint).json(&request_body);
+    let mut response = with_headers(request, provider_cfg)?
+        .send()
+        .await
+        .with_context(|| {
+            format!("failed to reach ollama at {}", host)
+        })?;
+
+    if !response.status().is_success() {
+        return Err(HttpStatusError::from_response(response)
+            .await
+            .into());
+    }
+
+    let generated_text = match tx {
+        Some(tx) => {
+            let mut generated = String::new();
+            let mut pending: Vec<u8> = Vec::new();
+            // every ndjson line, for the raw view
+            let mut raw = String::new();
+
+            while let Some(chunk) = response.chunk().await? {
+                pending.extend_from_slice(&chunk);
+
+                // a chunk isn't guaranteed to end on a line
+                while let Some(pos) =
+                    pending.iter().position(|b| *b == b'\n')
+                {
+                    let line: Vec<u8> =
+                        pending.drain(..=pos).collect();
+
+                    if line.iter().all(|b| b.is_ascii_whitespace()) {
+                        continue;
+                    }
+
+                    raw.push_str(&String::from_utf8_lossy(&line));
+
+                    let obj: serde_json::Value =
+                        serde_json::from_slice(&line).map_err(|e| {
+                            InvalidResponse(format!(
+                                "Failed to parse streamed JSON: {}",
+                                e
+                            ))
+                        })?;
+
+                    if let Some(text) =
+                        obj.get("response").and_then(|r| r.as_str())
+                        && !text.is_empty()
+                    {
+                        generated.push_str(text);
+                        let _ = tx
+                            .send(ResponseEvent::Partial(
+                                text.to_owned(),
+                            ))
+                            .await;
+                    }
+                }
+            }
+
+            debug_log::record_raw(Provider::Ollama, "response", &raw);
+
+            generated
+        }
+        None => {
+            let raw = response
+                .text()
+                .await
+                .context("failed to read the response")?;
+            debug_log::record_raw(Provider::Ollama, "response", &raw);
+
+            let response_json: serde_json::Value =
+                serde_json::from_str(&raw).map_err(|e| {
+                    InvalidResponse(format!(
+                        "Failed to parse response JSON: {}",
+                        e
+                    ))
+                })?;
+
+            response_json
+                .get("response")
+                .and_then(|r| r.as_str())
+                .ok_or_else(|| {
+                    InvalidResponse(
+                        "Invalid response format from Ollama"
+                            .to_owned(),
+                    )
+                })?
+                .to_owned()
+        }
+    };
+
+    parse_schema(&generated_text)
+}
+
+/// the api/generate request, structured
+/// through the format schema
+fn ollama_body(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    stream: bool,
+) -> Result<serde_json::Value> {
+    let mut options = sampling_params(provider_cfg, "top_p");
+    options.insert(
+        "num_predict".to_owned(),
+        provider_cfg.max_tokens.into(),
+    );
+
+    Ok(serde_json::json!({
+        "model": provider_cfg.model,
+        "system": preamble(prompt, provider_cfg),
+        "prompt": diffs,
+        "stream": stream,
+        "format": response_schema()?,
+        "options": options
+    }))
+}
+
+/// azure puts the deployment in the url and wants an
+/// api-key header, the rest is openai's chat completions
+/// {endpoint}/openai/deployments/{deployment}/chat/completions
+async fn azure_chat_completion(
+    prompt: &str,
+    provider_cfg: &ProviderConfig,
+    diffs: &str,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> Result<ResponseSchema> {
+    let endpoint =
+        provider_cfg.endpoint.as_deref().ok_or_else(|| {
+            anyhow!(
+                "AzureOpenAI needs an endpoint in its provider config"
+            )
+        })?;
+
+    // deployments are usually named after the model
+    let deployment = provider_cfg
+        .deployment
+        .as_deref()
+        .unwrap_or(&provider_cfg.model);
+
+    let api_version = provider_cfg
+        .api_version
+        .as_deref()
+        .unwrap_or(AZURE_API_VERSION);
+
+    let api_key = match &provider_cfg.api_key {
+        Some(key) => key.to_owned(),
+        None => std::env::var("AZURE_OPENAI_API_KEY").context(
+            "AzureOpenAI needs an api_key in its provider config or AZURE_OPENAI_API_KEY",
+        )?,
+    };
+
+    let url = format!(
+        "{}/openai/deployments/{}/chat/completions?api-version={}",
+        endpoint.trim_end_matches('/'),
+        deployment,
+        api_version
+    );
+
+    let request =
+        reqwest::Client::new().post(url).header("api-key", api_key);
+
+    chat_completion(
+        Provider::AzureOpenAI,
+        request,
+        prompt,
+        provider_cfg,
+        diffs,
+        ResponseFormat::JsonSchema,
+        azure_max_tokens_key(&provider_cfg.model),
+        tx,
+    )
+    .await
+}
+
+/// reasoning deployments reject max_tokens, same
+/// as openai's own api
+fn azure_max_tokens_key(model: &str) -> &'static str {
+    if is_reasoning_model(model) {
+        "max_completion_tokens"
+    } else {
+        "max_tokens"
+    }
+}
+
+/// openai's reasoning models, also behind
+/// openrouter and azure deployments
+pub fn is_reasoning_model(model: &str) -> bool {
+    let model = model.rsplit('/').next().unwrap_or(model);
+
+    ["gpt-5", "o1", "o3", "o4"]
+        .iter()
+        .any(|prefix| model.starts_with(prefix))
+}
+
+/// false when the server ignored stream: true and sent
+/// back the whole json, ex. some local or proxy servers
+pub fn is_event_stream(response: &reqwest::Response) -> bool {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/event-stream"))
+}
+
+/// reads a server-sent events body as it comes in, on_data
+/// gets every data: payload and whatever text it returns is
+/// forwarded as a Partial. the raw body is returned for the
+/// debug log
+pub async fn read_sse(
+    mut response: reqwest::Response,
+    tx: &mpsc::Sender<ResponseEvent>,
+    mut on_data: impl FnMut(&str) -> Result<Option<String>>,
+) -> Result<String> {
+    let mut pending: Vec<u8> = Vec::new();
+    let mut raw = String::new();
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .context("failed to read the response")?
+    {
+        pending.extend_from_slice(&chunk);
+
+        // a chunk isn't guaranteed to end on a line
+        while let Some(pos) = pending.iter().position(|b| *b == b'\n')
+        {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            raw.push_str(&line);
+
+            // event: lines and keep-alive comments are skipped
+            let Some(data) = line.trim_end().strip_prefix("data:")
+            else {
+                continue;
+            };
+
+            if let Some(text) = on_data(data.trim_start())?
+                && !text.is_empty()
+            {
+                let _ = tx.send(ResponseEvent::Partial(text)).await;
             }
         }
     }
+
+    Ok(raw)
+}
+
+/// non 2xx response from a provider we talk to directly
+/// kept as its own type so the retry logic can
+/// look at the status code
+#[derive(Debug)]
+pub struct HttpStatusError {
+    pub status: reqwest::StatusCode,
+    pub body: String,
+    /// how long the provider asked us to wait,
+    /// from the retry-after header (in seconds)
+    pub retry_after: Option<Duration>,
+}
+
+impl HttpStatusError {
+    pub async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        // the http-date form isn't worth parsing, none
+        // of the providers we talk to send it
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .filter(|secs| secs.is_finite() && *secs >= 0.0)
+            .map(Duration::from_secs_f64);
+        let body = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+
+        Self {
+            status,
+            body,
+            retry_after,
+        }
+    }
+
+    pub fn is_auth_error(&self) -> bool {
+        self.status == reqwest::StatusCode::UNAUTHORIZED
+            || self.status == reqwest::StatusCode::FORBIDDEN
+    }
+
+    pub fn message(&self) -> String {
+        error_message(&self.body)
+    }
+}
+
+/// the message out of a json error body, providers
+/// disagree on where it goes, ex. openai's
+/// {"error": {"message": ...}} or bedrock's {"message": ...}
+/// anything unrecognized is returned as is
+pub fn error_message(body: &str) -> String {
+    let body = body.trim();
+
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body)
+    else {
+        return body.to_owned();
+    };
+
+    let message = json
+        .pointer("/error/message")
+        .or_else(|| json.get("error").filter(|e| e.is_string()))
+        .or_else(|| json.get("message"))
+        .or_else(|| json.get("Message"))
+        .or_else(|| json.get("detail"))
+        .or_else(|| json.pointer("/errors/0/message"))
+        .and_then(|m| m.as_str());
+
+    match message {
+        Some(message) => message.to_owned(),
+        None => body.to_owned(),
+    }
+}
+
+/// for error bodies we get without a status, ex.
+/// through rig, going by each vendor's error codes
+pub fn is_auth_error_body(body: &str) -> bool {
+    let Ok(json) =
+        serde_json::from_str::<serde_json::Value>(body.trim())
+    else {
+        return false;
+    };
+
+    let field = |pointer: &str| {
+        json.pointer(pointer).and_then(|v| v.as_str()).unwrap_or("")
+    };
+
+    matches!(
+        field("/error/type"),
+        "authentication_error" | "permission_error"
+    ) || field("/error/code") == "invalid_api_key"
+        || matches!(
+            field("/error/status"),
+            "UNAUTHENTICATED" | "PERMISSION_DENIED"
+        )
+}
+
+impl fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "request failed with status {}: {}",
+            self.status,
+            self.message()
+        )
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+/// the provider answered, but not with
+/// anything that fits the schema
+#[derive(Debug)]
+pub struct InvalidResponse(pub String);
+
+impl fmt::Display for InvalidResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidResponse {}
+
+/// the model answered, but its text didn't fit the
+/// schema. kept for the corrective follow-up and, if
+/// that fails too, to show as is
+#[derive(Debug)]
+pub struct UnparsedOutput {
+    pub text: String,
+    pub error: serde_json::Error,
+}
+
+impl fmt::Display for UnparsedOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to parse JSON into valid schema: {}",
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for UnparsedOutput {}
+
+/// the model's text as the schema
+pub fn parse_schema(text: &str) -> Result<ResponseSchema> {
+    serde_json::from_str(text).map_err(|error| {
+        UnparsedOutput {
+            text: text.to_owned(),
+            error,
+        }
+        .into()
+    })
+}
+
+/// json schema for providers that let us pass
+/// our own structured output, inlined since
+/// gemini doesn't like $refs
+pub fn response_schema() -> Result<serde_json::Value> {
+    let generator = SchemaSettings::draft2020_12()
+        .with(|s| {
+            s.meta_schema = None;
+            s.inline_subschemas = true;
+        })
+        .into_generator();
+
+    let schema = generator.into_root_schema_for::<ResponseSchema>();
+
+    Ok(serde_json::to_value(&schema)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_system_prompt() -> ProviderConfig {
+        let mut cfg = ProviderConfig::new("model");
+        cfg.system_prompt = Some("never use emoji".to_owned());
+        cfg
+    }
+
+    #[test]
+    fn preamble_puts_the_system_prompt_first() {
+        let cfg = with_system_prompt();
+        assert_eq!(
+            preamble("prompt", &cfg),
+            "never use emoji\n\nprompt"
+        );
+
+        let cfg = ProviderConfig::new("model");
+        assert_eq!(preamble("prompt", &cfg), "prompt");
+    }
+
+    #[test]
+    fn azure_reasoning_models_take_max_completion_tokens() {
+        assert_eq!(
+            azure_max_tokens_key("o3-mini"),
+            "max_completion_tokens"
+        );
+        assert_eq!(
+            azure_max_tokens_key("gpt-5-nano"),
+            "max_completion_tokens"
+        );
+        assert_eq!(azure_max_tokens_key("gpt-4o"), "max_tokens");
+    }
+
+    #[test]
+    fn ollama_sends_the_system_prompt() {
+        let cfg = with_system_prompt();
+        let body =
+            ollama_body("prompt", &cfg, "diffs", false).unwrap();
+
+        assert_eq!(body["system"], "never use emoji\n\nprompt");
+        assert_eq!(body["prompt"], "diffs");
+    }
+
+    #[test]
+    fn gai_sends_the_system_instruction() {
+        let cfg = with_system_prompt();
+        let body = gai_body("prompt", &cfg, "diffs").unwrap();
+
+        assert_eq!(
+            body["system_instruction"]["parts"][0]["text"],
+            "never use emoji"
+        );
+        assert_eq!(
+            body["contents"][0]["parts"][0]["text"],
+            "prompt\n\ndiffs"
+        );
+
+        let cfg = ProviderConfig::new("model");
+        let body = gai_body("prompt", &cfg, "diffs").unwrap();
+        assert!(body.get("system_instruction").is_none());
+    }
 }
@@ -1,7 +1,17 @@
-use std::{collections::HashMap, fmt};
+use anyhow::{Result, anyhow};
+use regex_automata::meta::Regex;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+use strum::IntoEnumIterator;
 
 use crate::{
-    config::{Config, RuleConfig},
+    ai::response::PrefixType,
+    config::{
+        AiConfig, CommitConfig, Config, GaiConfig, RuleConfig,
+        SplitStrategy,
+    },
     consts::*,
     git::repo::GaiGit,
 };
@@ -10,6 +20,37 @@ use crate::{
 pub struct Request {
     pub prompt: String,
     pub diffs: String,
+
+    /// set by split, each group is sent
+    /// as its own request for a single commit
+    pub groups: Vec<RequestGroup>,
+
+    /// set by hold_large_files, left out of the
+    /// diffs until they've been summarized
+    pub large_files: Vec<LargeFile>,
+
+    /// gai.max_commits, the response is
+    /// capped to it once parsed
+    pub max_commits: Option<usize>,
+
+    /// gai.commit_config, headers over its
+    /// subject_max_len are sent back to be shortened
+    pub commit_config: CommitConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestGroup {
+    /// ex. src or . for the repo root
+    pub name: String,
+    pub files: Vec<String>,
+    pub diffs: String,
+}
+
+/// a file whose diff is too large to send whole
+#[derive(Debug, Clone)]
+pub struct LargeFile {
+    pub path: String,
+    pub diff: String,
 }
 
 impl fmt::Display for Request {
@@ -22,33 +63,312 @@ impl fmt::Display for Request {
         writeln!(f, "Request Diffs:")?;
         writeln!(f, "{}", self.diffs)?;
 
+        if !self.groups.is_empty() {
+            writeln!(f, "Request Groups:")?;
+            for group in &self.groups {
+                writeln!(
+                    f,
+                    "{} ({} files)",
+                    group.name,
+                    group.files.len()
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        if !self.large_files.is_empty() {
+            writeln!(f, "Summarized Files:")?;
+            for file in &self.large_files {
+                writeln!(
+                    f,
+                    "{} ({} chars)",
+                    file.path,
+                    file.diff.chars().count()
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "Estimated Tokens: ~{}", self.estimate_tokens())?;
+
         writeln!(f)
     }
 }
 
 impl Request {
-    pub fn build_diffs_string(
+    /// rough token count for the prompt + diffs
+    /// ~4 chars per token is close enough
+    /// for most tokenizers on english/code
+    pub fn estimate_tokens(&self) -> usize {
+        let prompt = self.prompt.chars().count();
+
+        // every group resends the prompt
+        let chars = if self.groups.is_empty() {
+            prompt + self.diffs.chars().count()
+        } else {
+            self.groups
+                .iter()
+                .map(|g| prompt + g.diffs.chars().count())
+                .sum()
+        };
+
+        // their summaries are short, the
+        // summary requests are what count
+        let summarize: usize = self
+            .large_files
+            .iter()
+            .map(|f| {
+                PROMPT_SUMMARIZE.chars().count()
+                    + f.diff.chars().count()
+            })
+            .sum();
+
+        (chars + summarize).div_ceil(4)
+    }
+
+    /// moves the diffs over summarize_min_chars into
+    /// large_files, should run after build_prompt and
+    /// before split, skipped when a template inlines {diffs}
+    pub fn hold_large_files(
         &mut self,
-        diffs: HashMap<String, String>,
+        cfg: &GaiConfig,
+        diffs: &mut HashMap<String, String>,
     ) {
-        let mut diffs_str = String::new();
+        self.large_files.clear();
 
-        for (file, diff) in diffs {
-            let file_diff = format!(
-                "File Name:{}\nDiff Content:{}\n\n",
-                file, diff
-            );
+        if !cfg.summarize_large_files
+            || self.prompt.contains("{diffs}")
+        {
+            return;
+        }
+
+        let mut paths: Vec<String> = diffs
+            .iter()
+            .filter(|(_, diff)| {
+                diff.chars().count() > cfg.summarize_min_chars
+            })
+            .map(|(path, _)| path.to_owned())
+            .collect();
+        paths.sort();
+
+        self.large_files = paths
+            .into_iter()
+            .filter_map(|path| {
+                let diff = diffs.remove(&path)?;
+                Some(LargeFile { path, diff })
+            })
+            .collect();
+    }
+
+    /// the request that summarizes a single large file
+    pub fn for_large_file(file: &LargeFile) -> Request {
+        Request {
+            prompt: PROMPT_SUMMARIZE.to_owned(),
+            diffs: format_diffs(std::iter::once((
+                &file.path, &file.diff,
+            ))),
+            ..Default::default()
+        }
+    }
+
+    /// the request that shortens a single header
+    /// to max_len characters
+    pub fn for_header(header: &str, max_len: usize) -> Request {
+        Request {
+            prompt: PROMPT_SHORTEN_SUBJECT.to_owned(),
+            diffs: format!(
+                "Header: {}\nMaximum length: {} characters",
+                header, max_len
+            ),
+            ..Default::default()
+        }
+    }
+
+    /// large_files swapped for their summaries, in
+    /// large_files order, each goes to its file's group
+    pub fn with_summaries(&self, summaries: &[String]) -> Request {
+        let mut req = self.clone();
+        req.large_files.clear();
+
+        for (file, summary) in self.large_files.iter().zip(summaries)
+        {
+            let entry = format_summary(file, summary);
+
+            match req
+                .groups
+                .iter_mut()
+                .find(|g| g.files.contains(&file.path))
+            {
+                Some(group) => group.diffs.push_str(&entry),
+                None => req.diffs.push_str(&entry),
+            }
+        }
+
+        req
+    }
+
+    /// groups the diffs by strategy, should run after
+    /// hold_large_files and before build_diffs_string
+    /// skipped when a template inlines {diffs}
+    pub fn split(
+        &mut self,
+        strategy: SplitStrategy,
+        diffs: &HashMap<String, String>,
+    ) {
+        self.groups.clear();
 
-            diffs_str.push_str(&file_diff);
+        if strategy == SplitStrategy::None
+            || self.prompt.contains("{diffs}")
+        {
+            return;
+        }
+
+        let mut groups: BTreeMap<String, Vec<String>> =
+            BTreeMap::new();
+        // large files get their summary
+        // added to the group later
+        for file in diffs
+            .keys()
+            .chain(self.large_files.iter().map(|f| &f.path))
+        {
+            let name = match file.split_once('/') {
+                Some((dir, _)) => dir.to_owned(),
+                None => ".".to_owned(),
+            };
+
+            groups.entry(name).or_default().push(file.to_owned());
+        }
+
+        self.groups = groups
+            .into_iter()
+            .map(|(name, mut files)| {
+                files.sort();
+                let diffs =
+                    format_diffs(files.iter().filter_map(|f| {
+                        diffs.get(f).map(|d| (f, d))
+                    }));
+
+                RequestGroup { name, files, diffs }
+            })
+            .collect();
+    }
+
+    /// the request sent for a single group
+    pub fn for_group(&self, group: &RequestGroup) -> Request {
+        Request {
+            prompt: format!(
+                "{}\n{}",
+                self.prompt, PROMPT_SPLIT_GROUP
+            ),
+            diffs: group.diffs.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// the biggest request that goes out on its own,
+    /// each group and large file summary is separate
+    pub fn largest_request_tokens(&self) -> usize {
+        let prompt = self.prompt.chars().count();
+
+        let main = if self.groups.is_empty() {
+            prompt + self.diffs.chars().count()
+        } else {
+            self.groups
+                .iter()
+                .map(|g| prompt + g.diffs.chars().count())
+                .max()
+                .unwrap_or(prompt)
+        };
+
+        let summarize = self
+            .large_files
+            .iter()
+            .map(|f| {
+                PROMPT_SUMMARIZE.chars().count()
+                    + f.diff.chars().count()
+            })
+            .max()
+            .unwrap_or_default();
+
+        main.max(summarize).div_ceil(4)
+    }
+
+    /// how far the biggest request is past the provider's
+    /// context_window, max_tokens of it are kept for the answer
+    pub fn tokens_over(&self, ai: &AiConfig) -> Option<usize> {
+        let provider_cfg = ai.providers.get(&ai.provider)?;
+        let window = provider_cfg.context_window?;
+        let budget =
+            window.saturating_sub(provider_cfg.max_tokens) as usize;
+
+        self.largest_request_tokens()
+            .checked_sub(budget)
+            .filter(|over| *over > 0)
+    }
+
+    /// fails before anything is sent when
+    /// the prompt won't fit, see tokens_over
+    pub fn check_context(&self, ai: &AiConfig) -> Result<()> {
+        let Some(over) = self.tokens_over(ai) else {
+            return Ok(());
+        };
+        let provider_cfg = &ai.providers[&ai.provider];
+
+        Err(anyhow!(
+            "the prompt is ~{} tokens, ~{} over what {} has room for \
+             ({} context_window - {} max_tokens), truncate the large \
+             files (t in the TUI or ai.files_to_truncate) or lower \
+             ai.max_diff_chars",
+            self.largest_request_tokens(),
+            over,
+            provider_cfg.model,
+            provider_cfg.context_window.unwrap_or_default(),
+            provider_cfg.max_tokens
+        ))
+    }
+
+    /// estimated input cost in USD, if the provider
+    /// has pricing set (USD per million input tokens)
+    pub fn estimate_cost(
+        &self,
+        input_price: Option<f64>,
+    ) -> Option<f64> {
+        input_price.map(|price| {
+            self.estimate_tokens() as f64 / 1_000_000.0 * price
+        })
+    }
+
+    pub fn build_diffs_string(
+        &mut self,
+        diffs: HashMap<String, String>,
+    ) {
+        let diffs_str = format_diffs(diffs.iter());
+
+        // a template that places the diffs itself
+        // gets them inline instead of as a separate message
+        if self.prompt.contains("{diffs}") {
+            self.prompt = self.prompt.replace("{diffs}", &diffs_str);
+            self.diffs = PROMPT_DIFFS_INLINE.to_owned();
+            return;
         }
 
         self.diffs = diffs_str;
     }
 
+    /// should be called before build_diffs_string
+    /// so a template's {diffs} can be filled in
     pub fn build_prompt(&mut self, cfg: &Config, gai: &GaiGit) {
+        self.max_commits = cfg.gai.max_commits;
+        self.commit_config = cfg.gai.commit_config.clone();
+
+        if let Some(template) = &cfg.gai.prompt_template {
+            self.prompt = build_from_template(template, cfg, gai);
+            return;
+        }
+
         let mut prompt = String::new();
 
-        let rules = build_rules(&cfg.ai.rules);
+        let rules = build_rules(&cfg.ai.rules, &cfg.gai);
 
         if let Some(sys_prompt) = &cfg.ai.system_prompt {
             prompt.push_str(sys_prompt);
@@ -80,24 +400,36 @@ impl Request {
         prompt.push_str(&rules);
         prompt.push('\n');
 
-        if cfg.ai.include_convention {
-            if let Some(commit_conv) = &cfg.ai.commit_convention {
-                prompt.push_str(commit_conv);
-            } else {
-                prompt.push_str(COMMIT_CONVENTION);
-            }
+        if let Some(language) = &cfg.gai.language {
+            prompt.push_str(&language_directive(language));
+            prompt.push('\n');
+        }
 
+        if let Some(max) = cfg.gai.max_commits {
+            prompt.push_str(&max_commits_directive(max));
             prompt.push('\n');
         }
 
-        if cfg.gai.stage_hunks {
-            prompt.push_str(PROMPT_STAGE_HUNKS);
-        } else {
-            prompt.push_str(PROMPT_STAGE_FILES);
+        if cfg.ai.include_convention {
+            prompt.push_str(commit_convention(cfg));
+            prompt.push('\n');
         }
 
+        prompt.push_str(staging_instructions(cfg));
         prompt.push('\n');
 
+        if cfg.gai.include_branch_context
+            && let Some(branch) = gai.branch_name()
+        {
+            prompt.push_str(&branch_context(&branch, cfg));
+        }
+
+        if cfg.gai.style_examples_count > 0 {
+            prompt.push_str(&style_examples(
+                &gai.recent_subjects(cfg.gai.style_examples_count),
+            ));
+        }
+
         if cfg.ai.include_file_tree {
             prompt.push_str("Current File Tree: \n");
             prompt.push_str(&gai.get_repo_tree());
@@ -113,7 +445,177 @@ impl Request {
     }
 }
 
-fn build_rules(cfg: &RuleConfig) -> String {
+/// stops adding subjects once STYLE_EXAMPLES_MAX_CHARS
+/// is reached, empty when there's no history
+fn style_examples(subjects: &[String]) -> String {
+    let mut examples = String::new();
+
+    for subject in subjects {
+        if examples.len() + subject.len() > STYLE_EXAMPLES_MAX_CHARS {
+            break;
+        }
+
+        examples.push_str(&format!("- {}\n", subject));
+    }
+
+    if examples.is_empty() {
+        return examples;
+    }
+
+    format!("{}\n{}", PROMPT_STYLE_EXAMPLES, examples)
+}
+
+fn format_diffs<'a>(
+    diffs: impl Iterator<Item = (&'a String, &'a String)>,
+) -> String {
+    let mut diffs_str = String::new();
+
+    for (file, diff) in diffs {
+        let file_diff =
+            format!("File Name:{}\nDiff Content:{}\n\n", file, diff);
+
+        diffs_str.push_str(&file_diff);
+    }
+
+    diffs_str
+}
+
+/// the summary in place of the diff, the rename and
+/// hunk id lines are kept so the file can still be staged
+fn format_summary(file: &LargeFile, summary: &str) -> String {
+    let kept: String = file
+        .diff
+        .lines()
+        .filter(|l| {
+            l.starts_with("Renamed from ")
+                || l.starts_with("Hunk_id[")
+        })
+        .map(|l| format!("{}\n", l))
+        .collect();
+
+    format!(
+        "File Name:{}\nDiff Summary (too large to send whole):\n{}{}\n\n",
+        file.path,
+        kept,
+        summary.trim_end()
+    )
+}
+
+/// fills in the placeholders of a user template,
+/// {diffs} is left for build_diffs_string
+/// the staging instructions (and language) are always appended since
+/// the response can't be applied without them
+fn build_from_template(
+    template: &str,
+    cfg: &Config,
+    gai: &GaiGit,
+) -> String {
+    let file_list: String = gai
+        .files
+        .iter()
+        .filter(|f| !gai.skips_file(f))
+        .map(|f| format!("{}\n", f.display_path()))
+        .collect();
+
+    let mut prompt = template
+        .replace("{rules}", &build_rules(&cfg.ai.rules, &cfg.gai))
+        .replace("{convention}", commit_convention(cfg))
+        .replace("{hint}", cfg.ai.hint.as_deref().unwrap_or_default())
+        .replace("{file_list}", &file_list)
+        .replace("{branch}", &gai.get_branch());
+
+    // these walk the repo, skip them if unused
+    if prompt.contains("{file_tree}") {
+        prompt = prompt.replace("{file_tree}", &gai.get_repo_tree());
+    }
+
+    if prompt.contains("{git_status}") {
+        prompt = prompt
+            .replace("{git_status}", &gai.get_repo_status_as_str());
+    }
+
+    prompt.push('\n');
+
+    if let Some(language) = &cfg.gai.language {
+        prompt.push_str(&language_directive(language));
+        prompt.push('\n');
+    }
+
+    if let Some(max) = cfg.gai.max_commits {
+        prompt.push_str(&max_commits_directive(max));
+        prompt.push('\n');
+    }
+
+    prompt.push_str(staging_instructions(cfg));
+    prompt.push('\n');
+
+    prompt
+}
+
+/// prefixes are part of the schema so they
+/// have to stay english either way
+fn language_directive(language: &str) -> String {
+    format!(
+        "Write the commit messages in {}: the header, body and \
+         footer values should be in {} but the prefix \
+         (feat, fix, ...) and scope MUST stay in English",
+        language, language
+    )
+}
+
+fn max_commits_directive(max: usize) -> String {
+    if max <= 1 {
+        return "Create exactly ONE commit that covers all of the diffs"
+            .to_owned();
+    }
+
+    format!(
+        "Create at most {} commits, merge related changes into \
+         the same commit if there would be more",
+        max
+    )
+}
+
+/// the branch, plus a Refs: footer ask when
+/// there's an issue key in it
+fn branch_context(branch: &str, cfg: &Config) -> String {
+    let mut context = format!("Current Branch: {}\n", branch);
+
+    // checked when the config loads
+    let issue_key = Regex::new(&cfg.gai.issue_key_regex)
+        .ok()
+        .and_then(|re| re.find(branch))
+        .map(|m| &branch[m.range()]);
+
+    if let Some(key) = issue_key
+        && cfg.ai.rules.allow_footers
+    {
+        context.push_str(&format!(
+            "This branch is for issue {}, add a 'Refs: {}' footer to every commit\n",
+            key, key
+        ));
+    }
+
+    context
+}
+
+fn commit_convention(cfg: &Config) -> &str {
+    cfg.ai
+        .commit_convention
+        .as_deref()
+        .unwrap_or(COMMIT_CONVENTION)
+}
+
+fn staging_instructions(cfg: &Config) -> &'static str {
+    if cfg.gai.stage_hunks {
+        PROMPT_STAGE_HUNKS
+    } else {
+        PROMPT_STAGE_FILES
+    }
+}
+
+fn build_rules(cfg: &RuleConfig, gai_cfg: &GaiConfig) -> String {
+    let commit_cfg = &gai_cfg.commit_config;
     let mut rules = String::new();
 
     if cfg.group_related_files {
@@ -130,6 +632,18 @@ fn build_rules(cfg: &RuleConfig) -> String {
 
     rules.push_str(RULE_COMMIT_MESSAGE_HEADER);
     rules.push_str(RULE_PREFIX);
+    // the rest are flagged, no use letting the model pick them
+    let allowed_prefixes = gai_cfg.allowed_prefixes();
+    if allowed_prefixes.len() < PrefixType::iter().count() {
+        let allowed: Vec<String> = allowed_prefixes
+            .iter()
+            .map(|p| format!("{:?}", p).to_lowercase())
+            .collect();
+        rules.push_str(&format!(
+            "    - CRITICAL: Only use one of {}\n",
+            allowed.join(", ")
+        ));
+    }
 
     let scope_rule =
         match (cfg.allow_empty_scope, cfg.exclude_extension_in_scope)
@@ -140,6 +654,9 @@ fn build_rules(cfg: &RuleConfig) -> String {
             (false, false) => RULE_SCOPE_REQUIRED_WITH_EXTENSION,
         };
     rules.push_str(scope_rule);
+    if let Some(preset) = gai_cfg.style_preset {
+        rules.push_str(preset.scope_rule());
+    }
 
     rules.push_str(RULE_BREAKING);
 
@@ -148,15 +665,36 @@ fn build_rules(cfg: &RuleConfig) -> String {
         "    - CRITICAL: Maximum length is {} characters\n",
         cfg.max_header_length
     ));
+    if let Some(preset) = gai_cfg.style_preset {
+        rules.push_str(preset.subject_rule());
+    }
+    // anything longer gets cut when committing
+    if commit_cfg.subject_max_len > 0 {
+        rules.push_str(&format!(
+            "    - CRITICAL: The full first line, prefix and scope included, must stay under {} characters\n",
+            commit_cfg.subject_max_len
+        ));
+    }
 
     if cfg.allow_body {
         rules.push_str(RULE_BODY_BASE);
         rules.push_str(&format!(
             "    - Wrap lines at {} characters\n",
-            cfg.max_body_length
+            match commit_cfg.body_wrap_col {
+                0 => cfg.max_body_length.into(),
+                col => usize::from(cfg.max_body_length).min(col),
+            }
         ));
     } else {
-        rules.push_str("DO NOT CREATE A BODY, LEAVE IT BLANK");
+        rules.push_str("DO NOT CREATE A BODY, LEAVE IT BLANK\n");
+    }
+
+    if cfg.allow_footers {
+        rules.push_str(RULE_FOOTERS);
+    } else {
+        rules.push_str(
+            "DO NOT CREATE FOOTERS, LEAVE THE LIST EMPTY\n",
+        );
     }
 
     if cfg.verbose_descriptions {
@@ -168,3 +706,48 @@ fn build_rules(cfg: &RuleConfig) -> String {
     rules.push('\n');
     rules
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn prompt_for(cfg: &Config, name: &str) -> String {
+        let dir = env::temp_dir().join(format!(
+            "gai-request-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = git2::Repository::init(&dir).unwrap();
+        let gai = GaiGit::new(repo, &cfg.gai).unwrap();
+
+        let mut req = Request::default();
+        req.build_prompt(cfg, &gai);
+
+        let _ = fs::remove_dir_all(&dir);
+        req.prompt
+    }
+
+    #[test]
+    fn language_adds_its_directive() {
+        let mut cfg = Config::default();
+        let without = prompt_for(&cfg, "without");
+
+        cfg.gai.language = Some("German".to_owned());
+        let with = prompt_for(&cfg, "with");
+
+        let directive = format!("{}\n", language_directive("German"));
+        assert!(with.contains(&directive));
+        assert_eq!(with.replacen(&directive, "", 1), without);
+    }
+
+    #[test]
+    fn no_language_leaves_the_prompt_alone() {
+        let cfg = Config::default();
+        let prompt = prompt_for(&cfg, "unset");
+
+        assert!(!prompt.contains("Write the commit messages in"));
+        assert_eq!(prompt, prompt_for(&cfg, "unset-again"));
+    }
+}
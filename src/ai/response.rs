@@ -1,15 +1,207 @@
+use rig::{completion::CompletionError, extractor::ExtractionError};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    borrow::Cow,
+    fmt,
+    future::Future,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use strum::{EnumIter, EnumString};
+use tokio::{
+    sync::{Semaphore, mpsc},
+    task::JoinSet,
+    time::{sleep, timeout},
+};
 
 use crate::{
-    ai::{provider::Provider, request::Request},
-    config::ProviderConfig,
+    ai::{
+        debug_log,
+        provider::{
+            HttpStatusError, InvalidResponse, Provider,
+            UnparsedOutput, error_message, is_auth_error_body,
+        },
+        request::{LargeFile, Request, RequestGroup},
+    },
+    config::{AiConfig, CommitConfig, ProviderConfig, ScopeStrategy},
+    consts::PROMPT_STRUCTURED_ONLY,
+    git::hook::HookRun,
 };
 
 /// response object along with any errors
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Response {
     pub result: Result<ResponseSchema, String>,
+    /// whichever provider in the chain
+    /// produced the result
+    pub provider: Option<Provider>,
+    /// set along with an Err result
+    #[serde(skip)]
+    pub error_kind: Option<ErrorKind>,
+    /// the body the result was parsed from, as it
+    /// came back, None when the provider hides it
+    #[serde(default)]
+    pub raw: Option<String>,
+}
+
+impl Response {
+    /// in case the model went over gai.max_commits
+    fn capped(mut self, max: Option<usize>) -> Self {
+        if let (Some(max), Ok(res)) = (max, &mut self.result) {
+            res.cap_commits(max);
+        }
+
+        self
+    }
+
+    /// for callers that bail with ?, the
+    /// error keeps its kind for exit codes
+    pub fn into_result(
+        self,
+    ) -> Result<ResponseSchema, RequestFailed> {
+        self.result.map_err(|message| RequestFailed {
+            message,
+            kind: self.error_kind.unwrap_or(ErrorKind::Other),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct RequestFailed {
+    pub message: String,
+    pub kind: ErrorKind,
+}
+
+impl fmt::Display for RequestFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RequestFailed {}
+
+/// roughly why a request failed, --quiet
+/// exits with a different code for each
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// bad or missing credentials
+    Auth,
+    /// couldn't reach the provider, timeouts,
+    /// rate limits and server errors
+    Network,
+    /// the response didn't fit the schema
+    Parse,
+    Other,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Auth => 2,
+            ErrorKind::Network => 3,
+            ErrorKind::Parse => 4,
+            ErrorKind::Other => 1,
+        }
+    }
+
+    /// first cause in the chain that says anything
+    fn classify(err: &anyhow::Error) -> Self {
+        for cause in err.chain() {
+            if let Some(e) = cause.downcast_ref::<HttpStatusError>() {
+                return if e.is_auth_error() {
+                    ErrorKind::Auth
+                } else if is_retryable(err) {
+                    ErrorKind::Network
+                } else {
+                    ErrorKind::Other
+                };
+            }
+
+            if let Some(ExtractionError::CompletionError(
+                CompletionError::ProviderError(body),
+            )) = cause.downcast_ref::<ExtractionError>()
+                && is_auth_error_body(body)
+            {
+                return ErrorKind::Auth;
+            }
+
+            // a missing api key variable
+            if cause.is::<std::env::VarError>() {
+                return ErrorKind::Auth;
+            }
+
+            if cause.is::<TimedOut>() || cause.is::<reqwest::Error>()
+            {
+                return ErrorKind::Network;
+            }
+
+            if cause.is::<InvalidResponse>()
+                || cause.is::<UnparsedOutput>()
+                || cause.is::<serde_json::Error>()
+                || matches!(
+                    cause.downcast_ref::<ExtractionError>(),
+                    Some(ExtractionError::NoData)
+                )
+            {
+                return ErrorKind::Parse;
+            }
+        }
+
+        ErrorKind::Other
+    }
+}
+
+/// what gets sent back over the channel
+/// when streaming, Partial can show up
+/// any number of times before Done
+/// Fallback/Retry means the last attempt failed
+/// and anything partial so far should be dropped
+#[derive(Debug)]
+pub enum ResponseEvent {
+    Partial(String),
+    Retry {
+        attempt: u32,
+        max: u32,
+    },
+    Fallback {
+        failed: Provider,
+        next: Provider,
+    },
+    /// the output didn't parse, the provider is
+    /// asked again for only the structured format
+    Reformat,
+    /// a large file's summary came back, these
+    /// all finish before the real request starts
+    Summarized {
+        done: usize,
+        total: usize,
+    },
+    /// a split group finished, commits show up in
+    /// whatever order the groups complete in
+    GroupDone {
+        done: usize,
+        total: usize,
+        commit: Option<ResponseCommit>,
+    },
+    Done(Response),
+    /// the model picker's list, fetched
+    /// alongside whatever request is running
+    Models {
+        provider: Provider,
+        models: Result<Vec<String>, String>,
+    },
+    /// the post_apply_hook finished, Err when
+    /// it couldn't be started at all
+    Hook(Result<HookRun, String>),
+    /// one of the above from the tui's request task, the
+    /// id lets a cancelled request's leftovers be dropped
+    Request {
+        id: u64,
+        event: Box<ResponseEvent>,
+    },
 }
 
 /// response object that a provider will respond with
@@ -46,10 +238,33 @@ pub struct CommitMessage {
     /// description compoennts
     pub header: String,
     pub body: String,
+    /// git trailers, one per entry
+    /// ex. BREAKING CHANGE: drops v1 or Refs: #123
+    pub footers: Vec<String>,
+
+    /// set when a user edit couldn't be parsed
+    /// back into a conventional commit, this gets
+    /// used as-is for the commit message
+    /// not part of the schema
+    #[serde(skip)]
+    pub edited: Option<String>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+    EnumString,
+    EnumIter,
+)]
 #[serde(rename_all = "lowercase")]
+#[strum(ascii_case_insensitive)]
 pub enum PrefixType {
     Feat,
     Fix,
@@ -72,15 +287,69 @@ pub enum PrefixType {
     Revert,
 }
 
+impl PrefixType {
+    /// the usual gitmoji.dev pick for
+    /// it, commit_config.gitmojis wins
+    pub fn gitmoji(self) -> &'static str {
+        match self {
+            PrefixType::Feat => "✨",
+            PrefixType::Fix => "🐛",
+            PrefixType::Refactor => "♻️",
+            PrefixType::Style => "🎨",
+            PrefixType::Test => "✅",
+            PrefixType::Docs => "📝",
+            PrefixType::Build => "📦",
+            PrefixType::CI => "👷",
+            PrefixType::Ops => "🚀",
+            PrefixType::Chore => "🔧",
+            PrefixType::Merge => "🔀",
+            PrefixType::Revert => "⏪",
+        }
+    }
+
+    /// its badge in the commits tab, a named ansi
+    /// color so it follows the terminal theme,
+    /// tui.prefix_colors wins
+    pub fn color(self) -> &'static str {
+        match self {
+            PrefixType::Feat => "green",
+            PrefixType::Fix => "red",
+            PrefixType::Refactor => "yellow",
+            PrefixType::Style => "magenta",
+            PrefixType::Test => "cyan",
+            PrefixType::Docs => "blue",
+            PrefixType::Build => "lightyellow",
+            PrefixType::CI => "lightblue",
+            PrefixType::Ops => "lightmagenta",
+            PrefixType::Chore => "gray",
+            PrefixType::Merge => "lightcyan",
+            PrefixType::Revert => "lightred",
+        }
+    }
+}
+
+impl ResponseSchema {
+    /// the commits past max are merged into
+    /// the last one that's kept
+    pub fn cap_commits(&mut self, max: usize) {
+        if self.commits.len() <= max {
+            return;
+        }
+
+        let extra = self.commits.split_off(max.max(1));
+        if let Some(last) = self.commits.last_mut() {
+            for commit in extra {
+                last.merge(commit);
+            }
+        }
+    }
+}
+
 impl ResponseCommit {
-    /// only used for UI for now
-    /// todo need to refactored out
-    pub fn get_commit_prefix(
-        &self,
-        capitalize_prefix: bool,
-        include_scope: bool,
-    ) -> String {
-        let prefix = if capitalize_prefix {
+    /// everything before the colon,
+    /// ex. ✨ feat(api)! with gitmoji on
+    pub fn get_commit_prefix(&self, cfg: &CommitConfig) -> String {
+        let prefix = if cfg.capitalize_prefix {
             format!("{:?}", self.message.prefix).to_uppercase()
         } else {
             format!("{:?}", self.message.prefix).to_lowercase()
@@ -88,30 +357,1280 @@ impl ResponseCommit {
 
         let breaking = if self.message.breaking { "!" } else { "" };
 
-        let scope = if include_scope {
-            format!("({})", self.message.scope.to_lowercase())
+        let scope = self.scope(cfg);
+        let scope = if cfg.include_scope && !scope.is_empty() {
+            format!("({})", scope)
         } else {
             "".to_owned()
         };
 
-        format!("{}{}{}", prefix, breaking, scope)
+        let gitmoji = cfg
+            .gitmoji_for(self.message.prefix)
+            .map(|emoji| format!("{} ", emoji))
+            .unwrap_or_default();
+
+        // the spec puts ! after the scope, ex. feat(api)!
+        format!("{}{}{}{}", gitmoji, prefix, scope, breaking)
+    }
+
+    /// lowercased, empty when there isn't one, Directory
+    /// gives the last part of the files' common parent
+    pub fn scope(&self, cfg: &CommitConfig) -> String {
+        match cfg.scope_strategy {
+            ScopeStrategy::Model => self.message.scope.to_lowercase(),
+            ScopeStrategy::Directory => {
+                let hunk_files =
+                    self.hunk_ids.iter().filter_map(|id| {
+                        id.rsplit_once(':').map(|(path, _)| path)
+                    });
+
+                common_dir(
+                    self.files
+                        .iter()
+                        .map(String::as_str)
+                        .chain(hunk_files),
+                )
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_lowercase()
+            }
+        }
+    }
+
+    /// the ! marker or a BREAKING CHANGE footer,
+    /// edited messages are parsed for either
+    pub fn is_breaking(&self) -> bool {
+        let parsed;
+        let message = match &self.message.edited {
+            Some(edited) => match CommitMessage::parse(edited) {
+                Some(message) => {
+                    parsed = message;
+                    &parsed
+                }
+                None => return false,
+            },
+            None => &self.message,
+        };
+
+        message.breaking
+            || message
+                .footers
+                .iter()
+                .any(|f| f.starts_with("BREAKING CHANGE:"))
+    }
+
+    /// folds next into this commit, files and hunks are
+    /// combined and next's subject moves into the body
+    pub fn merge(&mut self, next: ResponseCommit) {
+        for file in next.files {
+            if !self.files.contains(&file) {
+                self.files.push(file);
+            }
+        }
+
+        for hunk_id in next.hunk_ids {
+            if !self.hunk_ids.contains(&hunk_id) {
+                self.hunk_ids.push(hunk_id);
+            }
+        }
+
+        let message = &mut self.message;
+        let next = next.message;
+
+        // an edit is used as is, so both have to end up in it
+        if message.edited.is_some() || next.edited.is_some() {
+            let text = |m: &CommitMessage| {
+                m.edited.clone().unwrap_or_else(|| m.header.clone())
+            };
+            message.edited =
+                Some(format!("{}\n\n{}", text(message), text(&next)));
+        }
+
+        message.breaking |= next.breaking;
+        message.body = [
+            message.body.trim(),
+            next.header.trim(),
+            next.body.trim(),
+        ]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+        for footer in next.footers {
+            if !message.footers.contains(&footer) {
+                message.footers.push(footer);
+            }
+        }
+    }
+
+    /// reasons this commit breaks the configured
+    /// convention, empty when it's fine
+    pub fn validate(
+        &self,
+        allowed_prefixes: &[PrefixType],
+        max_header_length: u16,
+    ) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        if self.message.edited.is_some() {
+            reasons.push(
+                "edited message is not a conventional commit"
+                    .to_owned(),
+            );
+            return reasons;
+        }
+
+        if !allowed_prefixes.contains(&self.message.prefix) {
+            reasons.push(format!(
+                "prefix '{}' is not allowed",
+                format!("{:?}", self.message.prefix).to_lowercase()
+            ));
+        }
+
+        let header_len = self.message.header.chars().count();
+        if header_len > max_header_length as usize {
+            reasons.push(format!(
+                "subject is {} characters, the limit is {}",
+                header_len, max_header_length
+            ));
+        }
+
+        if self.message.header.trim().is_empty() {
+            reasons.push("subject is empty".to_owned());
+        }
+
+        reasons
     }
 }
 
-pub async fn get_response(
+impl CommitMessage {
+    /// parse a full commit message back into its parts
+    /// ex. feat(api)!: add endpoint\n\nbody
+    /// returns None if the first line isn't
+    /// a valid conventional commit header
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let (first, rest) =
+            text.split_once('\n').unwrap_or((text, ""));
+
+        let (prefix_part, header) = first.split_once(':')?;
+
+        // the spec puts ! after the scope but
+        // models put it before often enough, accept both
+        let breaking = prefix_part.contains('!');
+        let prefix_part = prefix_part.replace('!', "");
+
+        let (prefix_str, scope) = match prefix_part.split_once('(') {
+            Some((p, s)) => (p, s.strip_suffix(')')?.to_owned()),
+            None => (prefix_part.as_str(), String::new()),
+        };
+
+        // a gitmoji in front, ex. ✨ feat, is dropped
+        // it comes back from the config on apply
+        let prefix_str =
+            prefix_str.split_whitespace().last().unwrap_or_default();
+        let prefix = PrefixType::from_str(prefix_str).ok()?;
+
+        let header = header.trim();
+        if header.is_empty() {
+            return None;
+        }
+
+        let (body, footers) = split_footers(rest.trim());
+
+        Some(CommitMessage {
+            prefix,
+            scope,
+            breaking,
+            header: header.to_owned(),
+            body,
+            footers,
+            edited: None,
+        })
+    }
+}
+
+/// the last paragraph is treated as footers
+/// if every line of it looks like a trailer
+/// ex. Refs: #123 or BREAKING CHANGE: ...
+fn split_footers(text: &str) -> (String, Vec<String>) {
+    let (body, last) = match text.rsplit_once("\n\n") {
+        Some((body, last)) => (body.trim(), last),
+        None => ("", text),
+    };
+
+    // git-trailer style, "Token: value" or "Token #value"
+    let is_token = |token: &str| {
+        !token.is_empty()
+            && token
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    let is_trailer = |line: &str| {
+        line.starts_with("BREAKING CHANGE:")
+            || line.split_once(": ").is_some_and(|(t, _)| is_token(t))
+            || line.split_once(" #").is_some_and(|(t, _)| is_token(t))
+    };
+
+    if last.is_empty() || !last.lines().all(is_trailer) {
+        return (text.to_owned(), Vec::new());
+    }
+
+    (
+        body.to_owned(),
+        last.lines().map(|l| l.to_owned()).collect(),
+    )
+}
+
+/// split requests get one response per group,
+/// merged back together
+pub async fn get_response(req: &Request, ai: &AiConfig) -> Response {
+    let req = match summarize_large_files(req, ai, None).await {
+        Ok(req) => req,
+        Err(failed) => return failed,
+    };
+    let req = req.as_ref();
+
+    let response = if req.groups.is_empty() {
+        chain_response(req, ai).await
+    } else {
+        split_response(req, ai, None).await
+    };
+
+    shorten_headers(req, ai, response.capped(req.max_commits)).await
+}
+
+/// streams partial text over tx as it comes in,
+/// always finishing with ResponseEvent::Done
+pub async fn stream_response(
+    req: &Request,
+    ai: AiConfig,
+    tx: mpsc::Sender<ResponseEvent>,
+) {
+    let req = match summarize_large_files(req, &ai, Some(&tx)).await {
+        Ok(req) => req,
+        Err(failed) => {
+            let _ = tx.send(ResponseEvent::Done(failed)).await;
+            return;
+        }
+    };
+    let req = req.as_ref();
+
+    // groups run side by side, their partial
+    // text would just be interleaved
+    let response = if req.groups.is_empty() {
+        chain_stream(req, &ai, &tx).await
+    } else {
+        split_response(req, &ai, Some(&tx)).await
+    };
+
+    let response =
+        shorten_headers(req, &ai, response.capped(req.max_commits))
+            .await;
+    let _ = tx.send(ResponseEvent::Done(response)).await;
+}
+
+/// req with its large files summarized, by each provider's
+/// summarize_model, at most ai.max_concurrency at a time.
+/// a failed summary fails the whole request
+async fn summarize_large_files<'a>(
+    req: &'a Request,
+    ai: &AiConfig,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> Result<Cow<'a, Request>, Response> {
+    if req.large_files.is_empty() {
+        return Ok(Cow::Borrowed(req));
+    }
+
+    let summarize_ai = summarize_ai(ai);
+
+    let total = req.large_files.len();
+    let permits = Arc::new(Semaphore::new(ai.max_concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (i, file) in req.large_files.iter().enumerate() {
+        let file_req = Request::for_large_file(file);
+        let ai = summarize_ai.clone();
+        let permits = Arc::clone(&permits);
+
+        tasks.spawn(async move {
+            // never closed, so this can't fail
+            let _permit = permits.acquire_owned().await;
+            (i, chain_response(&file_req, &ai).await)
+        });
+    }
+
+    let mut summaries = vec![String::new(); total];
+    let mut done = 0;
+
+    while let Some(joined) = tasks.join_next().await {
+        let (i, resp) = match joined {
+            Ok(joined) => joined,
+            Err(e) => {
+                return Err(Response {
+                    result: Err(format!(
+                        "summary request failed: {}",
+                        e
+                    )),
+                    provider: None,
+                    error_kind: Some(ErrorKind::Other),
+                    raw: None,
+                });
+            }
+        };
+
+        // dropping the set aborts whatever is still going
+        summaries[i] = summary(&req.large_files[i], resp)?;
+
+        done += 1;
+        if let Some(tx) = tx {
+            let _ = tx
+                .send(ResponseEvent::Summarized { done, total })
+                .await;
+        }
+    }
+
+    Ok(Cow::Owned(req.with_summaries(&summaries)))
+}
+
+/// every provider with its summarize_model in place
+/// of the model, for the cheap side requests
+fn summarize_ai(ai: &AiConfig) -> AiConfig {
+    let mut summarize_ai = ai.clone();
+    for provider_cfg in summarize_ai.providers.values_mut() {
+        if let Some(model) = provider_cfg.summarize_model.take() {
+            provider_cfg.model = model;
+        }
+    }
+
+    summarize_ai
+}
+
+/// headers that would take the first line over
+/// subject_max_len are sent back to be shortened, up to
+/// shorten_attempts times each. a failed or still too
+/// long answer leaves them to be cut when committing
+async fn shorten_headers(
+    req: &Request,
+    ai: &AiConfig,
+    mut response: Response,
+) -> Response {
+    let cfg = &req.commit_config;
+    if cfg.subject_max_len == 0 || cfg.shorten_attempts == 0 {
+        return response;
+    }
+
+    let Ok(res) = &mut response.result else {
+        return response;
+    };
+
+    let shorten_ai = summarize_ai(ai);
+
+    for commit in &mut res.commits {
+        // the user's own words aren't touched
+        if commit.message.edited.is_some() {
+            continue;
+        }
+
+        // the prefix plus ": " come first
+        let prefix_len =
+            commit.get_commit_prefix(cfg).chars().count();
+        let Some(max_len) =
+            cfg.subject_max_len.checked_sub(prefix_len + 2)
+        else {
+            continue;
+        };
+
+        for _ in 0..cfg.shorten_attempts {
+            let len = commit.message.header.chars().count();
+            if len <= max_len {
+                break;
+            }
+
+            let header_req =
+                Request::for_header(&commit.message.header, max_len);
+            let Ok(shortened) =
+                chain_response(&header_req, &shorten_ai).await.result
+            else {
+                break;
+            };
+
+            let Some(header) = shortened
+                .commits
+                .first()
+                .map(|c| c.message.header.trim().to_owned())
+                .filter(|h| !h.is_empty() && h.chars().count() < len)
+            else {
+                continue;
+            };
+
+            commit.message.header = header;
+        }
+    }
+
+    response
+}
+
+/// the header and body of the one commit
+/// a summary request is asked for
+fn summary(
+    file: &LargeFile,
+    resp: Response,
+) -> Result<String, Response> {
+    let failed = |message: String, resp: Response| Response {
+        result: Err(format!(
+            "summarizing {}: {}",
+            file.path, message
+        )),
+        error_kind: resp.error_kind.or(Some(ErrorKind::Parse)),
+        ..resp
+    };
+
+    let commit = match &resp.result {
+        Ok(res) => res.commits.first(),
+        Err(e) => return Err(failed(e.to_owned(), resp)),
+    };
+
+    let Some(commit) = commit else {
+        return Err(failed("no summary came back".to_owned(), resp));
+    };
+
+    let message = &commit.message;
+    if message.body.trim().is_empty() {
+        return Ok(message.header.to_owned());
+    }
+
+    Ok(format!("{}\n{}", message.header, message.body.trim_end()))
+}
+
+/// tries each provider in order until one succeeds
+/// if all of them fail, every error is kept
+async fn chain_response(req: &Request, ai: &AiConfig) -> Response {
+    let mut errors = Vec::new();
+
+    let mut last_raw = None;
+    let mut unparsed = None;
+
+    for (provider, provider_cfg) in ai.provider_chain() {
+        let (res, raw) =
+            debug_log::capture_raw(extract_with_retries(
+                req,
+                provider,
+                &provider_cfg,
+                ai,
+                None,
+            ))
+            .await;
+
+        match res {
+            Ok(res) => {
+                return Response {
+                    result: Ok(res),
+                    provider: Some(provider),
+                    error_kind: None,
+                    raw,
+                };
+            }
+            Err(e) => {
+                errors.push((
+                    provider,
+                    describe_error(provider, &e),
+                    ErrorKind::classify(&e),
+                ));
+                last_raw = raw.or(last_raw);
+                unparsed = unparsed_text(&e)
+                    .map(|text| (provider, text.to_owned()))
+                    .or(unparsed);
+            }
+        }
+    }
+
+    match unparsed {
+        Some((provider, text)) => {
+            unparsed_response(provider, &text, last_raw)
+        }
+        None => failed_response(errors, last_raw),
+    }
+}
+
+async fn chain_stream(
     req: &Request,
+    ai: &AiConfig,
+    tx: &mpsc::Sender<ResponseEvent>,
+) -> Response {
+    let chain = ai.provider_chain();
+    let mut errors = Vec::new();
+
+    let mut last_raw = None;
+    let mut unparsed = None;
+
+    for (i, (provider, provider_cfg)) in chain.iter().enumerate() {
+        let (res, raw) =
+            debug_log::capture_raw(extract_with_retries(
+                req,
+                *provider,
+                provider_cfg,
+                ai,
+                Some(tx),
+            ))
+            .await;
+
+        match res {
+            Ok(res) => {
+                return Response {
+                    result: Ok(res),
+                    provider: Some(*provider),
+                    error_kind: None,
+                    raw,
+                };
+            }
+            Err(e) => {
+                errors.push((
+                    *provider,
+                    describe_error(*provider, &e),
+                    ErrorKind::classify(&e),
+                ));
+                last_raw = raw.or(last_raw);
+                unparsed = unparsed_text(&e)
+                    .map(|text| (*provider, text.to_owned()))
+                    .or(unparsed);
+
+                if let Some((next, _)) = chain.get(i + 1) {
+                    let _ = tx
+                        .send(ResponseEvent::Fallback {
+                            failed: *provider,
+                            next: *next,
+                        })
+                        .await;
+                }
+            }
+        }
+    }
+
+    match unparsed {
+        Some((provider, text)) => {
+            unparsed_response(provider, &text, last_raw)
+        }
+        None => failed_response(errors, last_raw),
+    }
+}
+
+/// one request per group, at most ai.max_concurrency
+/// at a time, put back in group order at the end
+async fn split_response(
+    req: &Request,
+    ai: &AiConfig,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> Response {
+    let total = req.groups.len();
+    let permits = Arc::new(Semaphore::new(ai.max_concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for (i, group) in req.groups.iter().enumerate() {
+        let group_req = req.for_group(group);
+        let ai = ai.clone();
+        let permits = Arc::clone(&permits);
+
+        tasks.spawn(async move {
+            // never closed, so this can't fail
+            let _permit = permits.acquire_owned().await;
+            (i, chain_response(&group_req, &ai).await)
+        });
+    }
+
+    let mut responses: Vec<Option<Response>> =
+        (0..total).map(|_| None).collect();
+    let mut done = 0;
+
+    while let Some(joined) = tasks.join_next().await {
+        let (i, resp) = match joined {
+            Ok(joined) => joined,
+            Err(e) => {
+                return Response {
+                    result: Err(format!(
+                        "group request failed: {}",
+                        e
+                    )),
+                    provider: None,
+                    error_kind: Some(ErrorKind::Other),
+                    raw: None,
+                };
+            }
+        };
+
+        let group = &req.groups[i];
+
+        // dropping the set aborts whatever is still going
+        let resp = match SplitResponse::pin(group, resp) {
+            Ok(resp) => resp,
+            Err(failed) => return failed,
+        };
+
+        done += 1;
+        if let Some(tx) = tx {
+            let commit = resp
+                .result
+                .as_ref()
+                .ok()
+                .and_then(|r| r.commits.first().cloned());
+
+            let _ = tx
+                .send(ResponseEvent::GroupDone {
+                    done,
+                    total,
+                    commit,
+                })
+                .await;
+        }
+
+        responses[i] = Some(resp);
+    }
+
+    let mut split = SplitResponse::default();
+    for resp in responses.into_iter().flatten() {
+        split.add(resp);
+    }
+
+    split.finish()
+}
+
+#[derive(Default)]
+struct SplitResponse {
+    commits: Vec<ResponseCommit>,
+    provider: Option<Provider>,
+    /// each group's body under its name
+    raw: String,
+}
+
+impl SplitResponse {
+    /// keeps a single commit per group and pins it
+    /// to the group's files so the history stays clean
+    /// a failed group fails the whole thing
+    fn pin(
+        group: &RequestGroup,
+        resp: Response,
+    ) -> Result<Response, Response> {
+        let res = match resp.result {
+            Ok(res) => res,
+            Err(e) => {
+                return Err(Response {
+                    result: Err(format!("{}: {}", group.name, e)),
+                    provider: resp.provider,
+                    error_kind: resp.error_kind,
+                    raw: resp.raw.map(|raw| group_raw(group, &raw)),
+                });
+            }
+        };
+
+        let commits = res
+            .commits
+            .into_iter()
+            .next()
+            .map(|mut commit| {
+                commit.hunk_ids.retain(|id| {
+                    id.split_once(':').is_some_and(|(path, _)| {
+                        group.files.iter().any(|f| f == path)
+                    })
+                });
+                commit.files = group.files.to_owned();
+                commit
+            })
+            .into_iter()
+            .collect();
+
+        Ok(Response {
+            result: Ok(ResponseSchema { commits }),
+            provider: resp.provider,
+            error_kind: None,
+            raw: resp.raw.map(|raw| group_raw(group, &raw)),
+        })
+    }
+
+    /// takes a pinned response
+    fn add(&mut self, resp: Response) {
+        self.provider = resp.provider.or(self.provider);
+
+        if let Some(raw) = resp.raw {
+            if !self.raw.is_empty() {
+                self.raw.push_str("\n\n");
+            }
+            self.raw.push_str(&raw);
+        }
+
+        if let Ok(res) = resp.result {
+            self.commits.extend(res.commits);
+        }
+    }
+
+    fn finish(self) -> Response {
+        Response {
+            result: Ok(ResponseSchema {
+                commits: self.commits,
+            }),
+            provider: self.provider,
+            error_kind: None,
+            raw: (!self.raw.is_empty()).then_some(self.raw),
+        }
+    }
+}
+
+/// a group's body with its name on top
+fn group_raw(group: &RequestGroup, raw: &str) -> String {
+    format!("--- {} ---\n{}", group.name, raw)
+}
+
+/// retries a single provider on transient errors
+/// with exponential backoff + jitter, the total wait
+/// is capped by retry_max_wait_secs
+async fn extract_with_retries(
+    req: &Request,
+    provider: Provider,
+    provider_cfg: &ProviderConfig,
+    ai: &AiConfig,
+    tx: Option<&mpsc::Sender<ResponseEvent>>,
+) -> anyhow::Result<ResponseSchema> {
+    let max_wait = Duration::from_secs(ai.retry_max_wait_secs);
+    let mut waited = Duration::ZERO;
+    let mut attempt = 0;
+    let mut prompt = Cow::Borrowed(req.prompt.as_str());
+
+    loop {
+        let res = match tx {
+            Some(tx) => {
+                let extract = provider.extract_streamed(
+                    &prompt,
+                    provider_cfg,
+                    &req.diffs,
+                    ai.prompt_cache,
+                    tx,
+                );
+                with_timeout(
+                    extract,
+                    provider,
+                    provider_cfg.timeout_secs,
+                )
+                .await
+            }
+            None => {
+                let extract = provider.extract(
+                    &prompt,
+                    provider_cfg,
+                    &req.diffs,
+                    ai.prompt_cache,
+                );
+                with_timeout(
+                    extract,
+                    provider,
+                    provider_cfg.timeout_secs,
+                )
+                .await
+            }
+        };
+
+        let err = match res {
+            Ok(r) => return Ok(r),
+            Err(e) => e,
+        };
+
+        // one corrective follow-up, it isn't one of the retries
+        if let Some(text) = unparsed_text(&err) {
+            let corrected = matches!(prompt, Cow::Owned(_));
+
+            debug_log::record(
+                provider,
+                "unparsed",
+                json!({
+                    "attempt": if corrected { 2 } else { 1 },
+                    "error": format!("{:#}", err),
+                    "text": text,
+                }),
+            );
+
+            if corrected {
+                return Err(err);
+            }
+
+            prompt = Cow::Owned(format!(
+                "{}{}",
+                req.prompt, PROMPT_STRUCTURED_ONLY
+            ));
+
+            if let Some(tx) = tx {
+                let _ = tx.send(ResponseEvent::Reformat).await;
+            }
+
+            continue;
+        }
+
+        if attempt >= ai.max_retries || !is_retryable(&err) {
+            return Err(err);
+        }
+
+        attempt += 1;
+
+        // the provider knows better than our backoff
+        let delay = retry_after(&err)
+            .unwrap_or_else(|| backoff(ai.retry_backoff_ms, attempt));
+        if waited + delay > max_wait {
+            return Err(err);
+        }
+        waited += delay;
+
+        if let Some(tx) = tx {
+            let _ = tx
+                .send(ResponseEvent::Retry {
+                    attempt,
+                    max: ai.max_retries,
+                })
+                .await;
+        }
+
+        sleep(delay).await;
+    }
+}
+
+/// the full error chain, plus a hint when the
+/// provider turned down our credentials
+fn describe_error(provider: Provider, err: &anyhow::Error) -> String {
+    let mut description = format!("{:#}", err);
+    let mut auth_error = false;
+
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<HttpStatusError>() {
+            auth_error = e.is_auth_error();
+            break;
+        }
+
+        // rig hands back the raw body, without the status
+        if let Some(ExtractionError::CompletionError(
+            CompletionError::ProviderError(body),
+        )) = cause.downcast_ref::<ExtractionError>()
+        {
+            description =
+                format!("request failed: {}", error_message(body));
+            auth_error = is_auth_error_body(body);
+            break;
+        }
+    }
+
+    if auth_error {
+        description.push_str(&format!(
+            "\nCheck your api_key for provider {}",
+            provider
+        ));
+    }
+
+    description
+}
+
+/// only rate limits, server errors and
+/// connection issues are worth retrying
+/// auth/bad request errors won't fix themselves
+fn is_retryable(err: &anyhow::Error) -> bool {
+    let retryable_status = |status: reqwest::StatusCode| {
+        status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || status.is_server_error()
+    };
+
+    err.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<HttpStatusError>() {
+            return retryable_status(e.status);
+        }
+
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            return e.is_timeout()
+                || e.is_connect()
+                || e.status().is_some_and(retryable_status);
+        }
+
+        false
+    })
+}
+
+fn retry_after(err: &anyhow::Error) -> Option<Duration> {
+    err.chain().find_map(|cause| {
+        cause
+            .downcast_ref::<HttpStatusError>()
+            .and_then(|e| e.retry_after)
+    })
+}
+
+/// base * 2^(attempt - 1) plus up to 50% jitter
+fn backoff(base_ms: u64, attempt: u32) -> Duration {
+    let delay = base_ms.saturating_mul(1 << (attempt - 1).min(16));
+
+    // no rand crate, the clock is random enough for jitter
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    let jitter = nanos % (delay / 2 + 1);
+
+    Duration::from_millis(delay + jitter)
+}
+
+async fn with_timeout<T>(
+    fut: impl Future<Output = anyhow::Result<T>>,
+    provider: Provider,
+    secs: u64,
+) -> anyhow::Result<T> {
+    timeout(Duration::from_secs(secs), fut)
+        .await
+        .unwrap_or_else(|_| Err(TimedOut { provider, secs }.into()))
+}
+
+#[derive(Debug)]
+struct TimedOut {
     provider: Provider,
-    provider_cfg: ProviderConfig,
+    secs: u64,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Request to {} timed out after {}s",
+            self.provider, self.secs
+        )
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// the model's text when it answered with something
+/// that doesn't parse, blank answers don't count
+fn unparsed_text(err: &anyhow::Error) -> Option<&str> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<UnparsedOutput>())
+        .map(|unparsed| unparsed.text.as_str())
+        .filter(|text| !text.trim().is_empty())
+}
+
+/// what the model said is shown as a single commit instead
+/// of a bare error. it goes in as an edit so it's flagged
+/// invalid and never applied without a look
+fn unparsed_response(
+    provider: Provider,
+    text: &str,
+    raw: Option<String>,
 ) -> Response {
-    let res = provider
-        .extract(
-            &req.prompt,
-            &provider_cfg.model,
-            provider_cfg.max_tokens,
-            &req.diffs,
+    let text = text.trim();
+    debug_log::record(provider, "unparsed_fallback", json!(text));
+
+    let header = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+
+    let commit = ResponseCommit {
+        files: Vec::new(),
+        hunk_ids: Vec::new(),
+        message: CommitMessage {
+            prefix: PrefixType::Chore,
+            scope: String::new(),
+            breaking: false,
+            header: header.to_owned(),
+            body: String::new(),
+            footers: Vec::new(),
+            edited: Some(text.to_owned()),
+        },
+    };
+
+    Response {
+        result: Ok(ResponseSchema {
+            commits: vec![commit],
+        }),
+        provider: Some(provider),
+        error_kind: None,
+        raw,
+    }
+}
+
+/// the last provider's error decides the kind,
+/// same as it decides the provider
+fn failed_response(
+    errors: Vec<(Provider, String, ErrorKind)>,
+    raw: Option<String>,
+) -> Response {
+    let result = match errors.as_slice() {
+        [] => Err("no provider configured".to_owned()),
+        [(_, e, _)] => Err(e.to_owned()),
+        _ => Err(errors
+            .iter()
+            .map(|(p, e, _)| format!("{}: {}", p, e))
+            .collect::<Vec<_>>()
+            .join("\n")),
+    };
+
+    let last = errors.last();
+    Response {
+        result,
+        provider: last.map(|(p, _, _)| *p),
+        error_kind: Some(
+            last.map_or(ErrorKind::Other, |(_, _, k)| *k),
+        ),
+        raw,
+    }
+}
+
+/// directory every path sits under, ex. "src/tui",
+/// empty when they only share the repo root
+fn common_dir<'a>(paths: impl Iterator<Item = &'a str>) -> String {
+    let mut common: Option<Vec<&str>> = None;
+
+    for path in paths {
+        // the file name itself isn't a directory
+        let dirs: Vec<&str> = match path.rsplit_once('/') {
+            Some((dir, _)) => dir.split('/').collect(),
+            None => Vec::new(),
+        };
+
+        common = Some(match common {
+            None => dirs,
+            Some(common) => common
+                .iter()
+                .zip(&dirs)
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| *a)
+                .collect(),
+        });
+    }
+
+    common.unwrap_or_default().join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::Mutex,
+        thread,
+    };
+
+    /// an openai style server answering each request with
+    /// the next of replies as the message content, the
+    /// request bodies it got are kept in order
+    fn serve(
+        replies: Vec<String>,
+    ) -> (AiConfig, Arc<Mutex<Vec<serde_json::Value>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url =
+            format!("http://{}", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+
+        let received = Arc::clone(&bodies);
+        thread::spawn(move || {
+            for reply in replies {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+
+                let mut len = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        len = value.trim().parse().unwrap();
+                    }
+                }
+
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push(serde_json::from_slice(&body).unwrap());
+
+                let response = json!({
+                    "choices": [{ "message": { "content": reply } }]
+                })
+                .to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+        });
+
+        let mut provider_cfg = ProviderConfig::new("test");
+        provider_cfg.base_url = Some(base_url);
+
+        let mut ai = AiConfig {
+            provider: Provider::OpenAICompatible,
+            ..AiConfig::default()
+        };
+        ai.providers
+            .insert(Provider::OpenAICompatible, provider_cfg);
+
+        (ai, bodies)
+    }
+
+    fn schema(commits: Vec<ResponseCommit>) -> String {
+        serde_json::to_string(&ResponseSchema { commits }).unwrap()
+    }
+
+    fn commit(prefix: PrefixType, header: &str) -> ResponseCommit {
+        ResponseCommit {
+            files: vec!["src/main.rs".to_owned()],
+            hunk_ids: Vec::new(),
+            message: CommitMessage {
+                prefix,
+                scope: String::new(),
+                breaking: false,
+                header: header.to_owned(),
+                body: String::new(),
+                footers: Vec::new(),
+                edited: None,
+            },
+        }
+    }
+
+    #[test]
+    fn validate_flags_prefix_length_and_edits() {
+        let allowed = [PrefixType::Feat, PrefixType::Fix];
+
+        let ok = commit(PrefixType::Feat, "add endpoint");
+        assert!(ok.validate(&allowed, 20).is_empty());
+
+        let reasons = commit(PrefixType::Chore, "bump deps")
+            .validate(&allowed, 20);
+        assert_eq!(reasons, ["prefix 'chore' is not allowed"]);
+
+        // chars, not bytes
+        let reasons =
+            commit(PrefixType::Fix, "ééééé").validate(&allowed, 5);
+        assert!(reasons.is_empty());
+        let reasons =
+            commit(PrefixType::Fix, "ééééééé").validate(&allowed, 5);
+        assert_eq!(
+            reasons,
+            ["subject is 7 characters, the limit is 5"]
+        );
+
+        let reasons =
+            commit(PrefixType::Fix, "  ").validate(&allowed, 5);
+        assert_eq!(reasons, ["subject is empty"]);
+
+        // an edit that didn't parse is the only reason given
+        let mut edited = commit(PrefixType::Chore, "");
+        edited.message.edited = Some("whatever".to_owned());
+        assert_eq!(
+            edited.validate(&allowed, 5),
+            ["edited message is not a conventional commit"]
+        );
+    }
+
+    #[tokio::test]
+    async fn long_headers_are_sent_back_to_be_shortened() {
+        let (ai, bodies) = serve(vec![schema(vec![commit(
+            PrefixType::Feat,
+            "add login",
+        )])]);
+
+        let mut req = Request::default();
+        req.commit_config.subject_max_len = 30;
+        req.commit_config.shorten_attempts = 2;
+
+        let long = "add a login page with oauth and remember me";
+        let response = Response {
+            result: Ok(ResponseSchema {
+                commits: vec![
+                    commit(PrefixType::Feat, long),
+                    commit(PrefixType::Fix, "short enough"),
+                ],
+            }),
+            provider: None,
+            error_kind: None,
+            raw: None,
+        };
+
+        let response = shorten_headers(&req, &ai, response).await;
+        let commits = response.result.unwrap().commits;
+        assert_eq!(commits[0].message.header, "add login");
+        assert_eq!(commits[1].message.header, "short enough");
+
+        // "feat: " takes 6 of the 30, and once it's short
+        // enough the second attempt isn't needed
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        let user =
+            bodies[0]["messages"][1]["content"].as_str().unwrap();
+        assert_eq!(
+            user,
+            format!(
+                "Header: {}\nMaximum length: 24 characters",
+                long
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn unparsed_answers_are_asked_for_json_once() {
+        let (ai, bodies) = serve(vec![
+            "Sure! Here are your commits.".to_owned(),
+            schema(vec![commit(PrefixType::Feat, "add login")]),
+        ]);
+        let provider_cfg = &ai.providers[&Provider::OpenAICompatible];
+        let req = Request {
+            prompt: "prompt".to_owned(),
+            ..Default::default()
+        };
+
+        let res = extract_with_retries(
+            &req,
+            Provider::OpenAICompatible,
+            provider_cfg,
+            &ai,
+            None,
         )
         .await
-        .map_err(|e| format!("{:#}", e));
+        .unwrap();
+        assert_eq!(res.commits[0].message.header, "add login");
+
+        let system = |body: &serde_json::Value| {
+            body["messages"][0]["content"]
+                .as_str()
+                .unwrap()
+                .to_owned()
+        };
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert!(!system(&bodies[0]).contains(PROMPT_STRUCTURED_ONLY));
+        assert!(system(&bodies[1]).contains(PROMPT_STRUCTURED_ONLY));
+    }
+
+    #[tokio::test]
+    async fn a_second_unparsed_answer_is_an_error() {
+        let (ai, bodies) = serve(vec![
+            "Sure! Here are your commits.".to_owned(),
+            "feat: add login".to_owned(),
+        ]);
+        let provider_cfg = &ai.providers[&Provider::OpenAICompatible];
 
-    Response { result: res }
+        let err = extract_with_retries(
+            &Request::default(),
+            Provider::OpenAICompatible,
+            provider_cfg,
+            &ai,
+            None,
+        )
+        .await
+        .unwrap_err();
+
+        // it isn't retried either
+        assert_eq!(unparsed_text(&err), Some("feat: add login"));
+        assert_eq!(bodies.lock().unwrap().len(), 2);
+    }
 }
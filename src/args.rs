@@ -1,8 +1,9 @@
 use anyhow::Result;
 use clap::{
-    Parser, Subcommand,
+    Parser, Subcommand, ValueEnum,
     builder::styling::{self, AnsiColor},
 };
+use std::path::PathBuf;
 
 use crate::{ai::provider::Provider, config::Config};
 
@@ -34,6 +35,11 @@ pub struct Args {
     #[arg(short = 'H', long)]
     pub hint: Option<String>,
 
+    /// Append the prompt, request bodies and raw responses
+    /// to this file as json lines, api keys are redacted
+    #[arg(long, value_name = "PATH", global = true)]
+    pub debug_log: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -77,6 +83,81 @@ pub enum Commands {
         /// Override config option for this commit
         #[arg(short = 'c', long, value_name = "KEY=VALUE")]
         config: Option<Vec<String>>,
+
+        /// Print the generated commit messages to stdout
+        /// without applying them or prompting
+        #[arg(short = 'P', long)]
+        print: bool,
+
+        /// Only print the messages, failures are a single
+        /// line on stderr and exit with 2 for auth, 3 for
+        /// network and 4 for parse errors, implies --print
+        #[arg(short = 'q', long)]
+        quiet: bool,
+
+        /// Output format for --print, json implies --print
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Show the git operations applying would run
+        /// without changing the repo
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+
+        /// Add a Co-authored-by trailer to every commit,
+        /// can be repeated
+        #[arg(
+            short = 'a',
+            long = "co-author",
+            value_name = "NAME <EMAIL>"
+        )]
+        co_authors: Vec<String>,
+
+        /// Only consider these paths, relative to the
+        /// current directory, a directory covers
+        /// everything under it
+        #[arg(value_name = "PATH", conflicts_with = "amend")]
+        paths: Vec<PathBuf>,
+
+        /// Add a Signed-off-by trailer with
+        /// the git user identity
+        #[arg(long)]
+        signoff: bool,
+
+        /// Run this shell command after the commits are
+        /// applied, in place of gai.post_apply_hook
+        #[arg(
+            long,
+            value_name = "COMMAND",
+            conflicts_with = "amend"
+        )]
+        post_apply_hook: Option<String>,
+
+        /// Regenerate the message of the last commit
+        /// and amend it, instead of creating new commits
+        #[arg(long)]
+        amend: bool,
+
+        /// Amend even if the last commit is already pushed,
+        /// or with -y apply commits that failed validation
+        #[arg(long)]
+        force: bool,
+
+        /// Describe the unified diff in this file, - for
+        /// stdin, instead of the working tree. only prints
+        /// the messages, nothing is applied
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = [
+                "amend",
+                "paths",
+                "staged",
+                "dry_run",
+                "skip_confirmation",
+            ]
+        )]
+        patch: Option<PathBuf>,
     },
     /* todo: implement, see feature tracking
     /// Rebase commits
@@ -88,6 +169,13 @@ pub enum Commands {
     */
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    /// versioned, see JSON_OUTPUT_VERSION
+    Json,
+}
+
 #[derive(Debug, Subcommand)]
 pub enum Auth {
     /// Login using GitHub OAuth
@@ -103,6 +191,20 @@ pub enum Auth {
 }
 
 impl Args {
+    pub fn is_json(&self) -> bool {
+        matches!(
+            self.command,
+            Commands::Commit {
+                format: OutputFormat::Json,
+                ..
+            }
+        )
+    }
+
+    pub fn is_quiet(&self) -> bool {
+        matches!(self.command, Commands::Commit { quiet: true, .. })
+    }
+
     pub fn parse_flags(&self, config: &mut Config) -> Result<()> {
         if let Some(provider) = self.provider {
             config.ai.provider = provider;
@@ -115,9 +217,22 @@ impl Args {
             staged,
             hunks,
             files,
+            ref co_authors,
+            signoff,
+            ref post_apply_hook,
             ..
         } = self.command
         {
+            config.gai.co_authors.extend(co_authors.iter().cloned());
+
+            if let Some(hook) = post_apply_hook {
+                config.gai.post_apply_hook = Some(hook.to_owned());
+            }
+
+            if signoff {
+                config.gai.signoff = true;
+            }
+
             if staged {
                 config.gai.only_staged = true;
             }
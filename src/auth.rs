@@ -1,9 +1,12 @@
 use anyhow::Result;
+#[cfg(feature = "cli")]
 use dialoguer::{Password, theme::ColorfulTheme};
 use std::{fs, io::ErrorKind, path::PathBuf};
 
-use crate::SpinDeez;
+#[cfg(feature = "cli")]
+use crate::print::SpinDeez;
 
+#[cfg(feature = "cli")]
 pub fn auth_login() -> Result<()> {
     println!("Opening Browser for https://cli.gai.fyi/login");
     open::that("https://cli.gai.fyi/login")?;
@@ -17,6 +20,7 @@ pub fn auth_login() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "cli")]
 pub async fn auth_status(spinner: &SpinDeez) -> Result<()> {
     spinner.start("Fetching User Status");
     let token = get_token()?;
@@ -79,6 +83,7 @@ pub fn get_token() -> Result<String> {
     Ok(token)
 }
 
+#[cfg(feature = "cli")]
 fn store_token(token: &str) -> Result<()> {
     if token.is_empty() {
         return Err(anyhow::anyhow!("token cannot be empty"));
@@ -1,18 +1,104 @@
 use anyhow::Result;
 use config::{Config as ConfigBuilder, File};
 use directories::ProjectDirs;
+#[cfg(feature = "cli")]
+use ratatui::style::Color;
+use regex_automata::meta::Regex;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, io::ErrorKind};
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
 
-use crate::ai::provider::Provider;
+use strum::IntoEnumIterator;
+
+use crate::{
+    ai::{
+        provider::{Provider, is_reasoning_model},
+        response::PrefixType,
+    },
+    consts::{
+        DEFAULT_TIMEOUT_SECS, DIFF_CONTEXT_LINES, ISSUE_KEY_REGEX,
+        PRESET_SCOPE_ANGULAR, PRESET_SCOPE_CONVENTIONAL,
+        PRESET_SCOPE_KARMA, PRESET_SCOPE_SEMANTIC_RELEASE,
+        PRESET_SUBJECT_ANGULAR, PRESET_SUBJECT_CONVENTIONAL,
+        PRESET_SUBJECT_KARMA, PRESET_SUBJECT_SEMANTIC_RELEASE,
+        REPO_CONFIG, REPO_DENIED_PROVIDER_KEYS, SUMMARIZE_MIN_CHARS,
+        TRUNCATE_PREVIEW_LINES,
+    },
+};
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub ai: AiConfig,
     pub gai: GaiConfig,
     pub tui: TuiConfig,
 }
 
+/// .gai.toml at the root of the repo we're in, if any
+fn repo_config_path() -> Option<PathBuf> {
+    let repo = git2::Repository::discover(".").ok()?;
+    Some(repo.workdir()?.join(REPO_CONFIG))
+}
+
+/// a cloned repo's .gai.toml is untrusted, it can't run
+/// commands, read files or send the api key elsewhere
+fn check_repo_config(path: &Path, global: &Path) -> Result<()> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Ok(());
+    };
+
+    let cfg: toml::Table = toml::from_str(&text).map_err(|e| {
+        anyhow::anyhow!("{} isn't valid toml: {}", path.display(), e)
+    })?;
+
+    let mut denied = Vec::new();
+
+    if let Some(gai) = cfg.get("gai").and_then(|g| g.as_table()) {
+        denied.extend(
+            gai.keys()
+                .filter(|k| k.eq_ignore_ascii_case("post_apply_hook"))
+                .map(|k| format!("gai.{}", k)),
+        );
+    }
+
+    let providers = cfg
+        .get("ai")
+        .and_then(|ai| ai.get("providers"))
+        .and_then(|p| p.as_table());
+
+    for (provider, provider_cfg) in providers.into_iter().flatten() {
+        let Some(provider_cfg) = provider_cfg.as_table() else {
+            continue;
+        };
+
+        denied.extend(
+            provider_cfg
+                .keys()
+                .filter(|k| {
+                    REPO_DENIED_PROVIDER_KEYS
+                        .iter()
+                        .any(|d| k.eq_ignore_ascii_case(d))
+                })
+                .map(|k| format!("ai.providers.{}.{}", provider, k)),
+        );
+    }
+
+    if denied.is_empty() {
+        return Ok(());
+    }
+
+    Err(anyhow::anyhow!(
+        "{} can't set {}, they're only read from {}",
+        path.display(),
+        denied.join(", "),
+        global.display()
+    ))
+}
+
 impl Config {
     pub fn init() -> Result<Self> {
         if let Some(base_dirs) =
@@ -31,7 +117,7 @@ impl Config {
             cfg_dir.push("config.toml");
 
             if !cfg_dir.exists() {
-                println!(
+                eprintln!(
                     "No config.toml found. Creating anew. in {}",
                     cfg_dir.display()
                 );
@@ -40,12 +126,36 @@ impl Config {
                 fs::write(&cfg_dir, &def_toml)?;
             }
 
+            // the repo config is merged over the global
+            // one key by key, so it only needs what it changes
+            let mut builder = ConfigBuilder::builder()
+                .add_source(File::from(cfg_dir.as_path()));
+
+            if let Some(repo_cfg) = repo_config_path() {
+                check_repo_config(&repo_cfg, &cfg_dir)?;
+                builder = builder
+                    .add_source(File::from(repo_cfg).required(false));
+            }
+
             // assuming it parses the toml
-            let builder = ConfigBuilder::builder()
-                .add_source(File::from(cfg_dir))
-                .build()?;
+            let builder = builder.build()?;
+
+            let mut cfg: Config = builder.try_deserialize()?;
+
+            // older configs won't have entries for
+            // newly added providers
+            for (provider, provider_cfg) in
+                Provider::create_defaults()
+            {
+                cfg.ai
+                    .providers
+                    .entry(provider)
+                    .or_insert(provider_cfg);
+            }
+
+            cfg.resolve_secrets()?;
+            cfg.validate()?;
 
-            let cfg: Config = builder.try_deserialize()?;
             Ok(cfg)
         } else {
             Err(anyhow::anyhow!(
@@ -78,22 +188,386 @@ impl Config {
             builder = builder.set_override(key, value)?;
         }
 
-        let config = builder.build()?.try_deserialize()?;
+        let mut config: Config =
+            builder.build()?.try_deserialize()?;
+        config.resolve_secrets()?;
+        config.validate()?;
+
         Ok(config)
     }
+
+    /// writes the model into the global config.toml, only
+    /// that key changes so resolved secrets stay out of it
+    pub fn save_model(
+        provider: Provider,
+        model: &str,
+    ) -> Result<PathBuf> {
+        let path = ProjectDirs::from("com", "nuttycream", "gai")
+            .ok_or_else(|| {
+                anyhow::anyhow!("Cannot find a valid home directory.")
+            })?
+            .config_dir()
+            .join("config.toml");
+
+        let mut cfg: toml::Table =
+            toml::from_str(&fs::read_to_string(&path)?)?;
+
+        let providers = cfg
+            .entry("ai")
+            .or_insert_with(|| toml::Table::new().into())
+            .as_table_mut()
+            .and_then(|ai| {
+                ai.entry("providers")
+                    .or_insert_with(|| toml::Table::new().into())
+                    .as_table_mut()
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "ai.providers in {} isn't a table",
+                    path.display()
+                )
+            })?;
+
+        match providers
+            .get_mut(&provider.to_string())
+            .and_then(|p| p.as_table_mut())
+        {
+            Some(provider_cfg) => {
+                provider_cfg.insert("model".to_owned(), model.into());
+            }
+            // a partial entry wouldn't parse
+            None => {
+                let mut provider_cfg = Provider::create_defaults()
+                    .remove(&provider)
+                    .unwrap_or_else(|| ProviderConfig::new(model));
+                provider_cfg.model = model.to_owned();
+
+                providers.insert(
+                    provider.to_string(),
+                    toml::Value::try_from(provider_cfg)?,
+                );
+            }
+        }
+
+        fs::write(&path, toml::to_string_pretty(&cfg)?)?;
+
+        Ok(path)
+    }
+
+    /// whatever deserializing can't catch
+    fn validate(&self) -> Result<()> {
+        Regex::new(&self.gai.issue_key_regex).map_err(|e| {
+            // the build error alone only says "pattern 0"
+            let reason = e
+                .syntax_error()
+                .map_or_else(|| e.to_string(), |s| s.to_string());
+
+            anyhow::anyhow!(
+                "gai.issue_key_regex isn't a valid regex:\n{}",
+                reason
+            )
+        })?;
+
+        if self.gai.max_commits == Some(0) {
+            return Err(anyhow::anyhow!(
+                "gai.max_commits has to be at least 1"
+            ));
+        }
+
+        for (provider, provider_cfg) in &self.ai.providers {
+            provider_cfg.check_sampling(*provider)?;
+        }
+
+        // only the tui draws them
+        #[cfg(feature = "cli")]
+        self.tui.check_colors()?;
+
+        Ok(())
+    }
+
+    /// swaps env:VAR and file:/path api keys for
+    /// what they point to, inline keys are left alone
+    fn resolve_secrets(&mut self) -> Result<()> {
+        for (provider, provider_cfg) in &mut self.ai.providers {
+            if let Some(api_key) = &provider_cfg.api_key {
+                provider_cfg.api_key =
+                    Some(resolve_secret(*provider, api_key)?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn resolve_secret(provider: Provider, value: &str) -> Result<String> {
+    if let Some(var) = value.strip_prefix("env:") {
+        return std::env::var(var).map_err(|_| {
+            anyhow::anyhow!(
+                "{} api_key: environment variable {} is not set",
+                provider,
+                var
+            )
+        });
+    }
+
+    if let Some(path) = value.strip_prefix("file:") {
+        let secret = fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!(
+                "{} api_key: can't read {}: {}",
+                provider,
+                path,
+                e
+            )
+        })?;
+
+        // secret files usually end with a newline
+        let secret = secret.trim();
+        if secret.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{} api_key: {} is empty",
+                provider,
+                path
+            ));
+        }
+
+        return Ok(secret.to_owned());
+    }
+
+    Ok(value.to_owned())
 }
 
 /// gai git specific settings
-#[derive(Clone, Debug, Serialize, Deserialize, Default)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GaiConfig {
     pub only_staged: bool,
+    /// formatting only changes aren't sent,
+    /// like git diff -w
+    pub ignore_whitespace: bool,
+    /// unchanged lines around each change, like
+    /// git diff -U, more costs more tokens
+    pub diff_context_lines: u32,
     /// should we apply as hunks?
     pub stage_hunks: bool,
+    /// a truncated file still sends the first and last
+    /// lines of its diff, both 0 sends none of it
+    pub truncate_head: usize,
+    pub truncate_tail: usize,
     pub commit_config: CommitConfig,
+
+    /// replaces the built-in prompt, supports
+    /// {diffs}, {file_list}, {rules}, {convention},
+    /// {hint}, {branch}, {file_tree} and {git_status}
+    pub prompt_template: Option<String>,
+
+    /// pre-group files and send one
+    /// request (one commit) per group
+    pub split_strategy: SplitStrategy,
+
+    /// gitignore style globs left out of the diffs,
+    /// on top of whatever is in .gaiignore
+    pub ignore_globs: Vec<String>,
+
+    /// "Name <email>" entries added to every
+    /// applied commit as Co-authored-by trailers
+    pub co_authors: Vec<String>,
+
+    /// literal trailer lines added to every applied
+    /// commit, ex. "Reviewed-by: Jane <jane@example.com>"
+    pub trailers: Vec<String>,
+
+    /// Signed-off-by trailer from the git
+    /// user identity, like git commit -s
+    pub signoff: bool,
+
+    /// language for the commit messages, prefixes
+    /// stay english. ex. "German" or "de-DE"
+    pub language: Option<String>,
+
+    /// a team convention's types and wording, its types
+    /// are used when commit_config.allowed_prefixes isn't set
+    pub style_preset: Option<StylePreset>,
+
+    /// at most this many commits, related changes are
+    /// merged to fit. the model decides when None
+    pub max_commits: Option<usize>,
+
+    /// when there's a single commit it takes every
+    /// file in the diffs, like git add -A, files
+    /// removed from the list are still left out
+    pub auto_stage: bool,
+
+    /// ask before applying from the tui
+    pub confirm_apply: bool,
+
+    /// run through the shell after a successful
+    /// apply, ex. "cargo test", off when None
+    pub post_apply_hook: Option<String>,
+
+    /// recent commit subjects sent along
+    /// as style examples, 0 turns it off
+    pub style_examples_count: usize,
+
+    /// send the branch name along, an issue key
+    /// in it becomes a Refs: footer
+    pub include_branch_context: bool,
+
+    /// finds the issue key in the branch name,
+    /// ex. PROJ-123 in feature/PROJ-123-add-login
+    pub issue_key_regex: String,
+
+    /// files over summarize_min_chars are summarized
+    /// by the provider's summarize_model first, the
+    /// summary is sent in place of their diff
+    pub summarize_large_files: bool,
+    pub summarize_min_chars: usize,
+}
+
+impl GaiConfig {
+    /// the diff length summarize_large_files kicks in at,
+    /// a template that inlines {diffs} gets them whole
+    pub fn summarize_over(&self) -> Option<usize> {
+        let inlined = self
+            .prompt_template
+            .as_deref()
+            .is_some_and(|t| t.contains("{diffs}"));
+
+        (self.summarize_large_files && !inlined)
+            .then_some(self.summarize_min_chars)
+    }
+
+    /// the prefixes commits may use, allowed_prefixes
+    /// when it's set, else the preset's, else all of them
+    pub fn allowed_prefixes(&self) -> Vec<PrefixType> {
+        match (
+            &self.commit_config.allowed_prefixes,
+            self.style_preset,
+        ) {
+            (Some(prefixes), _) => prefixes.clone(),
+            (None, Some(preset)) => {
+                preset.allowed_prefixes().to_vec()
+            }
+            (None, None) => PrefixType::iter().collect(),
+        }
+    }
+
+    /// post_apply_hook, None when it's blank too
+    pub fn hook_command(&self) -> Option<&str> {
+        self.post_apply_hook
+            .as_deref()
+            .filter(|c| !c.trim().is_empty())
+    }
+}
+
+impl Default for GaiConfig {
+    fn default() -> Self {
+        Self {
+            only_staged: false,
+            ignore_whitespace: false,
+            diff_context_lines: DIFF_CONTEXT_LINES,
+            stage_hunks: false,
+            truncate_head: TRUNCATE_PREVIEW_LINES,
+            truncate_tail: TRUNCATE_PREVIEW_LINES,
+            commit_config: CommitConfig::default(),
+            prompt_template: None,
+            split_strategy: SplitStrategy::default(),
+            ignore_globs: vec![],
+            co_authors: vec![],
+            trailers: vec![],
+            signoff: false,
+            language: None,
+            style_preset: None,
+            max_commits: None,
+            auto_stage: false,
+            confirm_apply: true,
+            post_apply_hook: None,
+            style_examples_count: 0,
+            include_branch_context: false,
+            issue_key_regex: ISSUE_KEY_REGEX.to_owned(),
+            summarize_large_files: false,
+            summarize_min_chars: SUMMARIZE_MIN_CHARS,
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize,
+)]
+pub enum SplitStrategy {
+    /// the model groups everything itself
+    #[default]
+    None,
+    /// one group per top level directory,
+    /// files at the root are their own group
+    ByDirectory,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StylePreset {
+    /// angular's contributing guide, no chore or style
+    Angular,
+    /// @commitlint/config-conventional
+    Conventional,
+    /// karma's git commit msg guide
+    Karma,
+    /// the angular types, worded around
+    /// which ones cut a release
+    SemanticRelease,
+}
+
+impl StylePreset {
+    pub fn allowed_prefixes(self) -> &'static [PrefixType] {
+        use PrefixType::*;
+
+        match self {
+            Self::Angular => {
+                &[Build, CI, Docs, Feat, Fix, Refactor, Test]
+            }
+            Self::Conventional | Self::SemanticRelease => &[
+                Build, Chore, CI, Docs, Feat, Fix, Refactor, Revert,
+                Style, Test,
+            ],
+            Self::Karma => &[Feat, Fix, Docs, Refactor, Test, Chore],
+        }
+    }
+
+    /// what the scope should name, on top of ai.rules
+    pub fn scope_rule(self) -> &'static str {
+        match self {
+            Self::Angular => PRESET_SCOPE_ANGULAR,
+            Self::Conventional => PRESET_SCOPE_CONVENTIONAL,
+            Self::Karma => PRESET_SCOPE_KARMA,
+            Self::SemanticRelease => PRESET_SCOPE_SEMANTIC_RELEASE,
+        }
+    }
+
+    /// how the header is worded
+    pub fn subject_rule(self) -> &'static str {
+        match self {
+            Self::Angular => PRESET_SUBJECT_ANGULAR,
+            Self::Conventional => PRESET_SUBJECT_CONVENTIONAL,
+            Self::Karma => PRESET_SUBJECT_KARMA,
+            Self::SemanticRelease => PRESET_SUBJECT_SEMANTIC_RELEASE,
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize,
+)]
+pub enum ScopeStrategy {
+    /// whatever scope the model picked
+    #[default]
+    Model,
+    /// the common parent directory of the commit's
+    /// files, ex. src/tui/app.rs and src/tui/ui.rs -> tui
+    Directory,
 }
 
 /// commit message specific settings
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CommitConfig {
     /// prefix will be capitalized like feat -> Feat
     pub capitalize_prefix: bool,
@@ -104,29 +578,171 @@ pub struct CommitConfig {
     /// define it here before we commit
     pub include_scope: bool,
 
+    /// where the scope comes from, Directory
+    /// ignores the model's pick
+    pub scope_strategy: ScopeStrategy,
+
     /// use breaking symbol
     pub include_breaking: bool,
 
     /// breaking override defaults to "!"
     pub breaking_symbol: Option<char>,
+
+    /// the whole first line is cut on a word
+    /// boundary past this, 0 leaves it alone
+    pub subject_max_len: usize,
+
+    /// follow-up requests asking for a shorter header
+    /// before it's cut, per commit, 0 cuts right away
+    pub shorten_attempts: usize,
+
+    /// body lines are hard wrapped at this
+    /// column, 0 leaves them alone
+    pub body_wrap_col: usize,
+
+    /// sign commits using user.signingkey
+    /// supports gpg.format = ssh as well
+    pub sign: bool,
+
+    /// commits with any other prefix are flagged and
+    /// won't be applied without forcing it, None takes
+    /// the style_preset's or every prefix
+    pub allowed_prefixes: Option<Vec<PrefixType>>,
+
+    /// puts the prefix's gitmoji in front,
+    /// ex. ✨ feat: add endpoint
+    pub gitmoji: bool,
+
+    /// per prefix overrides of the
+    /// built in gitmojis, ex. feat = "🚀"
+    pub gitmojis: HashMap<PrefixType, String>,
     // todo make hashmap for customizable prefix types
     // todo allow user customizable format
 }
 
 /// tui specific settings
-#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TuiConfig {
     /// send out the request
     /// upon launching gai
     pub auto_request: bool,
-    // todo impl keybinds
+
+    /// keybinding profile, unknown
+    /// ones fail when the config loads
+    pub keymap: Keymap,
+
+    /// clicks and the scroll wheel, off since
+    /// it takes over the terminal's own selection
+    pub mouse: bool,
+
+    /// underline likely typos in the commits
+    /// tab, it never blocks applying
+    pub spell_check: bool,
+
+    /// word level highlights in diffs,
+    /// W toggles it while running
+    pub word_diff: bool,
+
+    /// list the new commits after applying,
+    /// off quits right away
+    pub apply_summary: bool,
+
+    /// badge colors by prefix, ex. feat = "green" or
+    /// "#50fa7b", the rest keep their defaults
+    pub prefix_colors: HashMap<PrefixType, String>,
+}
+
+impl Default for TuiConfig {
+    fn default() -> Self {
+        Self {
+            auto_request: false,
+            keymap: Keymap::default(),
+            mouse: false,
+            spell_check: true,
+            word_diff: false,
+            apply_summary: true,
+            prefix_colors: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+impl TuiConfig {
+    fn check_colors(&self) -> Result<()> {
+        for (prefix, color) in &self.prefix_colors {
+            if color.parse::<Color>().is_err() {
+                return Err(anyhow::anyhow!(
+                    "tui.prefix_colors.{}: {} isn't a color, use a name like \"green\", \"#rrggbb\" or 0-255",
+                    format!("{:?}", prefix).to_lowercase(),
+                    color
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the badge color for prefix, checked when
+    /// the config loads so it always parses
+    pub fn prefix_color(&self, prefix: PrefixType) -> Color {
+        self.prefix_colors
+            .get(&prefix)
+            .map(String::as_str)
+            .unwrap_or(prefix.color())
+            .parse()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Keymap {
+    #[default]
+    Default,
+    /// adds gg, G and :q
+    Vim,
 }
 
 /// anything dealing with the LLM request
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AiConfig {
     /// Enabled provider
     pub provider: Provider,
+
+    /// providers to try, in order,
+    /// when the enabled one fails
+    pub fallback: Vec<Provider>,
+
+    /// retries per provider on 429/5xx
+    /// and connection errors
+    pub max_retries: u32,
+
+    /// split groups sent at the same time
+    pub max_concurrency: usize,
+
+    /// first retry waits this long, doubling after
+    pub retry_backoff_ms: u64,
+
+    /// cap on the total time spent
+    /// waiting between retries
+    pub retry_max_wait_secs: u64,
+
+    /// how long a response stays cached
+    /// for the same diffs, 0 turns it off
+    pub cache_ttl_secs: u64,
+
+    /// the biggest files get truncated until the
+    /// diffs fit in this, 0 turns it off
+    pub max_diff_chars: usize,
+
+    /// marks the system prompt and diffs
+    /// as cacheable for claude
+    pub prompt_cache: bool,
+
     /// provider specific configuration
     pub providers: HashMap<Provider, ProviderConfig>,
 
@@ -150,7 +766,8 @@ pub struct AiConfig {
     /// include git status
     pub include_git_status: bool,
 
-    /// should we send untracked files as well?
+    /// untracked files go in the diffs,
+    /// gitignored ones never do
     pub include_untracked: bool,
 
     /// files that gai will be TRUNCATED
@@ -170,6 +787,7 @@ pub struct AiConfig {
 /// this is rules/constraints to send the ai
 /// along with the prompt
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct RuleConfig {
     /// group related files into logical commits based on the type of prefix
     pub group_related_files: bool,
@@ -199,6 +817,9 @@ pub struct RuleConfig {
     /// allows the creation of commit bodies
     pub allow_body: bool,
 
+    /// allows footers like BREAKING CHANGE: or Refs:
+    pub allow_footers: bool,
+
     // todo add hard validation
     /// max length of commit body
     pub max_body_length: u16,
@@ -208,12 +829,83 @@ pub struct RuleConfig {
 pub struct ProviderConfig {
     pub model: String,
     pub max_tokens: u64,
+
+    /// where to send requests for self hosted and openai
+    /// compatible providers, ex. ollama on http://localhost:11434
+    pub base_url: Option<String>,
+
+    /// azure openai resource, ex.
+    /// https://my-resource.openai.azure.com
+    pub endpoint: Option<String>,
+    /// azure deployment name, defaults to the model
+    pub deployment: Option<String>,
+    /// azure api-version query param
+    pub api_version: Option<String>,
+
+    /// aws region for bedrock, ex. us-east-1
+    /// falls back to AWS_REGION
+    pub region: Option<String>,
+
+    /// for providers that don't read theirs
+    /// from the environment only, ex. OpenRouter or Groq
+    /// also takes env:VAR or file:/path
+    pub api_key: Option<String>,
+
+    /// sent as the system message ahead of the
+    /// prompt, ex. "never use emoji"
+    pub system_prompt: Option<String>,
+
+    /// sampling, unset leaves it to the provider
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+
+    /// USD per million input tokens
+    /// used for the estimate shown before sending
+    pub input_price: Option<f64>,
+
+    /// the model's max context in tokens, a prompt that
+    /// won't fit next to max_tokens is never sent
+    pub context_window: Option<u64>,
+
+    /// something cheaper for summarize_large_files,
+    /// the model is used when unset
+    pub summarize_model: Option<String>,
+
+    /// gemini's thinking, off unless set, thought
+    /// parts never make it into the commits
+    #[serde(default)]
+    pub reasoning: bool,
+
+    /// sent with every request to the provider, ex.
+    /// X-Title for OpenRouter or a proxy's X-Org-Id
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// lets headers replace the one carrying the key,
+    /// otherwise a clashing header is dropped
+    #[serde(default)]
+    pub override_auth_headers: bool,
+
+    /// give up on the request after this long
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_timeout_secs() -> u64 {
+    DEFAULT_TIMEOUT_SECS
 }
 
 impl Default for AiConfig {
     fn default() -> Self {
         Self {
             provider: Provider::Gai,
+            fallback: vec![],
+            max_retries: 2,
+            max_concurrency: 4,
+            retry_backoff_ms: 500,
+            retry_max_wait_secs: 30,
+            cache_ttl_secs: 60 * 60 * 24,
+            max_diff_chars: 200_000,
+            prompt_cache: true,
             system_prompt: None,
             commit_convention: None,
             include_convention: true,
@@ -228,6 +920,29 @@ impl Default for AiConfig {
     }
 }
 
+impl AiConfig {
+    /// the enabled provider followed by any fallbacks
+    /// skipping duplicates and ones without a config
+    pub fn provider_chain(&self) -> Vec<(Provider, ProviderConfig)> {
+        let mut chain: Vec<(Provider, ProviderConfig)> = Vec::new();
+
+        for provider in std::iter::once(self.provider)
+            .chain(self.fallback.iter().copied())
+        {
+            if chain.iter().any(|(p, _)| *p == provider) {
+                continue;
+            }
+
+            if let Some(provider_cfg) = self.providers.get(&provider)
+            {
+                chain.push((provider, provider_cfg.to_owned()));
+            }
+        }
+
+        chain
+    }
+}
+
 impl Default for RuleConfig {
     fn default() -> Self {
         Self {
@@ -239,6 +954,7 @@ impl Default for RuleConfig {
             allow_empty_scope: true,
             max_header_length: 52,
             allow_body: true,
+            allow_footers: true,
             max_body_length: 72,
         }
     }
@@ -249,17 +965,218 @@ impl Default for CommitConfig {
         Self {
             capitalize_prefix: false,
             include_scope: true,
+            scope_strategy: ScopeStrategy::default(),
             include_breaking: true,
             breaking_symbol: None,
+            subject_max_len: 72,
+            shorten_attempts: 1,
+            body_wrap_col: 72,
+            sign: false,
+            allowed_prefixes: None,
+            gitmoji: false,
+            gitmojis: HashMap::new(),
         }
     }
 }
 
+impl CommitConfig {
+    /// None with gitmoji off
+    pub fn gitmoji_for(&self, prefix: PrefixType) -> Option<&str> {
+        if !self.gitmoji {
+            return None;
+        }
+
+        Some(
+            self.gitmojis
+                .get(&prefix)
+                .map(String::as_str)
+                .unwrap_or(prefix.gitmoji()),
+        )
+    }
+}
+
 impl ProviderConfig {
+    /// a provider that won't take the value would fail every
+    /// request with it, better to say so before sending
+    fn check_sampling(&self, provider: Provider) -> Result<()> {
+        if let Some(temperature) = self.temperature {
+            // anthropic's api, direct or through bedrock,
+            // stops at 1 where the rest go up to 2
+            let max = match provider {
+                Provider::Claude | Provider::Bedrock => 1.0,
+                _ => 2.0,
+            };
+
+            if !(0.0..=max).contains(&temperature) {
+                return Err(anyhow::anyhow!(
+                    "ai.providers.{}.temperature has to be between 0 and {}",
+                    provider,
+                    max
+                ));
+            }
+
+            if temperature != 1.0 && is_reasoning_model(&self.model) {
+                return Err(anyhow::anyhow!(
+                    "{} only takes the default temperature, remove ai.providers.{}.temperature",
+                    self.model,
+                    provider
+                ));
+            }
+        }
+
+        if let Some(top_p) = self.top_p
+            && !(0.0..=1.0).contains(&top_p)
+        {
+            return Err(anyhow::anyhow!(
+                "ai.providers.{}.top_p has to be between 0 and 1",
+                provider
+            ));
+        }
+
+        Ok(())
+    }
+
     pub fn new(model_name: &str) -> Self {
         Self {
             model: model_name.to_owned(),
             max_tokens: 5000,
+            base_url: None,
+            endpoint: None,
+            deployment: None,
+            api_version: None,
+            region: None,
+            api_key: None,
+            system_prompt: None,
+            temperature: None,
+            top_p: None,
+            input_price: None,
+            context_window: None,
+            summarize_model: None,
+            reasoning: false,
+            headers: HashMap::new(),
+            override_auth_headers: false,
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_is_checked_against_the_model() {
+        let mut cfg = ProviderConfig::new("gpt-5-nano");
+        cfg.temperature = Some(0.2);
+        assert!(cfg.check_sampling(Provider::OpenAI).is_err());
+
+        cfg.temperature = Some(1.0);
+        assert!(cfg.check_sampling(Provider::OpenAI).is_ok());
+
+        cfg.model = "openai/o3-mini".to_owned();
+        cfg.temperature = Some(0.2);
+        assert!(cfg.check_sampling(Provider::OpenRouter).is_err());
+
+        cfg.model = "gpt-4o".to_owned();
+        assert!(cfg.check_sampling(Provider::OpenAI).is_ok());
+
+        cfg.top_p = Some(1.5);
+        assert!(cfg.check_sampling(Provider::OpenAI).is_err());
+
+        cfg.top_p = None;
+        cfg.temperature = Some(1.5);
+        assert!(cfg.check_sampling(Provider::OpenAI).is_ok());
+
+        cfg.model = "claude-haiku-4-5".to_owned();
+        assert!(cfg.check_sampling(Provider::Claude).is_err());
+        assert!(cfg.check_sampling(Provider::Bedrock).is_err());
+
+        cfg.temperature = Some(0.7);
+        assert!(cfg.check_sampling(Provider::Claude).is_ok());
+    }
+
+    #[test]
+    fn secrets_resolve_from_env_and_files() {
+        let var = format!("GAI_TEST_KEY_{}", std::process::id());
+        // nothing else in the tests reads it
+        unsafe { std::env::set_var(&var, "from-env") };
+        assert_eq!(
+            resolve_secret(Provider::Gemini, &format!("env:{}", var))
+                .unwrap(),
+            "from-env"
+        );
+        unsafe { std::env::remove_var(&var) };
+        assert!(
+            resolve_secret(Provider::Gemini, &format!("env:{}", var))
+                .is_err()
+        );
+
+        let path = std::env::temp_dir()
+            .join(format!("gai-secret-{}", std::process::id()));
+        fs::write(&path, "from-file\n").unwrap();
+        let value = format!("file:{}", path.display());
+        assert_eq!(
+            resolve_secret(Provider::AzureOpenAI, &value).unwrap(),
+            "from-file"
+        );
+
+        fs::write(&path, "\n").unwrap();
+        assert!(
+            resolve_secret(Provider::AzureOpenAI, &value).is_err()
+        );
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(
+            resolve_secret(Provider::OpenAI, "sk-inline").unwrap(),
+            "sk-inline"
+        );
+    }
+
+    #[test]
+    fn repo_config_cant_set_trusted_keys() {
+        let dir = std::env::temp_dir()
+            .join(format!("gai-repo-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(REPO_CONFIG);
+        let global = dir.join("config.toml");
+
+        fs::write(&path, "[gai]\nlanguage = \"German\"\n").unwrap();
+        assert!(check_repo_config(&path, &global).is_ok());
+
+        for denied in [
+            "[gai]\npost_apply_hook = \"make\"\n",
+            "[ai.providers.Claude]\nbase_url = \"http://x\"\n",
+            "[ai.providers.OpenAI]\napi_key = \"file:/etc/passwd\"\n",
+            "[ai.providers.Groq.headers]\nX-Key = \"1\"\n",
+        ] {
+            fs::write(&path, denied).unwrap();
+            assert!(check_repo_config(&path, &global).is_err());
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn preset_types_only_fill_an_unset_allowed_prefixes() {
+        let mut cfg = Config::default();
+        cfg.gai.style_preset = Some(StylePreset::Karma);
+        assert_eq!(
+            cfg.gai.allowed_prefixes(),
+            StylePreset::Karma.allowed_prefixes()
+        );
+
+        // every prefix, same as having none set, still wins
+        let every: Vec<PrefixType> = PrefixType::iter().collect();
+        cfg.gai.commit_config.allowed_prefixes = Some(every.clone());
+        assert_eq!(cfg.gai.allowed_prefixes(), every);
+
+        cfg.gai.commit_config.allowed_prefixes = None;
+        let cfg = cfg
+            .override_cfg(&["gai.style_preset=angular".to_owned()])
+            .unwrap();
+        assert_eq!(
+            cfg.gai.allowed_prefixes(),
+            StylePreset::Angular.allowed_prefixes()
+        );
+    }
+}
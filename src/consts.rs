@@ -5,9 +5,125 @@ pub const PROGRESS_TICK: &[&str; 9] =
 pub const CHATGPT_DEFAULT: &str = "gpt-5-nano";
 pub const CLAUDE_DEFAULT: &str = "claude-3-5-haiku";
 pub const GEMINI_DEFAULT: &str = "gemini-2.5-flash";
+pub const OLLAMA_DEFAULT: &str = "llama3.1";
+pub const OPENROUTER_DEFAULT: &str = "anthropic/claude-3.5-haiku";
+pub const GROQ_DEFAULT: &str = "llama-3.3-70b-versatile";
+pub const DEEPSEEK_DEFAULT: &str = "deepseek-chat";
+pub const MISTRAL_DEFAULT: &str = "mistral-large-latest";
+pub const COHERE_DEFAULT: &str = "command-r-plus";
+pub const BEDROCK_DEFAULT: &str =
+    "anthropic.claude-3-5-haiku-20241022-v1:0";
+
+/// the model picker's list for providers
+/// without a models endpoint we can use
+pub const GEMINI_MODELS: &[&str] = &[
+    "gemini-2.5-flash",
+    "gemini-2.5-flash-lite",
+    "gemini-2.5-pro",
+    "gemini-2.0-flash",
+];
+pub const CLAUDE_MODELS: &[&str] = &[
+    "claude-3-5-haiku",
+    "claude-3-7-sonnet-latest",
+    "claude-sonnet-4-0",
+    "claude-sonnet-4-5",
+    "claude-opus-4-1",
+];
+/// azure lists deployments, not models
+pub const AZURE_MODELS: &[&str] =
+    &["gpt-5-nano", "gpt-5-mini", "gpt-5", "gpt-4o-mini", "gpt-4o"];
+pub const BEDROCK_MODELS: &[&str] = &[
+    "anthropic.claude-3-5-haiku-20241022-v1:0",
+    "anthropic.claude-3-5-sonnet-20241022-v2:0",
+    "anthropic.claude-3-7-sonnet-20250219-v1:0",
+    "anthropic.claude-sonnet-4-20250514-v1:0",
+];
+
+pub const OLLAMA_HOST: &str = "http://localhost:11434";
+pub const OPENAI_URL: &str = "https://api.openai.com/v1";
+pub const OPENROUTER_URL: &str = "https://openrouter.ai/api/v1";
+pub const GROQ_URL: &str = "https://api.groq.com/openai/v1";
+pub const DEEPSEEK_URL: &str = "https://api.deepseek.com";
+pub const MISTRAL_URL: &str = "https://api.mistral.ai/v1";
+pub const ANTHROPIC_URL: &str = "https://api.anthropic.com/v1";
+/// without a version, chat is on v2
+/// and the model list is still v1
+pub const COHERE_URL: &str = "https://api.cohere.com";
+
+/// the messages api's one stable version
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// first GA version with structured outputs
+pub const AZURE_API_VERSION: &str = "2024-10-21";
+
+/// the only version bedrock takes for claude
+pub const BEDROCK_ANTHROPIC_VERSION: &str = "bedrock-2023-05-31";
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+/// thinking tokens for gemini under reasoning, at
+/// most half of max_tokens since they count against it
+pub const GEMINI_THINKING_BUDGET: u64 = 2048;
+
+/// git's own default for -U
+pub const DIFF_CONTEXT_LINES: u32 = 3;
+
+/// what a truncated file keeps from
+/// each end of its diff
+pub const TRUNCATE_PREVIEW_LINES: usize = 10;
+
+/// removes/truncates undo remembers in the tui
+pub const UNDO_HISTORY: usize = 32;
+
+/// bumped whenever the --format json output changes
+pub const JSON_OUTPUT_VERSION: u32 = 1;
+
+/// per repo overrides, merged over the global config
+pub const REPO_CONFIG: &str = ".gai.toml";
+
+/// provider keys only the global config can set, they decide
+/// where the api key goes and what gets read from disk
+pub const REPO_DENIED_PROVIDER_KEYS: &[&str] = &[
+    "base_url",
+    "endpoint",
+    "region",
+    "api_key",
+    "headers",
+    "override_auth_headers",
+];
+
+/// jira style keys, ex. PROJ-123
+pub const ISSUE_KEY_REGEX: &str = r"[A-Z][A-Z0-9]+-[0-9]+";
 
 pub const DEFAULT_SYS_PROMPT: &str = "You are an expert at git operations. Create git a logical list of git commits based on diffs and structure.";
 
+pub const PROMPT_DIFFS_INLINE: &str =
+    "Create the commits for the diffs included above.";
+
+pub const PROMPT_SPLIT_GROUP: &str = "These files were grouped ahead of time, create exactly ONE commit that covers all of them.";
+
+pub const PROMPT_STYLE_EXAMPLES: &str =
+    "Match the style of these recent commits:";
+
+/// cap on what the style examples add to the prompt
+pub const STYLE_EXAMPLES_MAX_CHARS: usize = 1500;
+
+/// diffs longer than this get summarized
+/// first under summarize_large_files
+pub const SUMMARIZE_MIN_CHARS: usize = 12_000;
+
+pub const PROMPT_SUMMARIZE: &str = "This diff is for a single file that is too large to send along with the others, summarize it as exactly ONE commit. \
+    The header says what changed, the body lists every meaningful change (what was added, removed or reworked, and why) in at most 10 short lines. \
+    The real commits are written from this summary, so leave out nothing that matters.";
+
+pub const PROMPT_SHORTEN_SUBJECT: &str = "This is a commit header that is too long. Rewrite it to fit the maximum length given, keeping its meaning and its imperative mood. \
+    Return exactly ONE commit with only the new header, keep prefix, scope and breaking as they are and leave the body, footers, files and hunk_ids empty.";
+
+pub const PROMPT_STRUCTURED_ONLY: &str = "\nYour last answer couldn't be parsed. Respond with only the structured format, \
+    a single JSON object that matches the schema, with no prose, markdown or code fences around it.";
+
+pub const PROMPT_REGENERATE: &str = "\nThese diffs belong to a single commit, create exactly ONE commit for them.";
+
 pub const LOGO: &str = r#""#;
 
 pub const PROMPT_STAGE_HUNKS: &str = "Fill hunk_ids with the HUNK_ID values shown in the diffs (format: \"filepath:index\").\
@@ -34,8 +150,7 @@ pub const RULE_COMMIT_MESSAGE_HEADER: &str =
 
 pub const RULE_PREFIX: &str = "  * prefix: Select the appropriate type from the PrefixType enum\n";
 
-pub const RULE_BREAKING: &str =
-    "  * breaking: Set to true if breaking change, false otherwise\n";
+pub const RULE_BREAKING: &str = "  * breaking: Set to true if the change breaks existing users, ex. removed or renamed public functions, types or config options, changed signatures, return types or defaults, dropped CLI flags. Internal refactors are not breaking\n";
 
 // Base instructions - will be combined with length/scope rules
 // ideally we include examples atp
@@ -47,6 +162,11 @@ pub const RULE_HEADER_BASE: &str = "  * header: CRITICAL - This field contains O
 
 pub const RULE_BODY_BASE: &str = "  * body: Provide detailed explanation of what changed and why\n";
 
+pub const RULE_FOOTERS: &str = "  * footers: Git trailers, one per entry (e.g., 'Refs: #123', 'Co-authored-by: ...')\n\
+    - If breaking is true, include 'BREAKING CHANGE: <what breaks and how to migrate>'\n\
+    - Only reference issues that appear in the hint or diffs, NEVER make them up\n\
+    - Use an empty list when there are none\n";
+
 pub const RULE_MESSAGE_VERBOSE: &str = "    - Make descriptions ACCURATE and VERBOSE\n\
     - Descriptions must closely align with the actual code changes\n";
 
@@ -58,6 +178,16 @@ pub const RULE_SCOPE_ALLOW_EMPTY_NO_EXTENSION: &str = "  * scope: Component name
 pub const RULE_SCOPE_REQUIRED_WITH_EXTENSION: &str = "  * scope: Component name with extension (e.g., 'main.rs', 'parser')\n";
 pub const RULE_SCOPE_REQUIRED_NO_EXTENSION: &str = "  * scope: Component name WITHOUT extension (e.g., 'main', 'parser')\n";
 
+pub const PRESET_SCOPE_ANGULAR: &str = "    - The scope names the affected package or area, ex. 'router' or 'compiler'\n";
+pub const PRESET_SCOPE_CONVENTIONAL: &str = "    - The scope is a noun for the section of the codebase, ex. 'parser'\n";
+pub const PRESET_SCOPE_KARMA: &str = "    - The scope is where the change happened, ex. 'config' or 'server', use '*' when it spans several places\n";
+pub const PRESET_SCOPE_SEMANTIC_RELEASE: &str = "    - The scope names the affected area, it ends up in the release notes\n";
+
+pub const PRESET_SUBJECT_ANGULAR: &str = "    - Use the imperative, present tense: 'change' not 'changed' nor 'changes'\n    - Don't capitalize the first letter and don't end with a period\n";
+pub const PRESET_SUBJECT_CONVENTIONAL: &str = "    - Use the imperative mood in lower case, without a period at the end\n";
+pub const PRESET_SUBJECT_KARMA: &str = "    - Use the imperative, present tense: 'change' not 'changed' nor 'changes'\n    - Don't capitalize the first letter and no dot at the end\n";
+pub const PRESET_SUBJECT_SEMANTIC_RELEASE: &str = "    - Use the imperative, present tense, it is read in the release notes\n    - The prefix decides the release: feat is a minor, fix a patch and breaking a major. Only use feat and fix for changes users should get a release for\n";
+
 pub const HUNK_INSTRUCTIONS: &str = "\n## File/Hunk Instructions:\n\
 Fill hunk_ids with the HUNK_ID values shown in the diffs (format: \"filepath:index\").\n\
 Each hunk can only appear in ONE commit.\n\
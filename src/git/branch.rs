@@ -0,0 +1,57 @@
+use anyhow::{Result, anyhow};
+use git2::{Branch, BranchType, build::CheckoutBuilder};
+
+use crate::git::repo::GaiGit;
+
+impl GaiGit {
+    pub fn branch_exists(&self, name: &str) -> bool {
+        self.repo.find_branch(name, BranchType::Local).is_ok()
+    }
+
+    /// creates name from HEAD and switches to it, the index
+    /// and working tree are left alone since it points at
+    /// the same commit. on a branch without commits yet HEAD
+    /// just moves over, the first commit creates it
+    pub fn switch_to_new_branch(&self, name: &str) -> Result<()> {
+        if !Branch::name_is_valid(name)? {
+            return Err(anyhow!(
+                "{} is not a valid branch name",
+                name
+            ));
+        }
+
+        if self.branch_exists(name) {
+            return Err(anyhow!("branch {} already exists", name));
+        }
+
+        if let Ok(head) = self.repo.head() {
+            let commit = head.peel_to_commit()?;
+            self.repo.branch(name, &commit, false)?;
+        }
+
+        self.repo.set_head(&format!("refs/heads/{}", name))?;
+
+        Ok(())
+    }
+
+    /// git checkout name, uncommitted changes carry over
+    /// and anything they'd conflict with is refused
+    pub fn checkout_branch(&self, name: &str) -> Result<()> {
+        let branch =
+            self.repo.find_branch(name, BranchType::Local)?;
+        let reference = branch.into_reference();
+        let target = reference.peel_to_tree()?;
+
+        self.repo.checkout_tree(
+            target.as_object(),
+            Some(CheckoutBuilder::new().safe()),
+        )?;
+
+        let refname = reference.name().ok_or_else(|| {
+            anyhow!("branch name is not valid utf8")
+        })?;
+        self.repo.set_head(refname)?;
+
+        Ok(())
+    }
+}
@@ -1,60 +1,207 @@
-use crate::ai::response::ResponseCommit;
+use crate::{ai::response::ResponseCommit, config::CommitConfig};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct GaiCommit {
     pub files: Vec<String>,
     pub hunk_ids: Vec<String>,
-    pub message: String,
+
+    /// first line, ex. feat(api): add endpoint
+    pub header: String,
+    pub body: Option<String>,
+    /// trailers, ex. BREAKING CHANGE: ... or Refs: #123
+    pub footers: Vec<String>,
 }
 
 impl GaiCommit {
     pub fn from_response(
         response: &ResponseCommit,
-        capitalize_prefix: bool,
-        include_scope: bool,
+        cfg: &CommitConfig,
     ) -> Self {
-        let message = {
-            let prefix = if capitalize_prefix {
-                format!("{:?}", response.message.prefix)
-                    .to_uppercase()
-            } else {
-                format!("{:?}", response.message.prefix)
-                    .to_lowercase()
-            };
+        if let Some(edited) = &response.message.edited {
+            let (header, body) =
+                edited.split_once('\n').unwrap_or((edited, ""));
+            let body = body.trim();
 
-            let breaking =
-                if response.message.breaking { "!" } else { "" };
-            let scope = if include_scope
-                && !response.message.scope.is_empty()
-            {
-                // gonna set it to lowercase PERMA
-                // sometimes the AI responds with a scope
-                // that includes the file extension and is capitalized
-                // like (Respfileonse.rs) which looks ridiculous imo
-                // the only way i can think of is to make it a rule to not include
-                // extension names
-                format!("({})", response.message.scope.to_lowercase())
-            } else {
-                "".to_owned()
+            // footers stay part of the body here,
+            // we don't know what the user meant,
+            // and it isn't wrapped either
+            return GaiCommit {
+                files: response.files.to_owned(),
+                hunk_ids: response.hunk_ids.to_owned(),
+                header: header.trim().to_owned(),
+                body: (!body.is_empty()).then(|| body.to_owned()),
+                footers: Vec::new(),
             };
+        }
+
+        // gonna set the scope to lowercase PERMA
+        // sometimes the AI responds with a scope
+        // that includes the file extension and is capitalized
+        // like (Respfileonse.rs) which looks ridiculous imo
+        // the only way i can think of is to make it a rule to not include
+        // extension names
+        let header = format!(
+            "{}: {}",
+            response.get_commit_prefix(cfg),
+            response.message.header,
+        );
+
+        let header = truncate_subject(&header, cfg.subject_max_len);
+        let body = wrap_body(
+            response.message.body.trim(),
+            cfg.body_wrap_col,
+        );
 
-            format!(
-                "{}{}{}: {}\n{}",
-                prefix,
-                breaking,
-                scope,
-                response.message.header,
-                if response.message.body.is_empty() {
-                    String::new()
-                } else {
-                    format!("\n\n{}", response.message.body)
-                }
-            )
-        };
         GaiCommit {
             files: response.files.to_owned(),
             hunk_ids: response.hunk_ids.to_owned(),
-            message,
+            header,
+            body: (!body.is_empty()).then_some(body),
+            footers: response
+                .message
+                .footers
+                .iter()
+                .map(|f| f.trim().to_owned())
+                .filter(|f| !f.is_empty())
+                .collect(),
+        }
+    }
+
+    /// the response as it'll be committed, with the
+    /// header cut and the body wrapped, for previews
+    pub fn preview(
+        response: &ResponseCommit,
+        cfg: &CommitConfig,
+    ) -> ResponseCommit {
+        let mut preview = response.to_owned();
+        if preview.message.edited.is_some() {
+            return preview;
+        }
+
+        let commit = Self::from_response(response, cfg);
+        if let Some((_, header)) = commit.header.split_once(": ") {
+            preview.message.header = header.to_owned();
+        }
+        preview.message.body = commit.body.unwrap_or_default();
+        preview.message.scope = if cfg.include_scope {
+            response.scope(cfg)
+        } else {
+            String::new()
+        };
+
+        preview
+    }
+
+    /// full message, header, body and footers
+    /// each separated by a blank line
+    pub fn message(&self) -> String {
+        self.message_with_trailers(&[])
+    }
+
+    /// message() with extra trailers after the footers,
+    /// blank ones and ones already there get skipped
+    pub fn message_with_trailers(
+        &self,
+        trailers: &[String],
+    ) -> String {
+        let mut message = self.header.to_owned();
+
+        if let Some(body) = &self.body {
+            message.push_str("\n\n");
+            message.push_str(body);
+        }
+
+        let mut footers = self.footers.to_owned();
+        for trailer in trailers {
+            let trailer = trailer.trim();
+            if trailer.is_empty() {
+                continue;
+            }
+
+            let exists = footers.iter().any(|f| f == trailer)
+                || self
+                    .body
+                    .as_ref()
+                    .is_some_and(|b| b.contains(trailer));
+
+            if !exists {
+                footers.push(trailer.to_owned());
+            }
         }
+
+        // git only picks up trailers in the
+        // last paragraph, so keep the blank line
+        if !footers.is_empty() {
+            message.push_str("\n\n");
+            message.push_str(&footers.join("\n"));
+        }
+
+        message.push('\n');
+        message
+    }
+}
+
+/// cuts at the last space that fits,
+/// or mid word if there isn't one
+fn truncate_subject(subject: &str, max_len: usize) -> String {
+    if max_len == 0 || subject.chars().count() <= max_len {
+        return subject.to_owned();
+    }
+
+    // one extra so a space right at the limit counts
+    let cut: String = subject.chars().take(max_len + 1).collect();
+
+    match cut.rsplit_once(' ') {
+        Some((kept, _)) if !kept.trim().is_empty() => {
+            kept.trim_end().to_owned()
+        }
+        _ => cut.chars().take(max_len).collect(),
+    }
+}
+
+/// hard wraps each line on its own so lists and blank
+/// lines survive, continuations keep the indent of
+/// the line plus any bullet, ex. "- " or "* "
+fn wrap_body(body: &str, col: usize) -> String {
+    if col == 0 {
+        return body.to_owned();
+    }
+
+    let mut wrapped = Vec::new();
+
+    for line in body.lines() {
+        if line.chars().count() <= col {
+            wrapped.push(line.to_owned());
+            continue;
+        }
+
+        let indent = line.len() - line.trim_start().len();
+        let bullet = ["- ", "* ", "+ "]
+            .iter()
+            .find(|b| line[indent..].starts_with(*b))
+            .map_or(0, |b| b.len());
+        let hang = " ".repeat(indent + bullet);
+
+        let mut current = line[..indent + bullet].to_owned();
+        let mut empty = true;
+
+        for word in line[indent + bullet..].split_whitespace() {
+            let len = current.chars().count() + word.chars().count();
+            if !empty && len + 1 > col {
+                wrapped.push(current);
+                current = hang.to_owned();
+                empty = true;
+            }
+
+            if !empty {
+                current.push(' ');
+            }
+            current.push_str(word);
+            empty = false;
+        }
+
+        wrapped.push(current);
     }
+
+    wrapped.join("\n")
 }
@@ -1,5 +1,4 @@
-use git2::{DiffHunk, DiffLine, DiffOptions};
-use walkdir::WalkDir;
+use git2::{DiffFindOptions, DiffHunk, DiffLine, DiffOptions};
 
 use crate::git::repo::{
     DiffType, GaiFile, GaiGit, HunkDiff, LineDiff,
@@ -12,14 +11,24 @@ impl GaiGit {
     ) -> Result<(), git2::Error> {
         // start this puppy up
         let mut opts = DiffOptions::new();
-        opts.include_untracked(true)
-            .recurse_untracked_dirs(true)
-            .enable_fast_untracked_dirs(true);
+        opts.context_lines(self.diff_context_lines);
+        for path in &self.paths {
+            opts.pathspec(path);
+        }
+
+        // untracked files come in diffed against
+        // /dev/null, libgit2 skips the gitignored ones
+        // and they can't be staged anyways
+        if self.include_untracked && !self.only_staged {
+            opts.include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .show_untracked_content(true);
+        }
 
         let repo = &self.repo;
 
         let head = repo.head()?.peel_to_tree()?;
-        let diff = if self.only_staged {
+        let mut diff = if self.only_staged {
             repo.diff_tree_to_index(
                 Some(&head),
                 None,
@@ -29,86 +38,157 @@ impl GaiGit {
             repo.diff_tree_to_workdir(Some(&head), Some(&mut opts))?
         };
 
-        let mut gai_files: Vec<GaiFile> = Vec::new();
-
-        diff.print(git2::DiffFormat::Patch, |delta, hunk, line| {
-            let path = delta
-                .new_file()
-                .path()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_owned();
-
-            let should_truncate =
-                files_to_truncate.iter().any(|f| path.ends_with(f));
-
-            let gai_file =
-                match gai_files.iter_mut().find(|g| g.path == path) {
-                    Some(existing) => existing,
-                    None => {
-                        gai_files.push(GaiFile {
-                            path: path.clone(),
-                            should_truncate,
-                            hunks: Vec::new(),
-                        });
-                        gai_files.last_mut().unwrap()
-                    }
-                };
-
-            process_file_diff(&mut gai_file.hunks, &hunk, &line);
-
-            true
-        })?;
-
-        if self.only_staged {
-            return Ok(());
-        }
+        // a plain mv leaves the new file untracked,
+        // pair those with the deletion too
+        diff.find_similar(Some(
+            DiffFindOptions::new().renames(true).for_untracked(true),
+        ))?;
 
+        let gai_files = collect_files(&diff, files_to_truncate)?;
+
+        let (ignored, gai_files) = self.split_ignored(gai_files);
+        // a rebuild starts over, or toggling piles
+        // up the same paths again
+        self.ignored = ignored;
         self.files = gai_files;
+        self.files.sort_by_key(|g| g.should_truncate);
+
+        Ok(())
+    }
+
+    /// what HEAD changed against its parent, used
+    /// instead of create_diffs when amending
+    pub fn create_amend_diffs(
+        &mut self,
+        files_to_truncate: &[String],
+    ) -> Result<(), git2::Error> {
+        let head = self.repo.head()?.peel_to_commit()?;
+        // a root commit is diffed against nothing
+        let parent = match head.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+
+        let mut diff = self.repo.diff_tree_to_tree(
+            parent.as_ref(),
+            Some(&head.tree()?),
+            Some(
+                DiffOptions::new()
+                    .context_lines(self.diff_context_lines),
+            ),
+        )?;
+        diff.find_similar(Some(
+            DiffFindOptions::new().renames(true),
+        ))?;
+
+        let gai_files = collect_files(&diff, files_to_truncate)?;
 
-        // handle untracked files here
-        for path in &self.status.u_new {
-            let should_truncate =
-                files_to_truncate.iter().any(|f| path.ends_with(f));
+        let (ignored, gai_files) = self.split_ignored(gai_files);
+        self.ignored = ignored;
+        self.files = gai_files;
+        self.files.sort_by_key(|g| g.should_truncate);
+
+        Ok(())
+    }
+
+    /// a unified diff, ex. from git diff or a .patch file,
+    /// in place of the working tree. it doesn't have to
+    /// apply to this repo, nothing gets committed
+    pub fn create_patch_diffs(
+        &mut self,
+        patch: &[u8],
+        files_to_truncate: &[String],
+    ) -> Result<(), git2::Error> {
+        let diff = git2::Diff::from_buffer(patch)?;
+
+        let gai_files = collect_files(&diff, files_to_truncate)?;
+
+        let (ignored, gai_files) = self.split_ignored(gai_files);
+        self.ignored = ignored;
+        self.files = gai_files;
+        self.files.sort_by_key(|g| g.should_truncate);
+        self.from_patch = true;
+
+        Ok(())
+    }
 
-            for entry in WalkDir::new(path)
-                .follow_links(true)
+    /// (ignored paths, the rest), ignored files are
+    /// still there for git, just not for the AI
+    fn split_ignored(
+        &self,
+        gai_files: Vec<GaiFile>,
+    ) -> (Vec<String>, Vec<GaiFile>) {
+        let (ignored, gai_files): (Vec<GaiFile>, Vec<GaiFile>) =
+            gai_files
                 .into_iter()
-                .filter_map(|e| e.ok())
-            {
-                if entry.path().is_file()
-                    && let Ok(content) =
-                        std::fs::read_to_string(entry.path())
-                {
-                    let path = entry.path().to_str().unwrap();
-                    let lines: Vec<LineDiff> = content
-                        .lines()
-                        .map(|line| LineDiff {
-                            diff_type: DiffType::Additions,
-                            content: format!("{}\n", line),
-                        })
-                        .collect();
-
-                    self.files.push(GaiFile {
-                        path: path.to_owned(),
+                .partition(|g| self.is_gai_ignored(&g.path));
+
+        // a rename's old path has to go with it when
+        // the ignored file is committed
+        let ignored = ignored
+            .into_iter()
+            .flat_map(|g| std::iter::once(g.path).chain(g.old_path))
+            .collect();
+
+        (ignored, gai_files)
+    }
+}
+
+fn collect_files(
+    diff: &git2::Diff,
+    files_to_truncate: &[String],
+) -> Result<Vec<GaiFile>, git2::Error> {
+    let mut gai_files: Vec<GaiFile> = Vec::new();
+
+    diff.print(git2::DiffFormat::Patch, |delta, hunk, line| {
+        let path = delta
+            .new_file()
+            .path()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+
+        let should_truncate =
+            files_to_truncate.iter().any(|f| path.ends_with(f));
+
+        // git's own detection, these come
+        // through as a single "Binary files differ"
+        let binary_size = delta.flags().is_binary().then(|| {
+            if delta.new_file().exists() {
+                delta.new_file().size()
+            } else {
+                delta.old_file().size()
+            }
+        });
+
+        let old_path = (delta.status() == git2::Delta::Renamed)
+            .then(|| delta.old_file().path())
+            .flatten()
+            .and_then(|p| p.to_str())
+            .map(str::to_owned);
+
+        let gai_file =
+            match gai_files.iter_mut().find(|g| g.path == path) {
+                Some(existing) => existing,
+                None => {
+                    gai_files.push(GaiFile {
+                        path: path.clone(),
                         should_truncate,
-                        hunks: vec![HunkDiff {
-                            header: format!(
-                                "New File {}",
-                                lines.len()
-                            ),
-                            line_diffs: lines,
-                        }],
+                        hunks: Vec::new(),
+                        binary_size,
+                        old_path,
                     });
+                    gai_files.last_mut().unwrap()
                 }
-            }
-        }
+            };
 
-        self.files.sort_by_key(|g| g.should_truncate);
+        process_file_diff(&mut gai_file.hunks, &hunk, &line);
 
-        Ok(())
-    }
+        true
+    })?;
+
+    Ok(gai_files)
 }
 
 fn process_file_diff(
@@ -143,8 +223,96 @@ fn process_file_diff(
                 diff_hunks.push(HunkDiff {
                     header,
                     line_diffs: vec![diff_line],
+                    excluded: false,
                 });
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GaiConfig;
+
+    const PATCH: &str = "\
+diff --git a/src/main.rs b/src/main.rs
+index 1111111..2222222 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"hi\");
++    println!(\"hello\");
+ }
+@@ -10,2 +10,3 @@ fn other() {
+     a();
++    b();
+ }
+diff --git a/old.rs b/new.rs
+similarity index 100%
+rename from old.rs
+rename to new.rs
+diff --git a/Cargo.lock b/Cargo.lock
+new file mode 100644
+index 0000000..3333333
+--- /dev/null
++++ b/Cargo.lock
+@@ -0,0 +1 @@
++locked
+";
+
+    #[test]
+    fn patch_files_hunks_and_renames_are_parsed() {
+        let dir = std::env::temp_dir()
+            .join(format!("gai-patch-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let repo = git2::Repository::init(&dir).unwrap();
+
+        let mut gai =
+            GaiGit::new(repo, &GaiConfig::default()).unwrap();
+        gai.load_ignores(&["*.lock".to_owned()]).unwrap();
+        gai.create_patch_diffs(PATCH.as_bytes(), &[]).unwrap();
+
+        assert!(gai.from_patch);
+        assert_eq!(gai.ignored, vec!["Cargo.lock".to_owned()]);
+
+        let paths: Vec<&str> =
+            gai.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["src/main.rs", "new.rs"]);
+
+        let main = &gai.files[0];
+        assert_eq!(main.hunks.len(), 2);
+        assert_eq!(
+            main.hunks[1].header,
+            "@@ -10,2 +10,3 @@ fn other() {\n"
+        );
+        let diff_types: Vec<&DiffType> = main.hunks[0]
+            .line_diffs
+            .iter()
+            .map(|l| &l.diff_type)
+            .collect();
+        assert_eq!(
+            diff_types,
+            [
+                &DiffType::Unchanged,
+                &DiffType::Deletions,
+                &DiffType::Additions,
+                &DiffType::Unchanged,
+            ]
+        );
+        assert_eq!(
+            main.hunks[0].line_diffs[2].content,
+            "    println!(\"hello\");\n"
+        );
+
+        let renamed = &gai.files[1];
+        assert_eq!(renamed.old_path.as_deref(), Some("old.rs"));
+        assert!(renamed.hunks.is_empty());
+
+        // nothing to commit it to
+        assert!(gai.apply_commits(&[]).is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+use crate::git::repo::GaiGit;
+
+impl GaiGit {
+    /// .gaiignore at the repo root (gitignore syntax)
+    /// plus the globs from the config, matching files are
+    /// left out of the diffs but git itself doesn't care
+    pub fn load_ignores(&mut self, globs: &[String]) -> Result<()> {
+        let Some(root) = self.repo.workdir() else {
+            return Ok(());
+        };
+
+        let mut builder = GitignoreBuilder::new(root);
+
+        let gaiignore = root.join(".gaiignore");
+        if gaiignore.is_file()
+            && let Some(e) = builder.add(gaiignore)
+        {
+            return Err(e.into());
+        }
+
+        for glob in globs {
+            builder.add_line(None, glob)?;
+        }
+
+        self.ignore_matcher = Some(builder.build()?);
+
+        Ok(())
+    }
+
+    pub fn is_gai_ignored(&self, path: &str) -> bool {
+        self.ignore_matcher.as_ref().is_some_and(|m: &Gitignore| {
+            m.matched_path_or_any_parents(Path::new(path), false)
+                .is_ignore()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{config::GaiConfig, git::repo::GaiGit};
+
+    #[test]
+    fn gaiignore_and_globs_both_match() {
+        let dir = std::env::temp_dir()
+            .join(format!("gai-gaiignore-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = git2::Repository::init(&dir).unwrap();
+        fs::write(dir.join(".gaiignore"), "dist/\n!dist/keep.js\n")
+            .unwrap();
+
+        let mut gai =
+            GaiGit::new(repo, &GaiConfig::default()).unwrap();
+        gai.load_ignores(&["*.lock".to_owned()]).unwrap();
+
+        assert!(gai.is_gai_ignored("Cargo.lock"));
+        assert!(gai.is_gai_ignored("sub/yarn.lock"));
+        assert!(gai.is_gai_ignored("dist/app.js"));
+        assert!(!gai.is_gai_ignored("dist/keep.js"));
+        assert!(!gai.is_gai_ignored("src/main.rs"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
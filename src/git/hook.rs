@@ -0,0 +1,81 @@
+use anyhow::{Context, Result, anyhow};
+use git2::{Oid, ResetType};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+};
+
+use crate::git::repo::GaiGit;
+
+/// how the post_apply_hook went, output
+/// is empty unless it was captured
+#[derive(Debug)]
+pub struct HookRun {
+    pub status: ExitStatus,
+    pub output: String,
+}
+
+impl GaiGit {
+    /// None on a branch without commits yet
+    pub fn head_oid(&self) -> Option<Oid> {
+        self.repo.head().ok()?.target()
+    }
+
+    pub fn workdir(&self) -> Result<PathBuf> {
+        self.repo
+            .workdir()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| anyhow!("not a workdir"))
+    }
+
+    /// undoes the commits after oid, their
+    /// changes stay staged, git reset --soft
+    pub fn soft_reset(&self, oid: Oid) -> Result<()> {
+        let target = self.repo.find_object(oid, None)?;
+        self.repo.reset(&target, ResetType::Soft, None)?;
+
+        Ok(())
+    }
+}
+
+/// runs command through the shell from dir, capture
+/// collects stdout then stderr instead of inheriting them
+pub fn run(
+    command: &str,
+    dir: &Path,
+    capture: bool,
+) -> Result<HookRun> {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    cmd.current_dir(dir);
+
+    let context =
+        || format!("failed to run post_apply_hook `{}`", command);
+
+    if !capture {
+        let status = cmd.status().with_context(context)?;
+        return Ok(HookRun {
+            status,
+            output: String::new(),
+        });
+    }
+
+    let output =
+        cmd.stdin(Stdio::null()).output().with_context(context)?;
+
+    let mut text =
+        String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    Ok(HookRun {
+        status: output.status,
+        output: text,
+    })
+}
@@ -1,5 +1,13 @@
+pub mod branch;
 pub mod commit;
 pub mod diffs;
+pub mod gaiignore;
+pub mod hook;
+pub mod paths;
 pub mod repo;
+pub mod signing;
+pub mod spelling;
 pub mod staging;
 pub mod status;
+pub mod temp_file;
+pub mod word_diff;
@@ -0,0 +1,88 @@
+use anyhow::{Result, anyhow};
+use git2::{Pathspec, PathspecFlags};
+use std::path::{Component, Path, PathBuf};
+
+use crate::git::repo::GaiGit;
+
+impl GaiGit {
+    /// limits the diffs to paths, relative to the current
+    /// directory like git's own pathspecs. a directory
+    /// covers everything under it
+    pub fn restrict_to(&mut self, paths: &[PathBuf]) -> Result<()> {
+        let root = self.workdir()?.canonicalize()?;
+        let cwd = std::env::current_dir()?;
+
+        self.paths = paths
+            .iter()
+            .map(|path| {
+                let full = normalize(&cwd.join(path));
+                let relative =
+                    full.strip_prefix(&root).map_err(|_| {
+                        anyhow!(
+                            "{} is outside the repository",
+                            path.display()
+                        )
+                    })?;
+
+                // the repo root itself
+                if relative.as_os_str().is_empty() {
+                    return Ok("*".to_owned());
+                }
+
+                Ok(relative.to_string_lossy().replace('\\', "/"))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(())
+    }
+
+    /// every path from restrict_to has to match
+    /// a changed file, run after create_diffs
+    pub fn check_paths(&self) -> Result<()> {
+        for path in &self.paths {
+            let spec = Pathspec::new([path])?;
+            let matches = |file: &str| {
+                spec.matches_path(
+                    Path::new(file),
+                    PathspecFlags::DEFAULT,
+                )
+            };
+
+            if self.files.iter().any(|f| {
+                matches(&f.path)
+                    || f.old_path.as_deref().is_some_and(matches)
+            }) {
+                continue;
+            }
+
+            if self.ignored.iter().any(|f| matches(f)) {
+                return Err(anyhow!(
+                    "{} is ignored by .gaiignore or gai.ignore_globs",
+                    path
+                ));
+            }
+
+            return Err(anyhow!("{} has no changes", path));
+        }
+
+        Ok(())
+    }
+}
+
+/// . and .. resolved without touching the
+/// disk, a deleted file can't be canonicalized
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    normalized
+}
@@ -1,14 +1,19 @@
 use anyhow::Result;
-use git2::Repository;
+use git2::{Oid, Repository};
+use ignore::gitignore::Gitignore;
 use std::collections::HashMap;
 use walkdir::WalkDir;
 
+use crate::config::GaiConfig;
+
 pub struct GaiGit {
     /// Diffs
     pub files: Vec<GaiFile>,
     /// ideally this can be configured as well for global
     /// ignores that are not part of .gitignore.
     pub ignored: Vec<String>,
+    /// from .gaiignore and gai.ignore_globs
+    pub ignore_matcher: Option<Gitignore>,
 
     /// git2 based Repo
     pub repo: Repository,
@@ -16,9 +21,32 @@ pub struct GaiGit {
     pub status: GaiStatus,
 
     pub only_staged: bool,
+    /// new files nobody has git added yet
+    pub include_untracked: bool,
+    /// leave whitespace only hunks out of
+    /// what's sent, like git diff -w
+    pub ignore_whitespace: bool,
+    /// -U for every diff we make
+    pub diff_context_lines: u32,
     pub stage_hunks: bool,
-    pub capitalize_prefix: bool,
-    pub include_scope: bool,
+    /// lines a truncated file keeps from the
+    /// start and the end of its diff
+    pub truncate_head: usize,
+    pub truncate_tail: usize,
+    /// sign commits like git commit -S
+    pub sign: bool,
+    /// pathspecs the diffs are limited
+    /// to, everything when empty
+    pub paths: Vec<String>,
+    /// trailer lines for each commit,
+    /// ex. Co-authored-by or Signed-off-by
+    pub trailers: Vec<String>,
+    /// a single commit takes everything that's
+    /// still listed, not only what the model picked
+    pub auto_stage: bool,
+    /// the diffs were parsed from a patch, there's
+    /// no working tree to commit them to
+    pub from_patch: bool,
 }
 
 /// helper to store paths for the files
@@ -43,6 +71,95 @@ pub struct GaiFile {
     pub path: String,
     pub should_truncate: bool,
     pub hunks: Vec<HunkDiff>,
+    /// size in bytes when it's a binary file,
+    /// those have no hunks and are never described
+    pub binary_size: Option<u64>,
+    /// where it was before, when git detected a
+    /// rename, path is always the new one
+    pub old_path: Option<String>,
+}
+
+impl GaiFile {
+    /// old → new for renames, otherwise just the path
+    pub fn display_path(&self) -> String {
+        match &self.old_path {
+            Some(old) => format!("{} → {}", old, self.path),
+            None => self.path.to_owned(),
+        }
+    }
+
+    pub fn has_excluded_hunks(&self) -> bool {
+        self.hunks.iter().any(|h| h.excluded)
+    }
+
+    /// hunk ids for everything that wasn't excluded
+    pub fn included_hunk_ids(&self) -> Vec<String> {
+        self.hunks
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| !h.excluded)
+            .map(|(i, _)| format!("{}:{}", self.path, i))
+            .collect()
+    }
+
+    /// every hunk only changes whitespace, renames
+    /// and binary files always count as changes
+    pub fn is_whitespace_only(&self) -> bool {
+        self.old_path.is_none()
+            && self.binary_size.is_none()
+            && !self.hunks.is_empty()
+            && self.hunks.iter().all(HunkDiff::is_whitespace_only)
+    }
+
+    /// (added, removed) lines, excluded hunks don't count
+    pub fn line_stats(&self) -> (usize, usize) {
+        self.hunks
+            .iter()
+            .filter(|h| !h.excluded)
+            .flat_map(|h| &h.line_diffs)
+            .fold((0, 0), |(added, removed), line| {
+                match line.diff_type {
+                    DiffType::Additions => (added + 1, removed),
+                    DiffType::Deletions => (added, removed + 1),
+                    DiffType::Unchanged => (added, removed),
+                }
+            })
+    }
+
+    /// an added line of an included hunk opens or closes
+    /// a merge conflict. ======= alone is too common,
+    /// ex. markdown headings, to count
+    pub fn has_conflict_markers(&self) -> bool {
+        self.hunks
+            .iter()
+            .filter(|h| !h.excluded)
+            .flat_map(|h| &h.line_diffs)
+            .filter(|l| l.diff_type == DiffType::Additions)
+            .any(|l| is_conflict_marker(&l.content))
+    }
+}
+
+/// hunk indexes for a file, added to
+/// its entry when it already has one
+fn cover<'a>(
+    covered: &mut Vec<(&'a GaiFile, Vec<usize>)>,
+    file: &'a GaiFile,
+    indexes: impl IntoIterator<Item = usize>,
+) {
+    match covered.iter_mut().find(|(f, _)| f.path == file.path) {
+        Some((_, covered)) => covered.extend(indexes),
+        None => covered.push((file, indexes.into_iter().collect())),
+    }
+}
+
+/// <<<<<<< or >>>>>>> on its own or before a
+/// label, git's markers are exactly seven wide
+fn is_conflict_marker(line: &str) -> bool {
+    ["<<<<<<<", ">>>>>>>"].iter().any(|marker| {
+        line.strip_prefix(marker).is_some_and(|rest| {
+            rest.is_empty() || rest.starts_with([' ', '\r', '\n'])
+        })
+    })
 }
 
 #[derive(Debug, Clone)]
@@ -52,6 +169,46 @@ pub struct HunkDiff {
     pub header: String,
 
     pub line_diffs: Vec<LineDiff>,
+
+    /// excluded from the tui, these never
+    /// reach the AI or the commits
+    pub excluded: bool,
+}
+
+impl HunkDiff {
+    /// starting line in the old and new file,
+    /// read from the header ranges
+    pub fn line_starts(&self) -> (usize, usize) {
+        let mut ranges = self
+            .header
+            .trim_start_matches("@@")
+            .split_whitespace()
+            .take(2)
+            .map(|range| {
+                range
+                    .get(1..)
+                    .and_then(|r| r.split(',').next())
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0)
+            });
+
+        (ranges.next().unwrap_or(0), ranges.next().unwrap_or(0))
+    }
+
+    /// the removed and added lines are the same once
+    /// all whitespace is gone, ex. reindenting or CRLF
+    pub fn is_whitespace_only(&self) -> bool {
+        let stripped = |diff_type: DiffType| -> String {
+            self.line_diffs
+                .iter()
+                .filter(|l| l.diff_type == diff_type)
+                .flat_map(|l| l.content.chars())
+                .filter(|c| !c.is_whitespace())
+                .collect()
+        };
+
+        stripped(DiffType::Deletions) == stripped(DiffType::Additions)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -70,27 +227,33 @@ pub enum DiffType {
 }
 
 impl GaiGit {
-    /// todo: this could fail on an unitialized directory
-    /// for now, im not gonna handle those and we
-    /// just straight up panic if we failed to open
-    pub fn new(
-        only_staged: bool,
-        stage_hunks: bool,
-        capitalize_prefix: bool,
-        include_scope: bool,
-    ) -> Result<Self> {
-        let repo = Repository::open_from_env()?;
+    /// co-authors go ahead of the other trailers
+    pub fn new(repo: Repository, cfg: &GaiConfig) -> Result<Self> {
         let status = Self::build_status(&repo)?;
 
         Ok(GaiGit {
             repo,
             files: Vec::new(),
             ignored: Vec::new(),
+            ignore_matcher: None,
             status,
-            only_staged,
-            stage_hunks,
-            capitalize_prefix,
-            include_scope,
+            only_staged: cfg.only_staged,
+            include_untracked: true,
+            ignore_whitespace: cfg.ignore_whitespace,
+            diff_context_lines: cfg.diff_context_lines,
+            stage_hunks: cfg.stage_hunks,
+            truncate_head: cfg.truncate_head,
+            truncate_tail: cfg.truncate_tail,
+            sign: cfg.commit_config.sign,
+            paths: Vec::new(),
+            trailers: cfg
+                .co_authors
+                .iter()
+                .map(|c| format!("Co-authored-by: {}", c))
+                .chain(cfg.trailers.iter().cloned())
+                .collect(),
+            auto_stage: false,
+            from_patch: false,
         })
     }
 
@@ -105,6 +268,94 @@ impl GaiGit {
         head.unwrap_or("HEAD").to_string()
     }
 
+    /// None on a detached HEAD, an unborn
+    /// branch still has its name
+    pub fn branch_name(&self) -> Option<String> {
+        match self.repo.head() {
+            Ok(head) if head.is_branch() => {
+                head.shorthand().map(str::to_owned)
+            }
+            Ok(_) => None,
+            Err(_) => self
+                .repo
+                .find_reference("HEAD")
+                .ok()?
+                .symbolic_target()?
+                .strip_prefix("refs/heads/")
+                .map(str::to_owned),
+        }
+    }
+
+    /// subjects of the last n non merge commits
+    /// on HEAD, newest first
+    pub fn recent_subjects(&self, n: usize) -> Vec<String> {
+        let Ok(mut revwalk) = self.repo.revwalk() else {
+            return Vec::new();
+        };
+
+        // an unborn branch has no history to walk
+        if revwalk.push_head().is_err() {
+            return Vec::new();
+        }
+
+        revwalk
+            .filter_map(|oid| oid.ok())
+            .filter_map(|oid| self.repo.find_commit(oid).ok())
+            .filter(|c| c.parent_count() <= 1)
+            .filter_map(|c| c.summary().map(str::to_owned))
+            .take(n)
+            .collect()
+    }
+
+    /// the last n commits on HEAD with their
+    /// subjects, newest first, like git log --oneline
+    pub fn log_oneline(&self, n: usize) -> Vec<(Oid, String)> {
+        let Ok(mut revwalk) = self.repo.revwalk() else {
+            return Vec::new();
+        };
+
+        if revwalk.push_head().is_err() {
+            return Vec::new();
+        }
+
+        revwalk
+            .filter_map(|oid| oid.ok())
+            .filter_map(|oid| self.repo.find_commit(oid).ok())
+            .map(|c| {
+                (c.id(), c.summary().unwrap_or_default().to_owned())
+            })
+            .take(n)
+            .collect()
+    }
+
+    /// HEAD is already on the branch's upstream,
+    /// rewriting it would rewrite pushed history
+    pub fn head_is_pushed(&self) -> Result<bool> {
+        let head = self.repo.head()?;
+        if !head.is_branch() {
+            return Ok(false);
+        }
+
+        let Some(head_oid) = head.target() else {
+            return Ok(false);
+        };
+
+        let upstream = match git2::Branch::wrap(head).upstream() {
+            Ok(upstream) => upstream,
+            // nothing tracked, nothing pushed
+            Err(_) => return Ok(false),
+        };
+
+        let Some(upstream_oid) = upstream.get().target() else {
+            return Ok(false);
+        };
+
+        Ok(upstream_oid == head_oid
+            || self
+                .repo
+                .graph_descendant_of(upstream_oid, head_oid)?)
+    }
+
     pub fn get_repo_tree(&self) -> String {
         let repo_root =
             self.repo.workdir().ok_or("not a workdir").unwrap();
@@ -205,35 +456,154 @@ impl GaiGit {
         status_str
     }
 
+    /// marks the biggest files should_truncate until
+    /// the diffs fit in max_chars, returns their paths.
+    /// diffs over summarize_over don't count, they're
+    /// sent as a summary instead
+    pub fn auto_truncate(
+        &mut self,
+        max_chars: usize,
+        summarize_over: Option<usize>,
+    ) -> Vec<String> {
+        if max_chars == 0 {
+            return Vec::new();
+        }
+
+        let mut diffs = self.get_file_diffs_as_str();
+        if let Some(min_chars) = summarize_over {
+            diffs.retain(|_, d| d.chars().count() <= min_chars);
+        }
+
+        let mut total: usize =
+            diffs.values().map(|d| d.chars().count()).sum();
+
+        let mut sizes: Vec<(String, usize)> = self
+            .files
+            .iter()
+            .filter(|f| !f.should_truncate)
+            .filter_map(|f| {
+                diffs
+                    .get(&f.path)
+                    .map(|d| (f.path.to_owned(), d.chars().count()))
+            })
+            .collect();
+        sizes.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+
+        let mut truncated = Vec::new();
+        for (path, size) in sizes {
+            if total <= max_chars {
+                break;
+            }
+
+            let Some(i) =
+                self.files.iter().position(|f| f.path == path)
+            else {
+                continue;
+            };
+
+            // the diff is swapped for its preview
+            let preview = self.truncated_preview(&self.files[i]);
+            total =
+                total.saturating_sub(size) + preview.chars().count();
+            self.files[i].should_truncate = true;
+            truncated.push(path);
+        }
+
+        truncated
+    }
+
+    /// left out of the request entirely under ignore_whitespace,
+    /// the diffs tab still shows the whole thing
+    pub fn skips_file(&self, file: &GaiFile) -> bool {
+        self.ignore_whitespace && file.is_whitespace_only()
+    }
+
+    /// false when there's nothing a request could use,
+    /// ex. a clean tree or only skipped files
+    pub fn has_changes(&self) -> bool {
+        self.files.iter().any(|f| !self.skips_file(f))
+    }
+
+    /// files still holding conflict markers,
+    /// apply_commits refuses while any are listed
+    pub fn conflicted_files(&self) -> Vec<&str> {
+        self.files
+            .iter()
+            .filter(|f| f.has_conflict_markers())
+            .map(|f| f.path.as_str())
+            .collect()
+    }
+
+    /// what a commit covers, by file in the order it lists
+    /// them. a whole file brings its included hunks, hunk
+    /// ids (path:index) only their own. anything no longer
+    /// in the diffs is left out
+    pub fn commit_hunks(
+        &self,
+        files: &[String],
+        hunk_ids: &[String],
+    ) -> Vec<(&GaiFile, Vec<&HunkDiff>)> {
+        let mut covered: Vec<(&GaiFile, Vec<usize>)> = Vec::new();
+
+        for path in files {
+            if let Some(file) = self.files.iter().find(|f| {
+                &f.path == path || f.old_path.as_ref() == Some(path)
+            }) {
+                cover(&mut covered, file, 0..file.hunks.len());
+            }
+        }
+
+        for hunk_id in hunk_ids {
+            if let Some((path, index)) = hunk_id.split_once(':')
+                && let Ok(index) = index.parse::<usize>()
+                && let Some(file) =
+                    self.files.iter().find(|f| f.path == path)
+            {
+                cover(&mut covered, file, [index]);
+            }
+        }
+
+        covered
+            .into_iter()
+            .map(|(file, mut indexes)| {
+                indexes.sort_unstable();
+                indexes.dedup();
+
+                let hunks = indexes
+                    .iter()
+                    .filter_map(|&i| file.hunks.get(i))
+                    .filter(|h| !h.excluded)
+                    .collect();
+
+                (file, hunks)
+            })
+            .collect()
+    }
+
     pub fn get_file_diffs_as_str(&self) -> HashMap<String, String> {
         let mut file_diffs = HashMap::new();
         for gai_file in &self.files {
             let mut diff_str = String::new();
+
+            // one file, not a deletion and an addition
+            if let Some(old) = &gai_file.old_path {
+                diff_str.push_str(&format!("Renamed from {}\n", old));
+            }
+
             if gai_file.should_truncate {
-                diff_str.push_str("Truncated File");
+                diff_str.push_str(&self.truncated_preview(gai_file));
                 file_diffs.insert(gai_file.path.to_owned(), diff_str);
                 continue;
             }
 
-            for (i, hunk) in gai_file.hunks.iter().enumerate() {
-                diff_str.push_str(&format!(
-                    "Hunk_id[{}:{}]\n",
-                    gai_file.path, i
-                ));
-                diff_str.push_str(&hunk.header);
-                diff_str.push('\n');
-
-                for line in &hunk.line_diffs {
-                    let prefix = match line.diff_type {
-                        DiffType::Unchanged => ' ',
-                        DiffType::Additions => '+',
-                        DiffType::Deletions => '-',
-                    };
-                    diff_str.push(prefix);
-                    diff_str.push_str(&line.content);
-                }
-                diff_str.push('\n');
+            // no hunks, but it still has to be committed
+            if gai_file.binary_size.is_some() {
+                diff_str.push_str("Binary File");
+                file_diffs.insert(gai_file.path.to_owned(), diff_str);
+                continue;
             }
+
+            diff_str.push_str(&self.hunks_as_str(gai_file));
             if !diff_str.trim().is_empty() {
                 file_diffs.insert(gai_file.path.to_owned(), diff_str);
             }
@@ -241,4 +611,74 @@ impl GaiGit {
 
         file_diffs
     }
+
+    /// what a truncated file is sent and shown as, the first
+    /// truncate_head and last truncate_tail lines of its diff
+    /// with a count of what was left out in between
+    pub fn truncated_preview(&self, file: &GaiFile) -> String {
+        let mut preview = "Truncated File".to_owned();
+        let (head, tail) = (self.truncate_head, self.truncate_tail);
+
+        let diff = self.hunks_as_str(file);
+        // the blank lines between hunks aren't worth keeping
+        let lines: Vec<&str> =
+            diff.lines().filter(|l| !l.is_empty()).collect();
+        if head + tail == 0 || lines.is_empty() {
+            return preview;
+        }
+
+        preview.push('\n');
+        if lines.len() <= head + tail {
+            preview.push_str(&lines.join("\n"));
+            return preview;
+        }
+
+        for line in &lines[..head] {
+            preview.push_str(line);
+            preview.push('\n');
+        }
+        preview.push_str(&format!(
+            "... {} lines omitted ...",
+            lines.len() - head - tail
+        ));
+        for line in &lines[lines.len() - tail..] {
+            preview.push('\n');
+            preview.push_str(line);
+        }
+
+        preview
+    }
+
+    fn hunks_as_str(&self, file: &GaiFile) -> String {
+        let mut diff_str = String::new();
+
+        for (i, hunk) in file.hunks.iter().enumerate() {
+            // keep the index so hunk ids still
+            // line up with self.files
+            if hunk.excluded
+                || (self.ignore_whitespace
+                    && hunk.is_whitespace_only())
+            {
+                continue;
+            }
+
+            diff_str
+                .push_str(&format!("Hunk_id[{}:{}]\n", file.path, i));
+            diff_str.push_str(&hunk.header);
+            diff_str.push('\n');
+
+            for line in &hunk.line_diffs {
+                let prefix = match line.diff_type {
+                    DiffType::Unchanged => ' ',
+                    DiffType::Additions => '+',
+                    DiffType::Deletions => '-',
+                };
+                diff_str.push(prefix);
+                diff_str.push_str(&line.content);
+            }
+            diff_str.push('\n');
+        }
+
+        diff_str
+    }
 }
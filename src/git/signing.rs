@@ -0,0 +1,132 @@
+use anyhow::{Result, anyhow};
+use std::{
+    fs,
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+use crate::git::{repo::GaiGit, temp_file};
+
+/// how commits get signed, mirrors
+/// what git commit -S would use
+pub enum Signer {
+    Gpg { program: String, key: String },
+    Ssh { program: String, key: String },
+}
+
+impl GaiGit {
+    /// reads user.signingkey and gpg.format
+    /// from the git config, errors out instead
+    /// of falling back to unsigned commits
+    pub fn signer(&self) -> Result<Signer> {
+        let cfg = self.repo.config()?;
+
+        let key = cfg.get_string("user.signingkey").map_err(|_| {
+            anyhow!(
+                "commit signing is enabled but user.signingkey is not set in your git config"
+            )
+        })?;
+
+        let format = cfg
+            .get_string("gpg.format")
+            .unwrap_or_else(|_| "openpgp".to_owned());
+
+        match format.as_str() {
+            "openpgp" => {
+                let program = cfg
+                    .get_string("gpg.openpgp.program")
+                    .or_else(|_| cfg.get_string("gpg.program"))
+                    .unwrap_or_else(|_| "gpg".to_owned());
+
+                Ok(Signer::Gpg { program, key })
+            }
+            "ssh" => {
+                let program = cfg
+                    .get_string("gpg.ssh.program")
+                    .unwrap_or_else(|_| "ssh-keygen".to_owned());
+
+                Ok(Signer::Ssh { program, key })
+            }
+            other => Err(anyhow!(
+                "gpg.format = {} is not supported for signing",
+                other
+            )),
+        }
+    }
+}
+
+impl Signer {
+    /// returns the armored signature
+    /// for the commit buffer
+    pub fn sign(&self, content: &str) -> Result<String> {
+        // a literal ssh key's file, gone once it's signed
+        let mut key_file = None;
+
+        let mut cmd = match self {
+            Signer::Gpg { program, key } => {
+                let mut cmd = Command::new(program);
+                cmd.args(["--status-fd=2", "-bsau", key]);
+                cmd
+            }
+            Signer::Ssh { program, key } => {
+                let mut cmd = Command::new(program);
+                cmd.args(["-Y", "sign", "-n", "git", "-f"]);
+                match literal_ssh_key(key) {
+                    Some(literal) => {
+                        let path = temp_file::create(
+                            "gai_signing_key",
+                            literal.as_bytes(),
+                        )?;
+                        cmd.arg(&path);
+                        key_file = Some(path);
+                    }
+                    None => {
+                        cmd.arg(key);
+                    }
+                }
+                cmd
+            }
+        };
+
+        let output = run(&mut cmd, content);
+        if let Some(path) = key_file {
+            let _ = fs::remove_file(path);
+        }
+        let output = output?;
+
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to sign commit: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+}
+
+/// the signing program with content on stdin
+fn run(cmd: &mut Command, content: &str) -> Result<Output> {
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            anyhow!("failed to run signing program: {}", e)
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(content.as_bytes())?;
+    }
+
+    Ok(child.wait_with_output()?)
+}
+
+/// user.signingkey can be a path or the public key
+/// itself, ssh-keygen only takes a path so a literal
+/// key gets written out to a temp file
+fn literal_ssh_key(key: &str) -> Option<&str> {
+    key.strip_prefix("key::")
+        .or_else(|| key.starts_with("ssh-").then_some(key))
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+/// wrong->right pairs, only misspellings
+/// so real words never get flagged
+const MISSPELLINGS: &str = include_str!("misspellings.txt");
+
+fn misspellings() -> &'static HashMap<&'static str, &'static str> {
+    static MAP: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+
+    MAP.get_or_init(|| {
+        MISSPELLINGS
+            .lines()
+            .filter(|l| !l.starts_with('#'))
+            .filter_map(|l| l.split_once("->"))
+            .collect()
+    })
+}
+
+/// a flagged word and what it probably should be
+#[derive(Clone, Debug)]
+pub struct Typo {
+    pub word: String,
+    pub suggestion: &'static str,
+}
+
+/// advisory only, words that show up in the diffs
+/// are taken as identifiers and never flagged
+pub struct SpellChecker {
+    known: HashSet<String>,
+}
+
+impl SpellChecker {
+    pub fn from_diffs<'a>(
+        diffs: impl Iterator<Item = &'a str>,
+    ) -> Self {
+        let known = diffs
+            .flat_map(|diff| {
+                diff.split(|c: char| !c.is_alphanumeric())
+                    .filter(|w| !w.is_empty())
+                    .map(str::to_lowercase)
+            })
+            .collect();
+
+        Self { known }
+    }
+
+    /// each misspelled word once, in the order they show up
+    pub fn check(&self, text: &str) -> Vec<Typo> {
+        let mut typos: Vec<Typo> = Vec::new();
+
+        for (_, word) in words(text) {
+            if typos.iter().any(|t| t.word.eq_ignore_ascii_case(word))
+            {
+                continue;
+            }
+
+            if let Some(suggestion) = self.suggest(word) {
+                typos.push(Typo {
+                    word: word.to_owned(),
+                    suggestion,
+                });
+            }
+        }
+
+        typos
+    }
+
+    fn suggest(&self, word: &str) -> Option<&'static str> {
+        let lower = word.to_lowercase();
+        if self.known.contains(&lower) {
+            return None;
+        }
+
+        misspellings().get(lower.as_str()).copied()
+    }
+}
+
+/// (byte offset, word) of the plain words in text, anything
+/// that looks like code is skipped, ex. `foo`, snake_case,
+/// camelCase, paths or words with digits
+pub fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut in_backticks = false;
+
+    text.split_inclusive(char::is_whitespace)
+        .scan(0, |offset, token| {
+            let start = *offset;
+            *offset += token.len();
+            Some((start, token))
+        })
+        .filter_map(move |(start, token)| {
+            let ticks = token.matches('`').count();
+            let quoted = in_backticks || ticks > 0;
+            if ticks % 2 == 1 {
+                in_backticks = !in_backticks;
+            }
+            if quoted {
+                return None;
+            }
+
+            let trimmed = token.trim_start_matches(is_punctuation);
+            let lead = token.len() - trimmed.len();
+            let word = trimmed
+                .trim_end()
+                .trim_end_matches(is_punctuation)
+                .trim_end_matches("'s");
+
+            let looks_like_code = word.is_empty()
+                || !word
+                    .chars()
+                    .all(|c| c.is_alphabetic() || c == '\'')
+                || word.chars().skip(1).any(char::is_uppercase);
+
+            (!looks_like_code).then_some((start + lead, word))
+        })
+}
+
+fn is_punctuation(c: char) -> bool {
+    matches!(
+        c,
+        ',' | '.' | ';' | ':' | '!' | '?' | '"' | '\'' | '(' | ')'
+    )
+}
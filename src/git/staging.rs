@@ -1,46 +1,516 @@
-use std::path::Path;
+use anyhow::{Result, anyhow};
+use git2::Oid;
+use std::{fs, path::Path};
 
 use crate::git::{
     commit::GaiCommit,
     repo::{DiffType, GaiGit},
+    signing::Signer,
 };
 
 impl GaiGit {
-    pub fn apply_commits(&self, commits: &[GaiCommit]) {
-        //println!("{:#?}", self.commits);
-        for commit in commits {
-            self.commit(commit);
+    /// returns the new commits, oldest first. commits
+    /// with nothing left to stage are skipped
+    pub fn apply_commits(
+        &self,
+        commits: &[GaiCommit],
+    ) -> Result<Vec<Oid>> {
+        let conflicted = self.conflicted_files();
+        if !conflicted.is_empty() {
+            return Err(anyhow!(
+                "{} still {} conflict markers, resolve them or \
+                 remove {} from the diffs",
+                conflicted.join(", "),
+                if conflicted.len() == 1 { "has" } else { "have" },
+                if conflicted.len() == 1 { "it" } else { "them" },
+            ));
+        }
+
+        self.apply_planned(self.planned(commits))
+    }
+
+    /// commit as-is but with only path in it, whatever of it
+    /// is still included. auto_stage doesn't widen it
+    pub fn apply_file_commit(
+        &self,
+        commit: &GaiCommit,
+        path: &str,
+    ) -> Result<Oid> {
+        let file = self
+            .files
+            .iter()
+            .find(|f| f.path == path)
+            .ok_or_else(|| anyhow!("{} has no changes", path))?;
+
+        if file.has_conflict_markers() {
+            return Err(anyhow!(
+                "{} still has conflict markers, resolve them first",
+                path
+            ));
+        }
+
+        let commit = GaiCommit {
+            files: vec![file.path.clone()],
+            hunk_ids: file.included_hunk_ids(),
+            ..commit.clone()
+        };
+
+        self.apply_planned(vec![commit])?
+            .pop()
+            .ok_or_else(|| anyhow!("nothing to commit in {}", path))
+    }
+
+    fn apply_planned(
+        &self,
+        commits: Vec<GaiCommit>,
+    ) -> Result<Vec<Oid>> {
+        if self.from_patch {
+            return Err(anyhow!(
+                "the diffs came from a patch, there's nothing to apply"
+            ));
+        }
+
+        // resolve before touching anything so a missing
+        // key doesn't leave us with unsigned commits
+        let signer = if self.sign {
+            Some(self.signer()?)
+        } else {
+            None
+        };
+
+        // each commit rebuilds the index from HEAD, so
+        // keep a copy of what was staged beforehand
+        let staged = if self.only_staged {
+            Some(git2::Index::open(&self.repo.path().join("index"))?)
+        } else {
+            None
+        };
+
+        // a failed commit undoes the ones before it,
+        // the index goes back to what it was too
+        let start = self.head_oid();
+        let index_path = self.repo.path().join("index");
+        let index_before = fs::read(&index_path).ok();
+
+        let mut created = Vec::new();
+
+        for (i, commit) in commits.iter().enumerate() {
+            let e = match self.commit(
+                commit,
+                signer.as_ref(),
+                staged.as_ref(),
+            ) {
+                Ok(oid) => {
+                    created.push(oid);
+                    continue;
+                }
+                Err(e) => e,
+            };
+
+            let failed = format!(
+                "commit {} of {} ({}) failed",
+                i + 1,
+                commits.len(),
+                commit.header
+            );
+
+            return match self
+                .roll_back(start, index_before.as_deref())
+            {
+                Ok(()) => Err(e.context(format!(
+                    "{}, nothing was applied",
+                    failed
+                ))),
+                Err(rollback) => Err(anyhow!(
+                    "{}: {:#}, and rolling back failed too: {:#}",
+                    failed,
+                    e,
+                    rollback
+                )),
+            };
+        }
+
+        Ok(created)
+    }
+
+    /// the commits as they'll be applied, files and hunks
+    /// no longer in the diffs (ex. removed from the list)
+    /// are dropped. under auto_stage a single commit gets
+    /// every file that's left. ignored files are folded in
+    fn planned(&self, commits: &[GaiCommit]) -> Vec<GaiCommit> {
+        let mut planned = self.listed_only(commits);
+        self.fold_ignored(&mut planned);
+        planned
+    }
+
+    fn listed_only(&self, commits: &[GaiCommit]) -> Vec<GaiCommit> {
+        if self.auto_stage
+            && let [commit] = commits
+        {
+            return vec![GaiCommit {
+                files: self
+                    .files
+                    .iter()
+                    .map(|f| f.path.clone())
+                    .collect(),
+                hunk_ids: self
+                    .files
+                    .iter()
+                    .flat_map(|f| f.included_hunk_ids())
+                    .collect(),
+                ..commit.clone()
+            }];
+        }
+
+        let listed = |path: &str| {
+            self.files.iter().any(|f| {
+                f.path == path || f.old_path.as_deref() == Some(path)
+            })
+        };
+
+        commits
+            .iter()
+            .map(|commit| GaiCommit {
+                files: commit
+                    .files
+                    .iter()
+                    .filter(|path| listed(path))
+                    .cloned()
+                    .collect(),
+                hunk_ids: commit
+                    .hunk_ids
+                    .iter()
+                    .filter(|id| {
+                        id.split_once(':')
+                            .is_some_and(|(path, _)| listed(path))
+                    })
+                    .cloned()
+                    .collect(),
+                ..commit.clone()
+            })
+            .filter(|commit| {
+                !commit.files.is_empty()
+                    || !commit.hunk_ids.is_empty()
+            })
+            .collect()
+    }
+
+    /// ignored files are still committed, the AI just never
+    /// saw them. each goes with the commit sharing the most
+    /// of its directory, the first one on a tie
+    fn fold_ignored(&self, commits: &mut [GaiCommit]) {
+        for path in &self.ignored {
+            let shared = |commit: &GaiCommit| {
+                commit
+                    .files
+                    .iter()
+                    .map(String::as_str)
+                    .chain(
+                        commit
+                            .hunk_ids
+                            .iter()
+                            .filter_map(|id| id.split_once(':'))
+                            .map(|(file, _)| file),
+                    )
+                    .map(|file| shared_dirs(file, path))
+                    .max()
+                    .unwrap_or(0)
+            };
+
+            if let Some(commit) =
+                commits.iter_mut().rev().max_by_key(|c| shared(c))
+            {
+                commit.files.push(path.clone());
+            }
+        }
+    }
+
+    /// HEAD and the index as they were before
+    /// apply_commits, the working tree is never touched
+    fn roll_back(
+        &self,
+        start: Option<Oid>,
+        index: Option<&[u8]>,
+    ) -> Result<()> {
+        match start {
+            Some(oid) => {
+                if self.head_oid() != Some(oid) {
+                    self.soft_reset(oid)?;
+                }
+            }
+            // the branch didn't exist before the first commit
+            None => {
+                let head = self.repo.find_reference("HEAD")?;
+                let target =
+                    head.symbolic_target().map(str::to_owned);
+
+                if let Some(target) = target
+                    && let Ok(mut branch) =
+                        self.repo.find_reference(&target)
+                {
+                    branch.delete()?;
+                }
+            }
+        }
+
+        let index_path = self.repo.path().join("index");
+        match index {
+            Some(bytes) => fs::write(&index_path, bytes)?,
+            None if index_path.exists() => {
+                fs::remove_file(&index_path)?
+            }
+            None => {}
+        }
+        self.repo.index()?.read(true)?;
+
+        Ok(())
+    }
+
+    /// rewrites HEAD's message, the tree and
+    /// parents stay the same
+    pub fn amend_head(&self, commit: &GaiCommit) -> Result<()> {
+        let signer = if self.sign {
+            Some(self.signer()?)
+        } else {
+            None
+        };
+
+        let head = self.repo.head()?.peel_to_commit()?;
+        let commit_msg =
+            &commit.message_with_trailers(&self.trailers);
+
+        match signer {
+            Some(signer) => {
+                let parents: Vec<git2::Commit> =
+                    head.parents().collect();
+                let parents: Vec<&git2::Commit> =
+                    parents.iter().collect();
+
+                let buf = self.repo.commit_create_buffer(
+                    &head.author(),
+                    &self.repo.signature()?,
+                    commit_msg,
+                    &head.tree()?,
+                    &parents[..],
+                )?;
+
+                let content = buf.as_str().ok_or_else(|| {
+                    anyhow!("commit buffer is not valid utf8")
+                })?;
+
+                let signature = signer.sign(content)?;
+                let oid = self
+                    .repo
+                    .commit_signed(content, &signature, None)?;
+
+                self.update_head(oid, commit_msg)?;
+            }
+            None => {
+                head.amend(
+                    Some("HEAD"),
+                    None,
+                    Some(&self.repo.signature()?),
+                    None,
+                    Some(commit_msg),
+                    None,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// what apply_commits would do, as roughly
+    /// equivalent git commands, without touching the repo
+    pub fn dry_run(&self, commits: &[GaiCommit]) -> String {
+        let commits = self.planned(commits);
+        let mut out = String::new();
+        let sign = if self.sign { " -S" } else { "" };
+
+        for (i, commit) in commits.iter().enumerate() {
+            out.push_str(&format!(
+                "# Commit {} of {}\n",
+                i + 1,
+                commits.len()
+            ));
+
+            // every commit starts from HEAD
+            out.push_str("git read-tree HEAD\n");
+
+            if self.stage_hunks {
+                for path in self.hunkless_files(&commit.files) {
+                    out.push_str(&self.dry_run_stage(path));
+                }
+
+                out.push_str(&format!(
+                    "git apply --cached <hunks {}>\n",
+                    commit.hunk_ids.join(", ")
+                ));
+            } else {
+                let (partial, whole) =
+                    self.split_partial(&commit.files);
+
+                for path in whole {
+                    out.push_str(&self.dry_run_stage(path));
+                }
+
+                for path in partial {
+                    let hunks: Vec<String> = self
+                        .files
+                        .iter()
+                        .filter(|f| f.path == *path)
+                        .flat_map(|f| f.included_hunk_ids())
+                        .collect();
+
+                    out.push_str(&format!(
+                        "git apply --cached <hunks {}>\n",
+                        hunks.join(", ")
+                    ));
+                }
+            }
+
+            let message =
+                commit.message_with_trailers(&self.trailers);
+
+            out.push_str(&format!(
+                "git commit{} -F - <<'EOF'\n",
+                sign
+            ));
+            out.push_str(&message);
+            out.push_str("EOF\n\n");
+        }
+
+        out
+    }
+
+    pub fn dry_run_amend(&self, commit: &GaiCommit) -> String {
+        let sign = if self.sign { " -S" } else { "" };
+
+        format!(
+            "git commit --amend{} -F - <<'EOF'\n{}EOF\n",
+            sign,
+            commit.message_with_trailers(&self.trailers)
+        )
+    }
+
+    fn dry_run_stage(&self, path: &str) -> String {
+        if self.only_staged {
+            return format!("git add <staged {}>\n", path);
+        }
+
+        if let Some(old) = self.renamed_from(path) {
+            return format!("git mv {} {}\n", old, path);
+        }
+
+        match self.repo.status_file(Path::new(path)) {
+            Ok(status)
+                if status.contains(git2::Status::WT_DELETED) =>
+            {
+                format!("git rm --cached {}\n", path)
+            }
+            _ => format!("git add {}\n", path),
         }
     }
 
-    fn commit(&self, commit: &GaiCommit) {
-        let mut index = self.repo.index().unwrap();
+    /// files with excluded hunks (partial) have to
+    /// go through a patch, the rest (whole) are staged as is
+    fn split_partial<'a>(
+        &self,
+        files: &'a [String],
+    ) -> (Vec<&'a String>, Vec<&'a String>) {
+        files.iter().partition(|path| {
+            self.files
+                .iter()
+                .any(|f| f.path == **path && f.has_excluded_hunks())
+        })
+    }
+
+    /// binaries, pure renames and ignored files, there's
+    /// nothing to patch so they're staged whole
+    fn hunkless_files<'a>(
+        &self,
+        files: &'a [String],
+    ) -> Vec<&'a String> {
+        files
+            .iter()
+            .filter(|path| {
+                self.ignored.contains(path)
+                    || self.files.iter().any(|f| {
+                        f.path == **path
+                            && (f.binary_size.is_some()
+                                || (f.old_path.is_some()
+                                    && f.hunks.is_empty()))
+                    })
+            })
+            .collect()
+    }
+
+    /// Signed-off-by for the git user identity,
+    /// after the trailers that are already there
+    pub fn add_signoff(&mut self) -> Result<()> {
+        let sig = self.repo.signature()?;
+        let name = sig.name().unwrap_or_default();
+        let email = sig.email().unwrap_or_default();
+
+        self.trailers
+            .push(format!("Signed-off-by: {} <{}>", name, email));
 
-        index.clear().unwrap();
+        Ok(())
+    }
+
+    fn commit(
+        &self,
+        commit: &GaiCommit,
+        signer: Option<&Signer>,
+        staged: Option<&git2::Index>,
+    ) -> Result<Oid> {
+        let mut index = self.repo.index()?;
+
+        index.clear()?;
 
         if let Ok(head) = self.repo.head()
             && let Ok(tree) = head.peel_to_tree()
         {
-            index.read_tree(&tree).unwrap();
+            index.read_tree(&tree)?;
         }
 
         // todo impl validation and add failed hunks
         if self.stage_hunks {
+            // binaries and renames have no hunks to patch
+            self.stage_files(
+                &mut index,
+                &self.hunkless_files(&commit.files),
+            )?;
+
             // going to bypass the index
             // and instead use the stored hunks
             // from create_diffs to create patches
-            self.stage_hunks(commit);
+            self.stage_hunks(commit)?;
         } else {
-            self.stage_files(&mut index, commit);
+            let (partial, whole) = self.split_partial(&commit.files);
+
+            if let Some(staged) = staged {
+                self.stage_staged_files(&mut index, staged, &whole)?;
+            } else {
+                self.stage_files(&mut index, &whole)?;
+            }
+
+            // files with excluded hunks only get
+            // what's left of them, as a patch
+            if !partial.is_empty() {
+                index.write()?;
+                self.stage_partial_files(&partial)?;
+                index.read(true)?;
+            }
         }
 
-        index.write().unwrap();
+        index.write()?;
 
-        let tree_oid = index.write_tree().unwrap();
-        let tree = self.repo.find_tree(tree_oid).unwrap();
+        let tree_oid = index.write_tree()?;
+        let tree = self.repo.find_tree(tree_oid)?;
 
         let parent_commit = match self.repo.revparse_single("HEAD") {
-            Ok(obj) => Some(obj.into_commit().unwrap()),
+            Ok(obj) => obj.into_commit().ok(),
             // ignore first commit
             Err(_) => None,
         };
@@ -50,54 +520,98 @@ impl GaiGit {
             parents.push(parent);
         }
 
-        let sig = self.repo.signature().unwrap();
+        let sig = self.repo.signature()?;
+
+        let commit_msg =
+            &commit.message_with_trailers(&self.trailers);
+
+        let oid = match signer {
+            Some(signer) => {
+                let buf = self.repo.commit_create_buffer(
+                    &sig,
+                    &sig,
+                    commit_msg,
+                    &tree,
+                    &parents[..],
+                )?;
+
+                let content = buf.as_str().ok_or_else(|| {
+                    anyhow!("commit buffer is not valid utf8")
+                })?;
 
-        let commit_msg = &commit.message;
+                let signature = signer.sign(content)?;
+                let oid = self
+                    .repo
+                    .commit_signed(content, &signature, None)?;
 
-        self.repo
-            .commit(
+                self.update_head(oid, commit_msg)?;
+                oid
+            }
+            None => self.repo.commit(
                 Some("HEAD"),
                 &sig,
                 &sig,
                 commit_msg,
                 &tree,
                 &parents[..],
-            )
-            .unwrap();
+            )?,
+        };
+
+        Ok(oid)
+    }
+
+    /// commit_signed doesn't move any refs
+    /// so we have to point HEAD at it ourselves
+    fn update_head(&self, oid: Oid, commit_msg: &str) -> Result<()> {
+        let head = self.repo.find_reference("HEAD")?;
+        let log_msg = format!(
+            "commit: {}",
+            commit_msg.lines().next().unwrap_or_default()
+        );
+
+        match head.symbolic_target() {
+            Some(target) => {
+                self.repo.reference(target, oid, true, &log_msg)?;
+            }
+            None => self.repo.set_head_detached(oid)?,
+        }
+
+        Ok(())
     }
 
-    pub fn stage_hunks(&self, commit: &GaiCommit) {
+    /// the patch is kept in failed.patch when
+    /// it doesn't apply, to see what went wrong
+    pub fn stage_hunks(&self, commit: &GaiCommit) -> Result<()> {
         let patch = self.create_patches(&commit.hunk_ids);
 
-        match git2::Diff::from_buffer(patch.as_bytes()) {
-            Ok(diff) => {
-                match self.repo.apply(
-                    &diff,
-                    git2::ApplyLocation::Index,
-                    None,
-                ) {
-                    Ok(_) => println!("Staged and Applied Hunks!"),
-                    Err(e) => {
-                        println!("failed to stage hunks: {}", e);
+        let diff = git2::Diff::from_buffer(patch.as_bytes())
+            .map_err(|e| anyhow!("failed to parse patches: {}", e))?;
 
-                        std::fs::write("failed.patch", &patch)
-                            .unwrap()
-                    }
+        if let Err(e) =
+            self.repo.apply(&diff, git2::ApplyLocation::Index, None)
+        {
+            return match fs::write("failed.patch", &patch) {
+                Ok(()) => Err(anyhow!(
+                    "failed to stage hunks: {}, the patch is in failed.patch",
+                    e
+                )),
+                Err(_) => {
+                    Err(anyhow!("failed to stage hunks: {}", e))
                 }
-            }
-
-            Err(e) => println!("failed parse patches: {}", e),
+            };
         }
+
+        Ok(())
     }
 
     fn stage_files(
         &self,
         index: &mut git2::Index,
-        commit: &GaiCommit,
-    ) {
-        for path in &commit.files {
+        files: &[&String],
+    ) -> Result<()> {
+        for path in files {
             let path = Path::new(&path);
-            let status = self.repo.status_file(path).unwrap();
+            let status = self.repo.status_file(path)?;
 
             // todo: some changes will implement a combo
             // ex: modified + renamed
@@ -106,16 +620,85 @@ impl GaiGit {
             if status.contains(git2::Status::WT_MODIFIED)
                 || status.contains(git2::Status::WT_NEW)
             {
-                index.add_path(path).unwrap();
+                index.add_path(path)?;
             }
             if status.contains(git2::Status::WT_DELETED) {
-                index.remove_path(path).unwrap();
+                index.remove_path(path)?;
             }
             if status.contains(git2::Status::WT_TYPECHANGE) {
-                index.remove_path(path).unwrap();
-                index.add_path(path).unwrap();
+                index.remove_path(path)?;
+                index.add_path(path)?;
+            }
+
+            // the deletion half of a rename
+            if let Some(old) =
+                path.to_str().and_then(|p| self.renamed_from(p))
+            {
+                index.remove_path(Path::new(old))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn renamed_from(&self, path: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|f| f.path == path)
+            .and_then(|f| f.old_path.as_deref())
+    }
+
+    /// only_staged counterpart of stage_files
+    /// takes the entries from the staged index
+    /// instead of the working tree
+    fn stage_staged_files(
+        &self,
+        index: &mut git2::Index,
+        staged: &git2::Index,
+        files: &[&String],
+    ) -> Result<()> {
+        for path in files {
+            let path = Path::new(&path);
+
+            match staged.get_path(path, 0) {
+                Some(entry) => index.add(&entry)?,
+                // staged deletion
+                None => {
+                    if index.get_path(path, 0).is_some() {
+                        index.remove_path(path)?;
+                    }
+                }
+            }
+
+            if let Some(old) =
+                path.to_str().and_then(|p| self.renamed_from(p))
+            {
+                index.remove_path(Path::new(old))?;
             }
         }
+
+        Ok(())
+    }
+
+    /// stages the included hunks of files
+    /// that had some of their hunks excluded
+    fn stage_partial_files(&self, files: &[&String]) -> Result<()> {
+        let hunk_ids: Vec<String> = self
+            .files
+            .iter()
+            .filter(|f| files.contains(&&f.path))
+            .flat_map(|f| f.included_hunk_ids())
+            .collect();
+
+        if hunk_ids.is_empty() {
+            return Ok(());
+        }
+
+        let patch = self.create_patches(&hunk_ids);
+        let diff = git2::Diff::from_buffer(patch.as_bytes())?;
+        self.repo.apply(&diff, git2::ApplyLocation::Index, None)?;
+
+        Ok(())
     }
 
     fn create_patches(&self, hunk_ids: &[String]) -> String {
@@ -147,7 +730,9 @@ impl GaiGit {
                 continue;
             };
 
-            let Some(hunk) = file.hunks.get(i) else {
+            let Some(hunk) =
+                file.hunks.get(i).filter(|h| !h.excluded)
+            else {
                 println!(
                     "le hunk {} not found in file {}",
                     i, file_path
@@ -163,11 +748,31 @@ impl GaiGit {
             // helps, since we can also print them out
             // if they lets say fail
             if current_file != file_path {
+                let old_path =
+                    file.old_path.as_deref().unwrap_or(file_path);
+
                 patch.push_str(&format!(
                     "diff --git a/{} b/{}\n",
-                    file_path, file_path
+                    old_path, file_path
                 ));
-                patch.push_str(&format!("--- a/{}\n", file_path));
+                if old_path != file_path {
+                    patch.push_str(&format!(
+                        "rename from {}\nrename to {}\n",
+                        old_path, file_path
+                    ));
+                }
+                // untracked files come from /dev/null
+                let is_new = self
+                    .repo
+                    .status_file(Path::new(file_path))
+                    .is_ok_and(|s| s.contains(git2::Status::WT_NEW));
+
+                if is_new {
+                    patch.push_str("new file mode 100644\n");
+                    patch.push_str("--- /dev/null\n");
+                } else {
+                    patch.push_str(&format!("--- a/{}\n", old_path));
+                }
                 patch.push_str(&format!("+++ b/{}\n", file_path));
                 current_file = file_path.to_string();
             }
@@ -192,3 +797,171 @@ impl GaiGit {
         patch
     }
 }
+
+/// how many leading directories two paths have in common
+fn shared_dirs(a: &str, b: &str) -> usize {
+    dirs(a).zip(dirs(b)).take_while(|(a, b)| a == b).count()
+}
+
+fn dirs(path: &str) -> impl Iterator<Item = &str> {
+    path.rsplit_once('/')
+        .map_or("", |(dir, _)| dir)
+        .split('/')
+        .filter(|d| !d.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GaiConfig;
+    use std::path::PathBuf;
+
+    /// a repo with one commit, dirs are per test so
+    /// they can run in parallel
+    fn repo(name: &str) -> (PathBuf, git2::Repository) {
+        let dir = std::env::temp_dir().join(format!(
+            "gai-staging-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = git2::Repository::init(&dir).unwrap();
+
+        let mut cfg = repo.config().unwrap();
+        cfg.set_str("user.name", "gai").unwrap();
+        cfg.set_str("user.email", "gai@example.com").unwrap();
+
+        fs::write(dir.join("README.md"), "gai\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("README.md")).unwrap();
+        let tree =
+            repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let sig = repo.signature().unwrap();
+        repo.commit(Some("HEAD"), &sig, &sig, "init", &tree, &[])
+            .unwrap();
+        drop(tree);
+
+        (dir, repo)
+    }
+
+    fn commit(header: &str, files: &[&str]) -> GaiCommit {
+        GaiCommit {
+            files: files.iter().map(|f| f.to_string()).collect(),
+            hunk_ids: Vec::new(),
+            header: header.to_owned(),
+            body: None,
+            footers: Vec::new(),
+        }
+    }
+
+    fn tree_files(repo: &git2::Repository, rev: &str) -> Vec<String> {
+        let tree = repo
+            .revparse_single(rev)
+            .unwrap()
+            .peel_to_tree()
+            .unwrap();
+        let mut files = Vec::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+            if entry.kind() == Some(git2::ObjectType::Blob) {
+                files.push(format!(
+                    "{}{}",
+                    dir,
+                    entry.name().unwrap()
+                ));
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .unwrap();
+        files
+    }
+
+    #[test]
+    fn a_failed_commit_rolls_back_the_earlier_ones() {
+        let (dir, repo) = repo("roll-back");
+        fs::write(dir.join("a.rs"), "a\n").unwrap();
+        fs::write(dir.join("b.rs"), "b\n").unwrap();
+        fs::write(dir.join("README.md"), "gai staged\n").unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("README.md")).unwrap();
+        index.write().unwrap();
+        drop(index);
+        let index_before = fs::read(dir.join(".git/index")).unwrap();
+        let head_before = repo.head().unwrap().target();
+
+        let mut gai =
+            GaiGit::new(repo, &GaiConfig::default()).unwrap();
+        gai.create_diffs(&[]).unwrap();
+
+        // b.rs can't be staged once it's a directory
+        fs::remove_file(dir.join("b.rs")).unwrap();
+        fs::create_dir(dir.join("b.rs")).unwrap();
+        fs::write(dir.join("b.rs/inner"), "b\n").unwrap();
+
+        let err = gai
+            .apply_commits(&[
+                commit("feat: add a", &["a.rs", "README.md"]),
+                commit("feat: add b", &["b.rs"]),
+            ])
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains(
+                "commit 2 of 2 (feat: add b) failed, nothing was applied"
+            ),
+            "{:#}",
+            err
+        );
+
+        let repo = git2::Repository::open(&dir).unwrap();
+        assert_eq!(repo.head().unwrap().target(), head_before);
+        assert_eq!(
+            fs::read(dir.join(".git/index")).unwrap(),
+            index_before
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn shared_dirs_counts_leading_directories() {
+        assert_eq!(shared_dirs("src/git/a.rs", "src/git/b.rs"), 2);
+        assert_eq!(shared_dirs("src/git/a.rs", "src/ai/b.rs"), 1);
+        assert_eq!(shared_dirs("a.rs", "src/b.rs"), 0);
+    }
+
+    #[test]
+    fn ignored_files_are_committed_with_their_directory() {
+        let (dir, repo) = repo("ignored");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("docs")).unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("src/gen.lock"), "locked\n").unwrap();
+        fs::write(dir.join("docs/usage.md"), "usage\n").unwrap();
+
+        let mut gai =
+            GaiGit::new(repo, &GaiConfig::default()).unwrap();
+        gai.load_ignores(&["*.lock".to_owned()]).unwrap();
+        gai.create_diffs(&[]).unwrap();
+
+        // never described, but still there to commit
+        assert!(gai.files.iter().all(|f| f.path != "src/gen.lock"));
+        assert_eq!(gai.ignored, vec!["src/gen.lock".to_owned()]);
+
+        gai.apply_commits(&[
+            commit("docs: add usage", &["docs/usage.md"]),
+            commit("feat: add main", &["src/main.rs"]),
+        ])
+        .unwrap();
+
+        let repo = git2::Repository::open(&dir).unwrap();
+        let head = tree_files(&repo, "HEAD");
+        assert!(head.contains(&"src/gen.lock".to_owned()));
+        // the docs commit didn't get it
+        assert!(
+            !tree_files(&repo, "HEAD~1")
+                .contains(&"src/gen.lock".to_owned())
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
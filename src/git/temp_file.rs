@@ -0,0 +1,57 @@
+use anyhow::Result;
+use std::{
+    env, fs,
+    io::{self, Write},
+    path::PathBuf,
+    process,
+};
+
+/// a new file in the temp dir that only the caller uses,
+/// create_new never follows a symlink or reuses a file, so
+/// two gai instances can't swap each other's. the caller
+/// removes it once it's done
+pub fn create(prefix: &str, contents: &[u8]) -> Result<PathBuf> {
+    let mut n = 0;
+
+    loop {
+        let path = env::temp_dir().join(format!(
+            "{}-{}-{}",
+            prefix,
+            process::id(),
+            n
+        ));
+
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(mut file) => {
+                file.write_all(contents)?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                n += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_file_is_new() {
+        let first = create("gai-temp-test", b"one").unwrap();
+        let second = create("gai-temp-test", b"two").unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(fs::read(&first).unwrap(), b"one");
+        assert_eq!(fs::read(&second).unwrap(), b"two");
+
+        let _ = fs::remove_file(first);
+        let _ = fs::remove_file(second);
+    }
+}
@@ -0,0 +1,183 @@
+use crate::git::repo::{DiffType, LineDiff};
+
+/// past this many tokens on a line the lcs gets
+/// expensive, the line is shown whole instead
+const MAX_TOKENS: usize = 200;
+
+/// a piece of a changed line, changed
+/// when it isn't on the other side
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub text: String,
+    pub changed: bool,
+}
+
+/// word level segments for every line of a hunk, None
+/// for the lines that aren't paired. a run of deletions
+/// is paired with the additions right after it only when
+/// there are as many of each, otherwise which line became
+/// which is a guess
+pub fn hunk_segments(
+    lines: &[LineDiff],
+) -> Vec<Option<Vec<Segment>>> {
+    let mut segments = vec![None; lines.len()];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let removed = run_len(lines, i, DiffType::Deletions);
+        let added = run_len(lines, i + removed, DiffType::Additions);
+
+        if removed == 0 || removed != added {
+            i += removed.max(1);
+            continue;
+        }
+
+        for k in i..i + removed {
+            let (old, new) = (k, k + removed);
+            if let Some((old_segments, new_segments)) = line_segments(
+                &lines[old].content,
+                &lines[new].content,
+            ) {
+                segments[old] = Some(old_segments);
+                segments[new] = Some(new_segments);
+            }
+        }
+
+        i += removed * 2;
+    }
+
+    segments
+}
+
+fn run_len(
+    lines: &[LineDiff],
+    start: usize,
+    kind: DiffType,
+) -> usize {
+    lines
+        .get(start..)
+        .unwrap_or_default()
+        .iter()
+        .take_while(|l| l.diff_type == kind)
+        .count()
+}
+
+/// None when the lines are too long or have too little
+/// in common, highlighting nearly everything helps no one
+fn line_segments(
+    old: &str,
+    new: &str,
+) -> Option<(Vec<Segment>, Vec<Segment>)> {
+    let old = tokenize(old.trim_end_matches('\n'));
+    let new = tokenize(new.trim_end_matches('\n'));
+
+    if old.len() > MAX_TOKENS || new.len() > MAX_TOKENS {
+        return None;
+    }
+
+    let (old_kept, new_kept) = lcs(&old, &new);
+
+    let common: usize = old
+        .iter()
+        .zip(&old_kept)
+        .filter(|(token, kept)| **kept && !token.trim().is_empty())
+        .map(|(token, _)| token.len())
+        .sum();
+    let total =
+        old.iter().chain(&new).map(|t| t.len()).sum::<usize>();
+
+    if common * 2 * 3 < total {
+        return None;
+    }
+
+    Some((segments(&old, &old_kept), segments(&new, &new_kept)))
+}
+
+/// words, whitespace runs and single punctuation
+/// characters, ex. "foo(bar, 1)" -> foo ( bar , " " 1 )
+fn tokenize(line: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+
+    let class = |c: char| {
+        if c.is_alphanumeric() || c == '_' {
+            0
+        } else if c.is_whitespace() {
+            1
+        } else {
+            2
+        }
+    };
+
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, n)| *n);
+
+        // punctuation is never grouped
+        if class(c) == 2 || next.is_none_or(|n| class(n) != class(c))
+        {
+            let end = i + c.len_utf8();
+            tokens.push(&line[start..end]);
+            start = end;
+        }
+    }
+
+    tokens
+}
+
+/// which tokens of each side are part of the
+/// longest common subsequence
+fn lcs(old: &[&str], new: &[&str]) -> (Vec<bool>, Vec<bool>) {
+    let (n, m) = (old.len(), new.len());
+    let mut table = vec![vec![0u16; m + 1]; n + 1];
+
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            table[i][j] = if old[i] == new[j] {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let mut old_kept = vec![false; n];
+    let mut new_kept = vec![false; m];
+    let (mut i, mut j) = (0, 0);
+
+    while i < n && j < m {
+        if old[i] == new[j] {
+            old_kept[i] = true;
+            new_kept[j] = true;
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    (old_kept, new_kept)
+}
+
+/// neighbouring tokens with the same state are joined
+fn segments(tokens: &[&str], kept: &[bool]) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+
+    for (token, kept) in tokens.iter().zip(kept) {
+        let changed = !kept;
+
+        match segments.last_mut() {
+            Some(last) if last.changed == changed => {
+                last.text.push_str(token)
+            }
+            _ => segments.push(Segment {
+                text: token.to_string(),
+                changed,
+            }),
+        }
+    }
+
+    segments
+}
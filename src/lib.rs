@@ -0,0 +1,124 @@
+//! gai's commit generation without the TUI
+//!
+//! ```no_run
+//! # async fn run() -> anyhow::Result<()> {
+//! let cfg = gai::config::Config::init()?;
+//! for commit in gai::generate(&cfg, "path/to/repo".as_ref()).await? {
+//!     println!("{}", commit.message());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! the types an embedder needs are [`config::Config`] (or
+//! `Config::default()` to skip the config files),
+//! [`git::commit::GaiCommit`] and, to go step by step,
+//! [`git::repo::GaiGit`], [`ai::request::Request`] and
+//! [`ai::response::get_response`]. with
+//! `default-features = false` the `cli` feature is off, which
+//! leaves out the TUI and its terminal crates (ratatui,
+//! crossterm, clap, dialoguer and friends)
+
+pub mod ai;
+#[cfg(feature = "cli")]
+pub mod args;
+pub mod auth;
+pub mod config;
+pub mod consts;
+pub mod git;
+#[cfg(feature = "cli")]
+pub mod graph;
+#[cfg(feature = "cli")]
+pub mod print;
+#[cfg(feature = "cli")]
+pub mod tui;
+
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::path::Path;
+
+use crate::{
+    ai::{request::Request, response::get_response},
+    config::Config,
+    git::{commit::GaiCommit, repo::GaiGit},
+};
+
+/// the commits gai would make for the changes in the repo at
+/// repo_path, nothing is applied. none when there aren't any
+/// changes. GaiCommit::message() is the full commit message
+pub async fn generate(
+    cfg: &Config,
+    repo_path: &Path,
+) -> Result<Vec<GaiCommit>> {
+    let mut gai = open_repo(cfg, Some(repo_path))?;
+    gai.create_diffs(&cfg.ai.files_to_truncate)?;
+
+    if !gai.has_changes() {
+        return Ok(Vec::new());
+    }
+
+    let (req, _) = build_request(cfg, &mut gai);
+    req.check_context(&cfg.ai)?;
+
+    let result = get_response(&req, &cfg.ai).await.into_result()?;
+
+    Ok(result
+        .commits
+        .iter()
+        .map(|c| GaiCommit::from_response(c, &cfg.gai.commit_config))
+        .collect())
+}
+
+/// the repo set up the way cfg.gai says, without any diffs
+/// yet. None finds it like git does, from GIT_DIR or the
+/// current directory up
+pub fn open_repo(
+    cfg: &Config,
+    path: Option<&Path>,
+) -> Result<GaiGit> {
+    let repo = match path {
+        Some(path) => {
+            Repository::discover(path).with_context(|| {
+                format!(
+                    "{} isn't in a git repository",
+                    path.display()
+                )
+            })?
+        }
+        None => Repository::open_from_env()?,
+    };
+
+    let mut gai = GaiGit::new(repo, &cfg.gai)?;
+
+    if cfg.gai.signoff {
+        gai.add_signoff()?;
+    }
+
+    gai.auto_stage = cfg.gai.auto_stage;
+    gai.include_untracked = cfg.ai.include_untracked;
+    gai.load_ignores(&cfg.gai.ignore_globs)?;
+
+    Ok(gai)
+}
+
+/// the request for whatever is in gai's diffs, run after
+/// one of the create_*diffs. also returns the files that
+/// were truncated to fit ai.max_diff_chars
+pub fn build_request(
+    cfg: &Config,
+    gai: &mut GaiGit,
+) -> (Request, Vec<String>) {
+    let truncated = gai.auto_truncate(
+        cfg.ai.max_diff_chars,
+        cfg.gai.summarize_over(),
+    );
+
+    let mut req = Request::default();
+    req.build_prompt(cfg, gai);
+    let mut diffs = gai.get_file_diffs_as_str();
+    req.hold_large_files(&cfg.gai, &mut diffs);
+    req.split(cfg.gai.split_strategy, &diffs);
+    req.build_diffs_string(diffs);
+
+    (req, truncated)
+}
@@ -1,73 +1,257 @@
-pub mod ai;
-pub mod args;
-pub mod auth;
-pub mod config;
-pub mod consts;
-pub mod git;
-pub mod graph;
-pub mod print;
-pub mod tui;
-
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
 use dialoguer::{Confirm, Select, theme::ColorfulTheme};
 use dotenv::dotenv;
-
-use crate::{
-    ai::{request::Request, response::get_response},
-    args::{Args, Auth, Commands},
+use std::{fs, io::Read, path::Path};
+
+use gai::{
+    ai::{
+        debug_log,
+        request::Request,
+        response::{RequestFailed, get_response},
+    },
+    args::{Args, Auth, Commands, OutputFormat},
     auth::{auth_login, auth_status, clear_auth},
-    config::Config,
-    git::{commit::GaiCommit, repo::GaiGit},
-    print::{SpinDeez, pretty_print_commits, pretty_print_status},
+    config::{Config, SplitStrategy},
+    consts::PROMPT_REGENERATE,
+    git::{commit::GaiCommit, hook, repo::GaiGit},
+    open_repo,
+    print::{
+        SpinDeez, json_print_commits, json_print_error,
+        pretty_print_commits, pretty_print_status,
+    },
     tui::run_tui,
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
-    let mut cfg = config::Config::init()?;
-
     let args = Args::parse();
-    let spinner = SpinDeez::new()?;
+
+    // scripts need the error as json too,
+    // plus a non-zero exit
+    let json = args.is_json();
+    let quiet = args.is_quiet();
+    if json || quiet {
+        if let Err(e) = run(args).await {
+            if json {
+                json_print_error(&e);
+            } else {
+                eprintln!(
+                    "error: {}",
+                    format!("{:#}", e)
+                        .lines()
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                );
+            }
+
+            std::process::exit(if quiet { exit_code(&e) } else { 1 });
+        }
+
+        return Ok(());
+    }
+
+    run(args).await
+}
+
+/// the diff for --patch, - reads it from stdin
+fn read_patch(path: &Path) -> Result<Vec<u8>> {
+    if path == Path::new("-") {
+        let mut patch = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut patch)
+            .context("can't read the patch from stdin")?;
+        return Ok(patch);
+    }
+
+    fs::read(path)
+        .with_context(|| format!("can't read {}", path.display()))
+}
+
+/// what --quiet exits with, anything that isn't
+/// a failed request is a plain 1
+fn exit_code(err: &anyhow::Error) -> i32 {
+    err.downcast_ref::<RequestFailed>()
+        .map_or(1, |e| e.kind.exit_code())
+}
+
+async fn run(args: Args) -> Result<()> {
+    let mut cfg = Config::init()?;
+    let spinner = if args.is_quiet() {
+        SpinDeez::hidden()
+    } else {
+        SpinDeez::new()?
+    };
 
     args.parse_flags(&mut cfg)?;
 
+    if let Some(path) = &args.debug_log {
+        debug_log::init(path, &cfg)?;
+    }
+
     match args.command {
         Commands::Auth { ref auth } => {
             run_auth(auth, &spinner).await?;
         }
 
         _ => {
-            let mut gai = GaiGit::new(
-                cfg.gai.only_staged,
-                cfg.gai.stage_hunks,
-                cfg.gai.commit_config.capitalize_prefix,
-                cfg.gai.commit_config.include_scope,
-            )?;
+            let mut gai = open_repo(&cfg, None)?;
 
-            gai.create_diffs(&cfg.ai.files_to_truncate)?;
+            if let Commands::Commit { ref paths, .. } = args.command {
+                gai.restrict_to(paths)?;
+            }
+
+            let amend = matches!(
+                args.command,
+                Commands::Commit { amend: true, .. }
+            );
+
+            let patch = match &args.command {
+                Commands::Commit {
+                    patch: Some(path), ..
+                } => Some(read_patch(path)?),
+                _ => None,
+            };
+
+            if amend {
+                if args.interactive {
+                    return Err(anyhow!(
+                        "--amend can't be used with the TUI"
+                    ));
+                }
+
+                if !matches!(
+                    args.command,
+                    Commands::Commit { force: true, .. }
+                ) && gai.head_is_pushed()?
+                {
+                    return Err(anyhow!(
+                        "HEAD is already pushed to its upstream, use --force to amend it anyway"
+                    ));
+                }
+
+                // one message for HEAD, the working
+                // tree has nothing to do with it
+                cfg.gai.split_strategy = SplitStrategy::None;
+                cfg.ai.include_git_status = false;
+                gai.create_amend_diffs(&cfg.ai.files_to_truncate)?;
+            } else if let Some(patch) = &patch {
+                // the working tree isn't what's described
+                cfg.ai.include_git_status = false;
+                gai.create_patch_diffs(
+                    patch,
+                    &cfg.ai.files_to_truncate,
+                )
+                .context("can't parse the patch")?;
+            } else {
+                gai.create_diffs(&cfg.ai.files_to_truncate)?;
+                gai.check_paths()?;
+            }
+
+            // applying refuses these later, say so up front
+            for path in gai.conflicted_files() {
+                eprintln!(
+                    "Warning: {} has unresolved conflict markers",
+                    path
+                );
+            }
+
+            // amending works off HEAD's own diff
+            let no_changes = !amend && !gai.has_changes();
+
+            // nothing but the messages should end up in
+            // stdout here, a patch has nowhere to apply to
+            if let Commands::Commit {
+                print,
+                quiet,
+                format,
+                ref config,
+                ..
+            } = args.command
+                && (print
+                    || quiet
+                    || format == OutputFormat::Json
+                    || (patch.is_some() && !args.interactive))
+            {
+                if no_changes {
+                    eprintln!("No changes to commit");
+                    return Ok(());
+                }
+
+                let mut cfg = match config {
+                    Some(c) => cfg.override_cfg(c)?,
+                    None => cfg,
+                };
+
+                // piped into git commit -F -, which
+                // takes a single message
+                if (print || quiet) && format == OutputFormat::Text {
+                    cfg.gai.max_commits = Some(1);
+                }
+
+                let mut req = build_request(&cfg, &mut gai, &spinner);
+                if amend {
+                    req.prompt.push_str(PROMPT_REGENERATE);
+                }
+                return run_print(req, cfg, format).await;
+            }
 
             if args.interactive {
-                let req = build_request(&cfg, &gai, &spinner);
+                let req = build_request(&cfg, &mut gai, &spinner);
                 run_tui(req, cfg, gai, None).await?;
                 return Ok(());
             }
 
+            if let Commands::Commit {
+                skip_confirmation,
+                dry_run,
+                config,
+                amend: true,
+                ..
+            } = args.command
+            {
+                let cfg = match config {
+                    Some(c) => cfg.override_cfg(&c)?,
+                    None => cfg,
+                };
+
+                let mut req = build_request(&cfg, &mut gai, &spinner);
+                req.prompt.push_str(PROMPT_REGENERATE);
+
+                return run_amend(
+                    &spinner,
+                    req,
+                    cfg,
+                    gai,
+                    skip_confirmation,
+                    dry_run,
+                )
+                .await;
+            }
+
             pretty_print_status(&gai, args.compact)?;
 
+            #[allow(clippy::collapsible_match)]
             match args.command {
                 Commands::Commit {
                     skip_confirmation,
+                    dry_run,
+                    force,
                     config,
                     ..
                 } => {
+                    if no_changes {
+                        println!("No changes to commit");
+                        return Ok(());
+                    }
+
                     let cfg = match config {
                         Some(c) => cfg.override_cfg(&c)?,
                         None => cfg,
                     };
 
-                    let req = build_request(&cfg, &gai, &spinner);
+                    let req = build_request(&cfg, &mut gai, &spinner);
 
                     run_commit(
                         &spinner,
@@ -75,13 +259,16 @@ async fn main() -> Result<()> {
                         cfg,
                         gai,
                         skip_confirmation,
+                        dry_run,
+                        force,
                         args.compact,
                     )
                     .await?
                 }
                 Commands::Status { verbose } => {
                     if verbose {
-                        let req = build_request(&cfg, &gai, &spinner);
+                        let req =
+                            build_request(&cfg, &mut gai, &spinner);
                         println!("{}", req);
                     }
                 }
@@ -93,16 +280,73 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// commits apply_commits dropped, none
+/// of their files were left in the diffs
+fn print_skipped(skipped: usize) {
+    if skipped > 0 {
+        println!(
+            "Skipped {} commit{} whose files are no longer in the diffs",
+            skipped,
+            if skipped == 1 { "" } else { "s" }
+        );
+    }
+}
+
+/// runs post_apply_hook in the foreground, when it fails
+/// and we may ask, offers to soft reset back to head
+fn post_apply(
+    gai: &GaiGit,
+    cfg: &Config,
+    head: Option<git2::Oid>,
+    applied: usize,
+    ask: bool,
+) -> Result<()> {
+    let Some(command) = cfg.gai.hook_command() else {
+        return Ok(());
+    };
+
+    println!("Running post_apply_hook: {}", command);
+    let run = hook::run(command, &gai.workdir()?, false)?;
+    println!("post_apply_hook finished with {}", run.status);
+
+    // nothing to go back to on a branch without commits
+    let Some(head) = head.filter(|_| ask && !run.status.success())
+    else {
+        return Ok(());
+    };
+
+    if Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(format!(
+            "Soft reset the {} applied commit{}? The changes stay staged",
+            applied,
+            if applied == 1 { "" } else { "s" }
+        ))
+        .interact()?
+    {
+        gai.soft_reset(head)?;
+        println!("Reset to {}", head);
+    }
+
+    Ok(())
+}
+
 fn build_request(
     cfg: &Config,
-    gai: &GaiGit,
+    gai: &mut GaiGit,
     spinner: &SpinDeez,
 ) -> Request {
     spinner.start("Building Request...");
-    let mut req = Request::default();
-    req.build_prompt(cfg, gai);
-    req.build_diffs_string(gai.get_file_diffs_as_str());
-    spinner.stop(None);
+    let (req, truncated) = gai::build_request(cfg, gai);
+
+    if truncated.is_empty() {
+        spinner.stop(None);
+    } else {
+        spinner.stop(Some(&format!(
+            "Auto-truncated to fit ai.max_diff_chars: {}",
+            truncated.join(", ")
+        )));
+    }
+
     req
 }
 
@@ -116,30 +360,111 @@ async fn run_auth(auth: &Auth, spinner: &SpinDeez) -> Result<()> {
     Ok(())
 }
 
+async fn run_print(
+    req: Request,
+    cfg: Config,
+    format: OutputFormat,
+) -> Result<()> {
+    req.check_context(&cfg.ai)?;
+
+    let result = get_response(&req, &cfg.ai).await.into_result()?;
+
+    if format == OutputFormat::Json {
+        return json_print_commits(&result.commits, &cfg);
+    }
+
+    for (i, resp_commit) in result.commits.iter().enumerate() {
+        let commit = GaiCommit::from_response(
+            resp_commit,
+            &cfg.gai.commit_config,
+        );
+
+        if i > 0 {
+            println!();
+        }
+
+        println!("{}", commit.message().trim_end());
+    }
+
+    Ok(())
+}
+
+async fn run_amend(
+    spinner: &SpinDeez,
+    req: Request,
+    cfg: Config,
+    gai: GaiGit,
+    skip_confirmation: bool,
+    dry_run: bool,
+) -> Result<()> {
+    req.check_context(&cfg.ai)?;
+
+    spinner.start(&format!(
+        "Regenerating the message for HEAD using {}",
+        cfg.ai.provider
+    ));
+
+    let response = get_response(&req, &cfg.ai).await;
+    spinner.stop(None);
+
+    let result = response.into_result()?;
+    let resp_commit = result
+        .commits
+        .first()
+        .ok_or_else(|| anyhow!("the provider returned no commits"))?;
+
+    let commit =
+        GaiCommit::from_response(resp_commit, &cfg.gai.commit_config);
+
+    println!("{}", commit.message().trim_end());
+
+    if dry_run {
+        println!();
+        print!("{}", gai.dry_run_amend(&commit));
+        return Ok(());
+    }
+
+    if !skip_confirmation
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Amend HEAD with this message?")
+            .interact()?
+    {
+        println!("Exiting");
+        return Ok(());
+    }
+
+    gai.amend_head(&commit)?;
+    println!("Amended HEAD");
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run_commit(
     spinner: &SpinDeez,
     req: Request,
     cfg: Config,
     gai: GaiGit,
     skip_confirmation: bool,
+    dry_run: bool,
+    force: bool,
     compact: bool,
 ) -> Result<()> {
-    let provider = cfg.ai.provider;
     let provider_cfg = cfg
         .ai
         .providers
-        .get(&provider)
+        .get(&cfg.ai.provider)
         .expect("somehow did not find provider config");
 
+    req.check_context(&cfg.ai)?;
+
     loop {
         spinner.start(&format!(
             "Awaiting response from {} using {}",
             cfg.ai.provider, provider_cfg.model
         ));
 
-        let response =
-            get_response(&req, provider, provider_cfg.to_owned())
-                .await;
+        let response = get_response(&req, &cfg.ai).await;
 
         let result = match response.result.clone() {
             Ok(r) => r,
@@ -182,26 +507,73 @@ async fn run_commit(
             if result.commits.len() == 1 { "" } else { "s" }
         );
 
+        if let Some(provider) = response.provider
+            && provider != cfg.ai.provider
+        {
+            println!(
+                "{} failed, used {} instead",
+                cfg.ai.provider, provider
+            );
+        }
+
         pretty_print_commits(&result.commits, &cfg, &gai, compact)?;
 
+        let allowed_prefixes = cfg.gai.allowed_prefixes();
+        let mut invalid = 0;
+        for (i, commit) in result.commits.iter().enumerate() {
+            let reasons = commit.validate(
+                &allowed_prefixes,
+                cfg.ai.rules.max_header_length,
+            );
+
+            if !reasons.is_empty() {
+                invalid += 1;
+                println!(
+                    "Warning: Commit {} is invalid: {}",
+                    i + 1,
+                    reasons.join(", ")
+                );
+            }
+        }
+
         let commits: Vec<GaiCommit> = result
             .commits
             .iter()
             .map(|resp_commit| {
                 GaiCommit::from_response(
                     resp_commit,
-                    cfg.gai.commit_config.capitalize_prefix,
-                    cfg.gai.commit_config.include_scope,
+                    &cfg.gai.commit_config,
                 )
             })
             .collect();
 
+        if dry_run {
+            println!();
+            print!("{}", gai.dry_run(&commits));
+            break;
+        }
+
         if skip_confirmation {
+            // nobody is there to look at the warnings
+            if invalid > 0 && !force {
+                return Err(anyhow!(
+                    "{} of the commits failed validation, use --force to apply them anyway",
+                    invalid
+                ));
+            }
+
             println!("Skipping confirmation and applying commits...");
-            gai.apply_commits(&commits);
+            let head = gai.head_oid();
+            let applied = gai.apply_commits(&commits)?.len();
+            print_skipped(commits.len() - applied);
+            post_apply(&gai, &cfg, head, applied, false)?;
             break;
         }
 
+        if let Some(command) = cfg.gai.hook_command() {
+            println!("Applying runs post_apply_hook: {}", command);
+        }
+
         let options = ["Apply All", "Show in TUI", "Retry", "Exit"];
 
         let selection = Select::with_theme(&ColorfulTheme::default())
@@ -213,7 +585,10 @@ async fn run_commit(
 
         if selection == 0 {
             println!("Applying Commits...");
-            gai.apply_commits(&commits);
+            let head = gai.head_oid();
+            let applied = gai.apply_commits(&commits)?.len();
+            print_skipped(commits.len() - applied);
+            post_apply(&gai, &cfg, head, applied, true)?;
         } else if selection == 1 {
             let _ = run_tui(req, cfg, gai, Some(response)).await;
         } else if selection == 2 {
@@ -4,13 +4,14 @@ use crossterm::{
     style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
 };
 use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
 use std::io::stdout;
 
 use crate::{
     ai::response::ResponseCommit,
     config::Config,
-    consts::{PROGRESS_TEMPLATE, PROGRESS_TICK},
-    git::repo::GaiGit,
+    consts::{JSON_OUTPUT_VERSION, PROGRESS_TEMPLATE, PROGRESS_TICK},
+    git::{commit::GaiCommit, repo::GaiGit},
     graph::Arena,
 };
 
@@ -33,6 +34,13 @@ impl SpinDeez {
         Ok(Self { spinner: bar })
     }
 
+    /// for --quiet, start and stop draw nothing
+    pub fn hidden() -> Self {
+        Self {
+            spinner: ProgressBar::hidden(),
+        }
+    }
+
     pub fn start(&self, msg: &str) {
         self.spinner.reset();
 
@@ -256,7 +264,8 @@ pub fn pretty_print_status(
             stdout,
             SetForegroundColor(Color::Yellow),
             Print("No Diffs".bold()),
-            ResetColor
+            ResetColor,
+            Print("\n")
         )?;
 
         return Ok(());
@@ -391,10 +400,7 @@ fn compact_print_commits(
     let mut stdout = stdout();
 
     for (i, commit) in commits.iter().enumerate() {
-        let prefix = commit.get_commit_prefix(
-            cfg.gai.commit_config.capitalize_prefix,
-            cfg.gai.commit_config.include_scope,
-        );
+        let prefix = commit.get_commit_prefix(&cfg.gai.commit_config);
 
         execute!(
             stdout,
@@ -474,10 +480,7 @@ pub fn pretty_print_commits(
     let mut arena = Arena::new();
 
     for (i, commit) in commits.iter().enumerate() {
-        let prefix = commit.get_commit_prefix(
-            cfg.gai.commit_config.capitalize_prefix,
-            cfg.gai.commit_config.include_scope,
-        );
+        let prefix = commit.get_commit_prefix(&cfg.gai.commit_config);
 
         let commit_root = arena
             .new_node(format!("Commit {}", i + 1), Color::DarkGrey);
@@ -500,6 +503,18 @@ pub fn pretty_print_commits(
             arena.add_child(commit_root, body_node);
         }
 
+        if !commit.message.footers.is_empty() {
+            let footers_parent =
+                arena.new_node("Footers", Color::Yellow);
+            arena.add_child(commit_root, footers_parent);
+
+            for footer in &commit.message.footers {
+                let footer_node =
+                    arena.new_node(footer, Color::White);
+                arena.add_child(footers_parent, footer_node);
+            }
+        }
+
         if gai.stage_hunks {
             let hunks_node = arena.new_node(
                 format!("Hunks: {:?}", commit.hunk_ids),
@@ -523,3 +538,77 @@ pub fn pretty_print_commits(
 
     Ok(())
 }
+
+/// --format json, fields only get added
+/// within a version
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    version: u32,
+    commits: Vec<JsonCommit<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonCommit<'a> {
+    prefix: String,
+    scope: String,
+    breaking: bool,
+    header: &'a str,
+    body: &'a str,
+    footers: &'a [String],
+    /// the full message as it would be committed
+    message: String,
+    files: &'a [String],
+    hunk_ids: &'a [String],
+}
+
+#[derive(Serialize)]
+struct JsonError {
+    version: u32,
+    error: String,
+}
+
+pub fn json_print_commits(
+    commits: &[ResponseCommit],
+    cfg: &Config,
+) -> Result<()> {
+    let commits = commits
+        .iter()
+        .map(|commit| JsonCommit {
+            prefix: format!("{:?}", commit.message.prefix)
+                .to_lowercase(),
+            scope: commit.scope(&cfg.gai.commit_config),
+            breaking: commit.message.breaking,
+            header: &commit.message.header,
+            body: &commit.message.body,
+            footers: &commit.message.footers,
+            message: GaiCommit::from_response(
+                commit,
+                &cfg.gai.commit_config,
+            )
+            .message(),
+            files: &commit.files,
+            hunk_ids: &commit.hunk_ids,
+        })
+        .collect();
+
+    let output = JsonOutput {
+        version: JSON_OUTPUT_VERSION,
+        commits,
+    };
+
+    println!("{}", serde_json::to_string_pretty(&output)?);
+
+    Ok(())
+}
+
+pub fn json_print_error(error: &anyhow::Error) {
+    let output = JsonError {
+        version: JSON_OUTPUT_VERSION,
+        error: format!("{:#}", error),
+    };
+
+    // can't fail, it's two plain fields
+    if let Ok(json) = serde_json::to_string_pretty(&output) {
+        println!("{}", json);
+    }
+}
@@ -1,211 +1,1903 @@
-use ratatui::Frame;
-use tokio::sync::mpsc;
+use anyhow::{Result, anyhow};
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use git2::Oid;
+use ratatui::{Frame, layout::Position};
+use std::{
+    collections::HashMap, env, fs, path::Path, process::Command,
+    time::Instant,
+};
+use strum::IntoEnumIterator;
+use tokio::{sync::mpsc, task::JoinHandle};
 
 use crate::{
     ai::{
+        cache,
+        models::list_models,
+        provider::Provider,
         request::Request,
-        response::{Response, get_response},
+        response::{
+            CommitMessage, Response, ResponseCommit, ResponseEvent,
+            stream_response,
+        },
     },
     config::Config,
-    git::{commit::GaiCommit, repo::GaiGit},
+    consts::{DIFF_CONTEXT_LINES, PROMPT_REGENERATE, UNDO_HISTORY},
+    git::{
+        commit::GaiCommit,
+        hook,
+        repo::{GaiFile, GaiGit},
+        spelling::SpellChecker,
+        temp_file,
+    },
     tui::{
-        tabs::{SelectedTab, TabContent, TabList},
-        ui::UI,
+        tabs::{Badge, SelectedTab, TabContent, TabList},
+        ui::{
+            ApplySummary, BranchPrompt, ConfirmApply, HookStatus,
+            ModelPicker, ProviderTab, StatusLine,
+        },
+        ui::{FocusedPane, ListPane, UI, UIMode},
     },
 };
 
-pub struct App {
-    pub running: bool,
-    pub state: State,
-    pub cfg: Config,
-    pub gai: GaiGit,
-    pub ui: UI,
+pub struct App {
+    pub running: bool,
+    pub state: State,
+    pub cfg: Config,
+    pub gai: GaiGit,
+    pub ui: UI,
+
+    pub request: Request,
+    pub response: Option<Response>,
+    /// text streamed in so far
+    /// for the in-flight request
+    pub partial_response: String,
+    /// (attempt, max) while retrying
+    pub retry_status: Option<(u32, u32)>,
+    /// index of the commit being regenerated
+    pub regenerating: Option<usize>,
+    /// the file getting a commit of its own
+    pub committing_file: Option<String>,
+    /// (done, total) for split requests
+    pub group_progress: Option<(usize, usize)>,
+    /// (done, total) for large file summaries
+    pub summary_progress: Option<(usize, usize)>,
+    /// finished groups, in the order they came in
+    group_commits: Vec<ResponseCommit>,
+    /// set while a cacheable request is in flight
+    cache_key: Option<String>,
+    pub request_task: Option<JoinHandle<()>>,
+    /// bumped per request, events tagged
+    /// with an older one are stale
+    request_id: u64,
+    /// when the in-flight request was sent
+    request_started: Option<Instant>,
+    pub is_loading: bool,
+    pub applied_commits: bool,
+    /// HEAD from before applying, what
+    /// a failed hook can reset back to
+    applied_from: Option<Oid>,
+    /// what apply_commits created, oldest first
+    applied: Vec<Oid>,
+    /// the applied commits were undone
+    pub reset_applied: bool,
+    /// switched to before applying
+    pub applied_branch: Option<String>,
+    /// shown in the commits tab when
+    /// apply_commits bails out
+    pub apply_error: Option<String>,
+    /// git operations apply_commits would run
+    pub dry_run_output: Option<String>,
+
+    /// the editor needs the terminal, so
+    /// run_tui picks this up after the event
+    pub edit_requested: bool,
+
+    /// kept around since on linux the clipboard
+    /// contents go away with it
+    clipboard: Option<arboard::Clipboard>,
+    /// messages that failed to copy, these get
+    /// printed to stderr once the tui exits
+    pub uncopied: Vec<String>,
+
+    /// removals and truncations in the diffs
+    /// tab, newest last, capped at UNDO_HISTORY
+    history: Vec<FileEdit>,
+
+    /// knows the words in the diffs,
+    /// kept in sync by rebuild_request
+    spelling: SpellChecker,
+}
+
+pub enum State {
+    Running,
+}
+
+/// what undo needs to put a file back
+enum FileEdit {
+    /// where it was in gai.files
+    Removed {
+        pos: usize,
+        file: GaiFile,
+    },
+    Truncated {
+        path: String,
+    },
+}
+
+/// various ui actions
+#[derive(Clone, Copy)]
+pub enum Action {
+    ScrollUp,
+    ScrollDown,
+
+    FocusLeft,
+    FocusRight,
+
+    Enter,
+
+    NextTab,
+    PreviousTab,
+
+    ToggleHelp,
+    ToggleWrap,
+    ToggleWordDiff,
+    ToggleRawView,
+
+    SendRequest,
+    /// bypasses the response cache
+    SendFreshRequest,
+    /// drops the current commits first, then a fresh request
+    RegenerateAll,
+    /// esc while a request is in flight
+    CancelRequest,
+    ApplyCommits,
+    /// applies even when validation fails
+    ForceApplyCommits,
+    /// asks for a branch, switches to it, then applies
+    ApplyToNewBranch,
+    DryRunApply,
+    EditSelected,
+    RemoveCurrentSelected,
+    TruncateCurrentSelected,
+    Undo,
+    ToggleStaged,
+    ToggleUntracked,
+    ToggleHunk,
+    CopySelected,
+    RegenerateSelected,
+    /// one commit for the selected file, applied right away
+    CommitSelectedOnly,
+    // the apply confirmation
+    ConfirmApply,
+    CancelApply,
+    MoveCommitUp,
+    MoveCommitDown,
+    MergeWithNext,
+    // first/last item of a list
+    JumpTop,
+    JumpBottom,
+
+    // filtering the diffs list
+    StartSearch,
+    SearchInput(char),
+    SearchBackspace,
+    ConfirmSearch,
+    ClearSearch,
+
+    // the model picker
+    PickModel,
+    ModelPickerUp,
+    ModelPickerDown,
+    /// for this session, SaveModel writes it to config.toml too
+    ChooseModel,
+    SaveModel,
+    CancelModelPicker,
+
+    // the branch prompt
+    BranchInput(char),
+    BranchBackspace,
+    ConfirmBranch,
+    CancelBranch,
+
+    // the post_apply_hook popup
+    CloseHook,
+    // the summary after applying
+    CopyApplied,
+    ToggleSummaryLog,
+    /// soft resets the applied commits after a failed hook
+    ResetApplied,
+
+    Quit,
+
+    DiffTab,
+    OpenAITab,
+    ClaudeTab,
+    GeminiTab,
+    OllamaTab,
+    MistralTab,
+}
+
+impl App {
+    pub fn new(
+        request: Request,
+        cfg: Config,
+        gai: GaiGit,
+        response: Option<Response>,
+    ) -> Self {
+        let spelling = SpellChecker::from_diffs(
+            gai.get_file_diffs_as_str().values().map(String::as_str),
+        );
+
+        let mut ui = UI::new(cfg.tui.keymap, provider_tabs(&cfg));
+        ui.word_diff = cfg.tui.word_diff;
+
+        Self {
+            running: true,
+            state: State::Running,
+            ui,
+            cfg,
+            gai,
+            request,
+            response,
+            partial_response: String::new(),
+            retry_status: None,
+            regenerating: None,
+            committing_file: None,
+            group_progress: None,
+            summary_progress: None,
+            group_commits: Vec::new(),
+            cache_key: None,
+            request_task: None,
+            request_id: 0,
+            request_started: None,
+            is_loading: false,
+            applied_commits: false,
+            applied_from: None,
+            applied: Vec::new(),
+            reset_applied: false,
+            applied_branch: None,
+            apply_error: None,
+            dry_run_output: None,
+            edit_requested: false,
+            clipboard: None,
+            uncopied: Vec::new(),
+            history: Vec::new(),
+            spelling,
+        }
+    }
+
+    pub fn run(&mut self, frame: &mut Frame) {
+        self.ui.update_compact(frame.area());
+        let tab_list = &self.get_list();
+
+        // untruncating the last one empties the pane,
+        // compact doesn't show it at all
+        if self.ui.list_pane == ListPane::Secondary
+            && (tab_list.secondary.is_none() || self.ui.compact)
+        {
+            self.ui.list_pane = ListPane::Main;
+        }
+
+        // nothing selected, nothing to scroll
+        if self.ui.focused_pane == FocusedPane::Content
+            && self.focused_selected().is_none()
+            && !self.showing_raw()
+        {
+            self.ui.focus_list();
+        }
+
+        let tab_content = &self.get_content();
+
+        if !tab_list.main.is_empty()
+            && self.ui.selected_state.selected().is_none()
+        {
+            self.ui.selected_state.select(Some(0));
+        }
+
+        let status = &self.status_line();
+
+        self.ui.render(
+            frame,
+            tab_content,
+            tab_list,
+            self.is_loading,
+            status,
+        );
+    }
+
+    fn status_line(&self) -> StatusLine {
+        let model = self
+            .cfg
+            .ai
+            .providers
+            .get(&self.cfg.ai.provider)
+            .map(|p| p.model.to_owned())
+            .unwrap_or_default();
+
+        let (added, removed) = self
+            .gai
+            .files
+            .iter()
+            .map(|g| g.line_stats())
+            .fold((0, 0), |(a, r), (added, removed)| {
+                (a + added, r + removed)
+            });
+
+        StatusLine {
+            provider: self.cfg.ai.provider,
+            model,
+            files: self.gai.files.len(),
+            added,
+            removed,
+            applied: self.applied_commits,
+        }
+    }
+
+    /// clicks pick a tab or a list entry,
+    /// the wheel scrolls like j / k
+    pub fn on_mouse(&mut self, mouse: MouseEvent) {
+        // overlays only take keys
+        if self.ui.show_help
+            || self.ui.hook.is_some()
+            || self.ui.confirm_apply.is_some()
+            || self.ui.model_picker.is_some()
+            || self.ui.branch_prompt.is_some()
+        {
+            return;
+        }
+
+        match mouse.kind {
+            MouseEventKind::ScrollUp => self.scroll_up(),
+            MouseEventKind::ScrollDown => self.scroll_down(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                if self
+                    .ui
+                    .header_area
+                    .contains(Position::new(mouse.column, mouse.row))
+                {
+                    if let Some(tab) = self.ui.tab_at(mouse.column) {
+                        self.ui.goto_tab(tab + 1);
+                    }
+                    return;
+                }
+
+                let Some(idx) =
+                    self.ui.list_row_at(mouse.column, mouse.row)
+                else {
+                    return;
+                };
+
+                if idx < self.get_list().main.len() {
+                    self.dismiss_overlays();
+                    self.ui.selected_state.select(Some(idx));
+                    self.ui.list_pane = ListPane::Main;
+                    self.ui.focused_pane = FocusedPane::List;
+                    self.ui.mode = UIMode::TabNavigation;
+                    self.ui.content_scroll = 0;
+                    self.ui.h_scroll = 0;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn on_tick(&mut self) {
+        self.ui.throbber_state.calc_next();
+        self.ui.clear_expired_notice();
+    }
+
+    /// fresh skips the cache lookup, the
+    /// response still gets cached afterwards
+    pub async fn send_request(
+        &mut self,
+        tx: mpsc::Sender<ResponseEvent>,
+        fresh: bool,
+    ) {
+        if self.is_loading {
+            return;
+        }
+
+        // an empty prompt only gets made up commits
+        if !self.gai.has_changes() {
+            self.ui.notify("No changes to commit");
+            return;
+        }
+
+        // before the provider gets to complain about it
+        let truncated = self.gai.auto_truncate(
+            self.cfg.ai.max_diff_chars,
+            self.cfg.gai.summarize_over(),
+        );
+        if !truncated.is_empty() {
+            self.ui.notify(format!(
+                "Auto-truncated to fit ai.max_diff_chars: {}",
+                truncated.join(", ")
+            ));
+
+            for path in truncated {
+                self.push_history(FileEdit::Truncated { path });
+            }
+        }
+
+        self.rebuild_request();
+        self.regenerating = None;
+        self.committing_file = None;
+
+        let key = self.cache_key();
+        self.cache_key = Some(key.to_owned());
+
+        if !fresh
+            && let Some(resp) =
+                cache::load(&key, self.cfg.ai.cache_ttl_secs)
+        {
+            self.cache_key = None;
+            self.display_response(resp);
+            self.ui.notify("Loaded a cached response, P to refresh");
+            return;
+        }
+
+        // the whole reason is on the commits tab
+        if let Some(over) = self.request.tokens_over(&self.cfg.ai) {
+            self.cache_key = None;
+            self.ui.notify(format!(
+                "The prompt is ~{} tokens over the context_window, \
+                 t to truncate files",
+                over
+            ));
+            return;
+        }
+
+        // inexpensive clone?
+        let req = self.request.clone();
+        self.spawn_request(req, tx);
+    }
+
+    /// a fresh take on everything, the old commits
+    /// go away right away instead of once it's back
+    pub async fn regenerate_all(
+        &mut self,
+        tx: mpsc::Sender<ResponseEvent>,
+    ) {
+        if self.is_loading {
+            return;
+        }
+
+        self.response = None;
+        self.ui.selected_state.select_first();
+        self.ui.content_scroll = 0;
+
+        self.send_request(tx, true).await;
+    }
+
+    /// re-prompts with only the selected commit's files,
+    /// the result replaces just that commit
+    pub fn regenerate_selected(
+        &mut self,
+        tx: mpsc::Sender<ResponseEvent>,
+    ) {
+        if self.is_loading
+            || !matches!(self.ui.selected_tab, SelectedTab::Commits)
+        {
+            return;
+        }
+
+        let Some(selected) = self.ui.selected_state.selected() else {
+            return;
+        };
+
+        let Some(Response {
+            result: Ok(res), ..
+        }) = &self.response
+        else {
+            return;
+        };
+
+        let Some(commit) = res.commits.get(selected) else {
+            return;
+        };
+
+        let hunk_files = commit.hunk_ids.iter().filter_map(|id| {
+            id.split_once(':').map(|(path, _)| path)
+        });
+        let files: Vec<&str> = commit
+            .files
+            .iter()
+            .map(String::as_str)
+            .chain(hunk_files)
+            .collect();
+
+        let mut diffs: HashMap<String, String> = self
+            .gai
+            .get_file_diffs_as_str()
+            .into_iter()
+            .filter(|(path, _)| files.contains(&path.as_str()))
+            .collect();
+
+        if diffs.is_empty() {
+            self.ui.notify("No diffs left for this commit");
+            return;
+        }
+
+        let mut req = Request::default();
+        req.build_prompt(&self.cfg, &self.gai);
+        req.prompt.push_str(PROMPT_REGENERATE);
+        req.hold_large_files(&self.cfg.gai, &mut diffs);
+        req.build_diffs_string(diffs);
+
+        self.regenerating = Some(selected);
+        self.spawn_request(req, tx);
+    }
+
+    /// asks for a single commit of the selected file, it's
+    /// applied as soon as it's back. the commits tab is
+    /// left alone
+    pub fn commit_selected_only(
+        &mut self,
+        tx: mpsc::Sender<ResponseEvent>,
+    ) {
+        if self.is_loading || !self.in_diffs() {
+            return;
+        }
+
+        if self.gai.from_patch {
+            self.ui.notify(
+                "The diffs came from a patch, there's nothing to commit",
+            );
+            return;
+        }
+
+        let Some(path) = self.focused_path() else {
+            return;
+        };
+
+        let mut diffs: HashMap<String, String> = self
+            .gai
+            .get_file_diffs_as_str()
+            .into_iter()
+            .filter(|(p, _)| *p == path)
+            .collect();
+
+        if diffs.is_empty() {
+            self.ui.notify(format!(
+                "Nothing left to commit in {}",
+                path
+            ));
+            return;
+        }
+
+        let mut req = Request::default();
+        req.build_prompt(&self.cfg, &self.gai);
+        req.prompt.push_str(PROMPT_REGENERATE);
+        req.hold_large_files(&self.cfg.gai, &mut diffs);
+        req.build_diffs_string(diffs);
+
+        self.ui.notify(format!("Writing a commit for {}", path));
+        self.committing_file = Some(path);
+        self.spawn_request(req, tx);
+    }
+
+    fn spawn_request(
+        &mut self,
+        req: Request,
+        tx: mpsc::Sender<ResponseEvent>,
+    ) {
+        let ai = self.cfg.ai.clone();
+
+        self.is_loading = true;
+        self.request_started = Some(Instant::now());
+        self.partial_response.clear();
+        self.retry_status = None;
+        self.group_progress =
+            (!req.groups.is_empty()).then_some((0, req.groups.len()));
+        self.summary_progress = (!req.large_files.is_empty())
+            .then_some((0, req.large_files.len()));
+        self.group_commits.clear();
+        self.apply_error = None;
+        self.dry_run_output = None;
+
+        self.request_id += 1;
+        let id = self.request_id;
+
+        self.request_task = Some(tokio::spawn(async move {
+            let (request_tx, mut request_rx) = mpsc::channel(32);
+
+            let forward = async {
+                while let Some(event) = request_rx.recv().await {
+                    let event = ResponseEvent::Request {
+                        id,
+                        event: Box::new(event),
+                    };
+                    let _ = tx.send(event).await;
+                }
+            };
+
+            tokio::join!(
+                stream_response(&req, ai, request_tx),
+                forward
+            );
+        }));
+    }
+
+    /// drops the in-flight request, anything it already
+    /// queued still carries the old id and is ignored
+    /// once it comes through
+    pub fn cancel_request(&mut self) {
+        let Some(task) = self.request_task.take() else {
+            return;
+        };
+        task.abort();
+        self.request_id += 1;
+
+        self.is_loading = false;
+        self.regenerating = None;
+        self.committing_file = None;
+        self.cache_key = None;
+        self.request_started = None;
+        self.partial_response.clear();
+        self.retry_status = None;
+        self.group_progress = None;
+        self.summary_progress = None;
+        self.group_commits.clear();
+
+        self.ui.notify("Request cancelled");
+    }
+
+    /// keeps the request (and its token estimate)
+    /// in sync with whatever is in the diffs tab
+    pub fn rebuild_request(&mut self) {
+        let mut req = Request::default();
+        req.build_prompt(&self.cfg, &self.gai);
+        let mut diffs = self.gai.get_file_diffs_as_str();
+        self.spelling = SpellChecker::from_diffs(
+            diffs.values().map(String::as_str),
+        );
+        req.hold_large_files(&self.cfg.gai, &mut diffs);
+        req.split(self.cfg.gai.split_strategy, &diffs);
+        req.build_diffs_string(diffs);
+        self.request = req;
+    }
+
+    /// stops the app along with any
+    /// request that's still streaming in
+    pub fn quit(&mut self) {
+        if let Some(task) = self.request_task.take() {
+            task.abort();
+        }
+
+        self.running = false;
+    }
+
+    /// since the request went out, retries included
+    fn elapsed_secs(&self) -> u64 {
+        self.request_started
+            .map(|started| started.elapsed().as_secs())
+            .unwrap_or_default()
+    }
+
+    pub fn on_response_event(&mut self, event: ResponseEvent) {
+        match event {
+            ResponseEvent::Partial(text) => {
+                self.partial_response.push_str(&text);
+            }
+            ResponseEvent::Retry { attempt, max } => {
+                self.partial_response.clear();
+                self.retry_status = Some((attempt, max));
+            }
+            ResponseEvent::Fallback { .. } => {
+                self.partial_response.clear();
+                self.retry_status = None;
+            }
+            ResponseEvent::Reformat => {
+                self.partial_response.clear();
+                self.ui.notify(
+                    "The response didn't parse, asking for only the JSON",
+                );
+            }
+            ResponseEvent::Summarized { done, total } => {
+                self.summary_progress = Some((done, total));
+            }
+            ResponseEvent::GroupDone {
+                done,
+                total,
+                commit,
+            } => {
+                self.group_progress = Some((done, total));
+                self.group_commits.extend(commit);
+            }
+            ResponseEvent::Done(resp) => self.display_response(resp),
+            ResponseEvent::Models { provider, models } => {
+                match models {
+                    Ok(models) => {
+                        let current = self
+                            .cfg
+                            .ai
+                            .providers
+                            .get(&provider)
+                            .map(|p| p.model.as_str())
+                            .unwrap_or_default();
+
+                        self.ui.model_picker =
+                            Some(ModelPicker::new(
+                                provider, models, current,
+                            ));
+                    }
+                    Err(e) => self.ui.notify(e),
+                }
+            }
+            ResponseEvent::Hook(run) => {
+                if let Some(hook) = &mut self.ui.hook {
+                    hook.run = Some(run);
+                }
+            }
+            ResponseEvent::Request { id, event } => {
+                // a Done from before esc would otherwise
+                // replace the cancelled notice
+                if self.is_loading && id == self.request_id {
+                    self.on_response_event(*event);
+                }
+            }
+        }
+    }
+
+    /// asks the active provider for its models,
+    /// the picker opens once they come back
+    pub fn pick_model(&mut self, tx: mpsc::Sender<ResponseEvent>) {
+        let provider = self.cfg.ai.provider;
+        let Some(provider_cfg) =
+            self.cfg.ai.providers.get(&provider).cloned()
+        else {
+            self.ui.notify(format!("{} has no config", provider));
+            return;
+        };
+
+        self.ui.notify(format!("Listing {} models...", provider));
+
+        tokio::spawn(async move {
+            let models = list_models(provider, &provider_cfg)
+                .await
+                .map_err(|e| format!("{:#}", e));
+
+            let _ = tx
+                .send(ResponseEvent::Models { provider, models })
+                .await;
+        });
+    }
+
+    /// swaps the model for the rest of the session,
+    /// save also writes it back to config.toml
+    pub fn choose_model(&mut self, save: bool) {
+        let Some(picker) = self.ui.model_picker.take() else {
+            return;
+        };
+
+        let Some(model) = picker.selected() else {
+            return;
+        };
+
+        if let Some(provider_cfg) =
+            self.cfg.ai.providers.get_mut(&picker.provider)
+        {
+            provider_cfg.model = model.to_owned();
+        }
+
+        if !save {
+            self.ui.notify(format!("Using {}", model));
+            return;
+        }
+
+        match Config::save_model(picker.provider, model) {
+            Ok(path) => self.ui.notify(format!(
+                "Saved {} to {}",
+                model,
+                path.display()
+            )),
+            Err(e) => self
+                .ui
+                .notify(format!("Using {}, not saved: {}", model, e)),
+        }
+    }
+
+    /// provider tabs swap out the active provider
+    /// and jump to the commits tab
+    pub fn switch_provider(&mut self, provider: Provider) {
+        self.cfg.ai.provider = provider;
+        self.ui.goto_tab(SelectedTab::Commits as usize + 1);
+    }
+
+    fn cache_key(&self) -> String {
+        let provider_cfg =
+            self.cfg.ai.providers.get(&self.cfg.ai.provider);
+        let model = provider_cfg
+            .map(|p| p.model.as_str())
+            .unwrap_or_default();
+        let system_prompt =
+            provider_cfg.and_then(|p| p.system_prompt.as_deref());
+
+        cache::cache_key(
+            &self.request,
+            self.cfg.ai.provider,
+            model,
+            system_prompt,
+        )
+    }
+
+    pub fn display_response(&mut self, resp: Response) {
+        if let Some(key) = self.cache_key.take() {
+            // not worth interrupting anything over
+            let _ =
+                cache::store(&key, &resp, self.cfg.ai.cache_ttl_secs);
+        }
+
+        if let Some(path) = self.committing_file.take() {
+            self.commit_file(&path, resp);
+        } else {
+            match self.regenerating.take() {
+                Some(idx) => self.replace_commit(idx, resp),
+                None => self.response = Some(resp),
+            }
+        }
+
+        self.is_loading = false;
+        self.request_task = None;
+        self.request_started = None;
+        self.partial_response.clear();
+        self.retry_status = None;
+        self.group_progress = None;
+        self.summary_progress = None;
+        self.group_commits.clear();
+    }
+
+    /// swaps in the regenerated commit, a failed
+    /// regeneration keeps the old one around
+    fn replace_commit(&mut self, idx: usize, resp: Response) {
+        let new = match resp.result {
+            Ok(res) => res.commits,
+            Err(e) => {
+                self.ui.notify(format!("Regenerating failed: {}", e));
+                return;
+            }
+        };
+
+        let Some(Response {
+            result: Ok(res), ..
+        }) = &mut self.response
+        else {
+            return;
+        };
+
+        let count = new.len();
+        let Some(commit) = new.into_iter().next() else {
+            self.ui.notify("No commit came back, kept the old one");
+            return;
+        };
+
+        if let Some(old) = res.commits.get_mut(idx) {
+            *old = commit;
+        }
+
+        if count > 1 {
+            self.ui.notify(format!(
+                "Got {} commits back, kept the first",
+                count
+            ));
+        }
+    }
+
+    /// applies the first commit that came back with only
+    /// path in it, then reloads the diffs without it
+    fn commit_file(&mut self, path: &str, resp: Response) {
+        let commit = match resp.result {
+            Ok(res) => res.commits.into_iter().next(),
+            Err(e) => {
+                self.ui.notify(format!(
+                    "Committing {} failed: {}",
+                    path, e
+                ));
+                return;
+            }
+        };
+
+        let Some(commit) = commit else {
+            self.ui.notify("No commit came back");
+            return;
+        };
+
+        let commit_cfg = &self.cfg.gai.commit_config;
+        let reasons = commit.validate(
+            &self.cfg.gai.allowed_prefixes(),
+            self.cfg.ai.rules.max_header_length,
+        );
+        let commit = GaiCommit::from_response(&commit, commit_cfg);
+
+        if !reasons.is_empty() {
+            self.apply_error = Some(format!(
+                "Didn't commit {}, \"{}\" is invalid: {}",
+                path,
+                commit.header,
+                reasons.join(", ")
+            ));
+            return;
+        }
+
+        match self.gai.apply_file_commit(&commit, path) {
+            Ok(oid) => {
+                self.applied_commits = true;
+                self.ui.notify(format!(
+                    "Committed {} as {}",
+                    path,
+                    &oid.to_string()[..7]
+                ));
+            }
+            Err(e) => {
+                self.apply_error = Some(format!("{:#}", e));
+                return;
+            }
+        }
+
+        if self
+            .gai
+            .create_diffs(&self.cfg.ai.files_to_truncate)
+            .is_ok()
+        {
+            // the files it points at may be gone
+            self.history.clear();
+            self.ui.selected_state.select_first();
+            self.ui.content_scroll = 0;
+            self.ui.selected_hunk = 0;
+        }
+        self.rebuild_request();
+    }
+
+    fn response_commits_mut(
+        &mut self,
+    ) -> Option<&mut Vec<ResponseCommit>> {
+        match &mut self.response {
+            Some(Response {
+                result: Ok(res), ..
+            }) => Some(&mut res.commits),
+            _ => None,
+        }
+    }
+
+    /// moves the selected commit by offset (-1 up, 1 down),
+    /// apply_commits goes in list order
+    pub fn move_selected(&mut self, offset: isize) {
+        if self.is_loading
+            || !matches!(self.ui.selected_tab, SelectedTab::Commits)
+        {
+            return;
+        }
+
+        let Some(selected) = self.ui.selected_state.selected() else {
+            return;
+        };
+        let Some(commits) = self.response_commits_mut() else {
+            return;
+        };
+
+        let Some(target) = selected.checked_add_signed(offset) else {
+            return;
+        };
+        if selected >= commits.len() || target >= commits.len() {
+            return;
+        }
+
+        commits.swap(selected, target);
+        self.ui.selected_state.select(Some(target));
+        self.dry_run_output = None;
+    }
+
+    /// combines the selected commit with the one after it,
+    /// r can then regenerate the combined message
+    pub fn merge_with_next(&mut self) {
+        if self.is_loading
+            || !matches!(self.ui.selected_tab, SelectedTab::Commits)
+        {
+            return;
+        }
+
+        let Some(selected) = self.ui.selected_state.selected() else {
+            return;
+        };
+        let Some(commits) = self.response_commits_mut() else {
+            return;
+        };
+
+        if selected + 1 >= commits.len() {
+            self.ui.notify("Nothing after this commit to merge with");
+            return;
+        }
+
+        let next = commits.remove(selected + 1);
+        commits[selected].merge(next);
+
+        self.dry_run_output = None;
+        self.ui.notify("Merged, press r to regenerate the message");
+    }
+
+    /// (index, reasons) for every commit
+    /// that fails validation
+    fn invalid_commits(&self) -> Vec<(usize, Vec<String>)> {
+        let Some(Response {
+            result: Ok(res), ..
+        }) = &self.response
+        else {
+            return Vec::new();
+        };
+
+        let allowed_prefixes = self.cfg.gai.allowed_prefixes();
+
+        res.commits
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                (
+                    i,
+                    c.validate(
+                        &allowed_prefixes,
+                        self.cfg.ai.rules.max_header_length,
+                    ),
+                )
+            })
+            .filter(|(_, reasons)| !reasons.is_empty())
+            .collect()
+    }
+
+    /// shows what applying would do
+    /// in place of the selected commit
+    pub fn dry_run(&mut self) {
+        if !matches!(self.ui.selected_tab, SelectedTab::Commits) {
+            return;
+        }
+
+        let commits = self.response_commits();
+        if commits.is_empty() {
+            return;
+        }
+
+        if self.gai.from_patch {
+            self.ui.notify(
+                "Nothing to dry run, the diffs came from a patch",
+            );
+            return;
+        }
+
+        self.dry_run_output = Some(self.gai.dry_run(&commits));
+        self.ui.content_scroll = 0;
+    }
+
+    fn response_commits(&self) -> Vec<GaiCommit> {
+        let Some(Response {
+            result: Ok(res), ..
+        }) = &self.response
+        else {
+            return Vec::new();
+        };
+
+        res.commits
+            .iter()
+            .map(|response_commit| {
+                GaiCommit::from_response(
+                    response_commit,
+                    &self.cfg.gai.commit_config,
+                )
+            })
+            .collect()
+    }
+
+    /// refuses invalid commits unless forced,
+    /// returns how many were applied
+    pub fn apply_commits(&mut self, force: bool) -> Result<usize> {
+        match self.ui.selected_tab {
+            SelectedTab::Diffs => Ok(0),
+            _ => {
+                self.check_valid(force)?;
+
+                let commits = self.response_commits();
+                if commits.is_empty() {
+                    return Ok(0);
+                }
+
+                self.applied_from = self.gai.head_oid();
+                self.applied = self.gai.apply_commits(&commits)?;
+
+                Ok(self.applied.len())
+            }
+        }
+    }
+
+    /// everything is applied (and the hook is done),
+    /// shows what landed unless tui.apply_summary is off
+    pub fn finish_apply(&mut self) {
+        // commits from before these, for context
+        const LOG_CONTEXT: usize = 10;
+
+        if !self.cfg.tui.apply_summary || self.applied.is_empty() {
+            self.quit();
+            return;
+        }
+
+        let short = |oid: &Oid| oid.to_string()[..7].to_owned();
+        let log =
+            self.gai.log_oneline(self.applied.len() + LOG_CONTEXT);
+
+        let commits = self
+            .applied
+            .iter()
+            .map(|oid| {
+                let subject = log
+                    .iter()
+                    .find(|(o, _)| o == oid)
+                    .map(|(_, s)| s.to_owned())
+                    .unwrap_or_default();
+                (short(oid), subject)
+            })
+            .collect();
+
+        self.ui.summary = Some(ApplySummary {
+            commits,
+            log: log
+                .iter()
+                .map(|(oid, subject)| {
+                    (
+                        short(oid),
+                        subject.to_owned(),
+                        self.applied.contains(oid),
+                    )
+                })
+                .collect(),
+            show_log: false,
+            branch: self.applied_branch.clone(),
+        });
+    }
+
+    /// the full hashes, one per line
+    pub fn copy_applied(&mut self) {
+        let hashes: String = self
+            .applied
+            .iter()
+            .map(|oid| format!("{}\n", oid))
+            .collect();
+
+        self.copy(hashes, "the hashes");
+    }
+
+    /// starts the post_apply_hook in the background,
+    /// false when there's none and we can quit
+    pub fn run_post_apply_hook(
+        &mut self,
+        applied: usize,
+        tx: mpsc::Sender<ResponseEvent>,
+    ) -> bool {
+        let Some(command) =
+            self.cfg.gai.hook_command().map(str::to_owned)
+        else {
+            return false;
+        };
+
+        if applied == 0 {
+            return false;
+        }
+
+        let dir = match self.gai.workdir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                self.apply_error = Some(format!("{:#}", e));
+                return false;
+            }
+        };
+
+        self.ui.hook = Some(HookStatus {
+            command: command.to_owned(),
+            started: Instant::now(),
+            applied,
+            can_reset: self.applied_from.is_some(),
+            run: None,
+        });
+
+        tokio::spawn(async move {
+            let run = tokio::task::spawn_blocking(move || {
+                hook::run(&command, &dir, true)
+            })
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|run| run)
+            .map_err(|e| format!("{:#}", e));
+
+            let _ = tx.send(ResponseEvent::Hook(run)).await;
+        });
+
+        true
+    }
+
+    /// git reset --soft to before the apply, then quits
+    pub fn reset_applied(&mut self) {
+        let Some(oid) = self.applied_from else {
+            return;
+        };
+
+        match self.gai.soft_reset(oid) {
+            Ok(()) => {
+                self.applied_commits = false;
+                self.reset_applied = true;
+                self.quit();
+            }
+            Err(e) => self.ui.notify(format!("{:#}", e)),
+        }
+    }
+
+    fn check_valid(&self, force: bool) -> Result<()> {
+        // not something force gets past
+        if self.gai.from_patch {
+            return Err(anyhow!(
+                "The diffs came from a patch, there's nothing to apply them to. y copies a message"
+            ));
+        }
+
+        let invalid = self.invalid_commits();
+        if force || invalid.is_empty() {
+            return Ok(());
+        }
+
+        let reasons: Vec<String> = invalid
+            .iter()
+            .map(|(i, reasons)| {
+                format!("Commit {}: {}", i + 1, reasons.join(", "))
+            })
+            .collect();
+
+        Err(anyhow!(
+            "Some commits are invalid:\n{}\n\nPress X to apply anyway",
+            reasons.join("\n")
+        ))
+    }
+
+    /// shows what's about to be written, y applies,
+    /// returns false when there's nothing to confirm
+    pub fn request_apply(&mut self, force: bool) -> bool {
+        if !matches!(self.ui.selected_tab, SelectedTab::Commits) {
+            return false;
+        }
+
+        if let Err(e) = self.check_valid(force) {
+            self.apply_error = Some(format!("{:#}", e));
+            return false;
+        }
+
+        let commits = self.response_commits();
+        if commits.is_empty() {
+            return false;
+        }
+
+        let mut files: Vec<String> = Vec::new();
+        for commit in &commits {
+            let hunk_files =
+                commit.hunk_ids.iter().filter_map(|id| {
+                    id.split_once(':')
+                        .map(|(path, _)| path.to_owned())
+                });
+
+            for file in commit.files.iter().cloned().chain(hunk_files)
+            {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+        // committed along with the rest, just never described
+        for file in &self.gai.ignored {
+            if !files.contains(file) {
+                files.push(file.to_owned());
+            }
+        }
+
+        self.ui.confirm_apply = Some(ConfirmApply {
+            force,
+            subjects: commits.into_iter().map(|c| c.header).collect(),
+            files,
+            hook: self.cfg.gai.hook_command().map(str::to_owned),
+        });
+
+        true
+    }
+
+    /// asks for the branch to apply onto, the
+    /// prompt stands in for the apply confirmation
+    pub fn request_branch_apply(&mut self) {
+        if !matches!(self.ui.selected_tab, SelectedTab::Commits) {
+            return;
+        }
+
+        if let Err(e) = self.check_valid(false) {
+            self.apply_error = Some(format!("{:#}", e));
+            return;
+        }
+
+        let commits = self.response_commits().len();
+        if commits == 0 {
+            return;
+        }
+
+        self.ui.branch_prompt = Some(BranchPrompt {
+            commits,
+            ..Default::default()
+        });
+    }
+
+    pub fn branch_input(&mut self, c: Option<char>) {
+        let Some(prompt) = &mut self.ui.branch_prompt else {
+            return;
+        };
+
+        match c {
+            Some(c) => prompt.name.push(c),
+            None => {
+                prompt.name.pop();
+            }
+        }
+
+        prompt.exists = false;
+        prompt.error = None;
+    }
+
+    /// creates and switches to the typed branch, an
+    /// existing one is only checked out on a second
+    /// enter, true once we're on it and can apply
+    pub fn switch_branch(&mut self) -> bool {
+        let Some(prompt) = &mut self.ui.branch_prompt else {
+            return false;
+        };
+
+        let name = prompt.name.trim().to_owned();
+        if name.is_empty() {
+            prompt.error = Some("Type a branch name".to_owned());
+            return false;
+        }
+
+        let switched = if self.gai.branch_exists(&name) {
+            if !prompt.exists {
+                prompt.exists = true;
+                return false;
+            }
+
+            self.gai.checkout_branch(&name)
+        } else {
+            self.gai.switch_to_new_branch(&name)
+        };
+
+        match switched {
+            Ok(()) => {
+                self.ui.branch_prompt = None;
+                self.applied_branch = Some(name);
+                true
+            }
+            Err(e) => {
+                prompt.error = Some(format!("{:#}", e));
+                false
+            }
+        }
+    }
+
+    /// opens the selected commit in $EDITOR and
+    /// stores the edit back in the response
+    /// expects the terminal to already be restored
+    pub fn edit_selected(&mut self) -> Result<()> {
+        if !matches!(self.ui.selected_tab, SelectedTab::Commits) {
+            return Ok(());
+        }
+
+        let Some(selected) = self.ui.selected_state.selected() else {
+            return Ok(());
+        };
+
+        let Some(Response {
+            result: Ok(res), ..
+        }) = &mut self.response
+        else {
+            return Ok(());
+        };
+
+        let Some(commit) = res.commits.get_mut(selected) else {
+            return Ok(());
+        };
+
+        let current = GaiCommit::from_response(
+            commit,
+            &self.cfg.gai.commit_config,
+        )
+        .message();
+
+        let edited = open_in_editor(&current)?;
+        let edited = edited.trim();
+
+        if edited.is_empty() || edited == current.trim() {
+            return Ok(());
+        }
+
+        match CommitMessage::parse(edited) {
+            Some(message) => commit.message = message,
+            None => {
+                // keep the edit instead of throwing it away
+                commit.message.edited = Some(edited.to_owned());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// copies the full message of the selected commit
+    /// headless systems get it on stderr after exit instead
+    pub fn copy_selected(&mut self) {
+        if !matches!(self.ui.selected_tab, SelectedTab::Commits) {
+            return;
+        }
+
+        let Some(selected) = self.ui.selected_state.selected() else {
+            return;
+        };
+
+        let Some(Response {
+            result: Ok(res), ..
+        }) = &self.response
+        else {
+            return;
+        };
+
+        let Some(commit) = res.commits.get(selected) else {
+            return;
+        };
+
+        let message = GaiCommit::from_response(
+            commit,
+            &self.cfg.gai.commit_config,
+        )
+        .message();
+
+        self.copy(message, "the message");
+    }
+
+    /// what couldn't go on the clipboard
+    /// is printed on exit instead
+    fn copy(&mut self, text: String, what: &str) {
+        if self.clipboard.is_none() {
+            self.clipboard = arboard::Clipboard::new().ok();
+        }
+
+        let copied = self
+            .clipboard
+            .as_mut()
+            .is_some_and(|c| c.set_text(text.as_str()).is_ok());
+
+        if copied {
+            self.ui.notify("Copied!");
+        } else {
+            self.uncopied.push(text);
+            self.ui.notify(format!(
+                "Clipboard unavailable, {} will be printed on exit",
+                what
+            ));
+        }
+    }
+
+    pub fn remove_selected(&mut self) {
+        if let SelectedTab::Diffs = self.ui.selected_tab
+            && let Some(selected_file) = self.focused_path()
+            && let Some(pos) = self
+                .gai
+                .files
+                .iter()
+                .position(|g| g.path == selected_file)
+        {
+            let file = self.gai.files.remove(pos);
+            self.push_history(FileEdit::Removed { pos, file });
+            self.rebuild_request();
+        }
+    }
+
+    pub fn truncate_selected(&mut self) {
+        // the list can be filtered, so go by path
+        if let SelectedTab::Diffs = self.ui.selected_tab
+            && let Some(path) = self.focused_path()
+            && let Some(file) =
+                self.gai.files.iter_mut().find(|g| g.path == path)
+        {
+            file.should_truncate = !file.should_truncate;
+            self.push_history(FileEdit::Truncated { path });
+            self.rebuild_request();
+        }
+    }
+
+    fn push_history(&mut self, edit: FileEdit) {
+        if self.history.len() >= UNDO_HISTORY {
+            self.history.remove(0);
+        }
+        self.history.push(edit);
+    }
+
+    /// reverts the last removal or truncation
+    pub fn undo(&mut self) {
+        let Some(edit) = self.history.pop() else {
+            self.ui.notify("Nothing to undo");
+            return;
+        };
+
+        match edit {
+            FileEdit::Removed { pos, file } => {
+                self.ui.notify(format!("Restored {}", file.path));
+                let pos = pos.min(self.gai.files.len());
+                self.gai.files.insert(pos, file);
+            }
+            FileEdit::Truncated { path } => {
+                if let Some(file) =
+                    self.gai.files.iter_mut().find(|g| g.path == path)
+                {
+                    file.should_truncate = !file.should_truncate;
+                }
+                self.ui.notify(format!("Undid truncate of {}", path));
+            }
+        }
+
+        self.rebuild_request();
+    }
+
+    /// swap between the staged (index vs HEAD)
+    /// and working tree views, then rebuild the diffs
+    pub fn toggle_staged(&mut self) {
+        if !matches!(self.ui.selected_tab, SelectedTab::Diffs) {
+            return;
+        }
+
+        // the patch is all there is
+        if self.gai.from_patch {
+            self.ui.notify("The diffs came from a patch");
+            return;
+        }
+
+        let only_staged = !self.gai.only_staged;
+        self.gai.only_staged = only_staged;
+        // build_prompt reads it from here
+        self.cfg.gai.only_staged = only_staged;
+
+        if let Err(e) =
+            self.gai.create_diffs(&self.cfg.ai.files_to_truncate)
+        {
+            self.ui.notify(format!("Couldn't get the diffs: {}", e));
+            return;
+        }
+
+        // the files it points at are gone
+        self.history.clear();
+        self.ui.selected_state.select_first();
+        self.ui.content_scroll = 0;
+        self.ui.selected_hunk = 0;
+    }
 
-    pub request: Request,
-    pub response: Option<Response>,
-    pub is_loading: bool,
-    pub applied_commits: bool,
-}
+    /// untracked files in or out of the diffs,
+    /// staged diffs never have them
+    pub fn toggle_untracked(&mut self) {
+        if !matches!(self.ui.selected_tab, SelectedTab::Diffs)
+            || self.gai.only_staged
+        {
+            return;
+        }
 
-pub enum State {
-    Running,
-}
+        if self.gai.from_patch {
+            self.ui.notify("The diffs came from a patch");
+            return;
+        }
 
-/// various ui actions
-pub enum Action {
-    ScrollUp,
-    ScrollDown,
+        self.gai.include_untracked = !self.gai.include_untracked;
+        self.cfg.ai.include_untracked = self.gai.include_untracked;
 
-    FocusLeft,
-    FocusRight,
+        if let Err(e) =
+            self.gai.create_diffs(&self.cfg.ai.files_to_truncate)
+        {
+            self.ui.notify(format!("Couldn't get the diffs: {}", e));
+            return;
+        }
 
-    Enter,
+        self.history.clear();
+        self.ui.selected_state.select_first();
+        self.ui.content_scroll = 0;
+        self.ui.selected_hunk = 0;
 
-    NextTab,
-    PreviousTab, // shift+tab(?)
+        self.ui.notify(if self.gai.include_untracked {
+            "Including untracked files"
+        } else {
+            "Leaving out untracked files"
+        });
+    }
 
-    SendRequest,
-    ApplyCommits,
-    RemoveCurrentSelected,
-    TruncateCurrentSelected,
+    /// index into gai.files of the file
+    /// entered with Enter in the diffs tab
+    fn entered_file(&self) -> Option<usize> {
+        if !matches!(self.ui.selected_tab, SelectedTab::Diffs)
+            || !matches!(self.ui.mode, UIMode::Content)
+        {
+            return None;
+        }
 
-    Quit,
+        let path = self.focused_path()?;
 
-    DiffTab,
-    OpenAITab,
-    ClaudeTab,
-    GeminiTab,
-}
+        self.gai.files.iter().position(|f| f.path == path)
+    }
 
-impl App {
-    pub fn new(
-        request: Request,
-        cfg: Config,
-        gai: GaiGit,
-        response: Option<Response>,
-    ) -> Self {
-        Self {
-            running: true,
-            state: State::Running,
-            cfg,
-            gai,
-            ui: UI::new(),
-            request,
-            response,
-            is_loading: false,
-            applied_commits: false,
+    /// moving through the list dismisses
+    /// the apply error and dry run output
+    fn dismiss_overlays(&mut self) {
+        if matches!(self.ui.mode, UIMode::TabNavigation) {
+            self.apply_error = None;
+            self.dry_run_output = None;
         }
     }
 
-    pub fn run(&mut self, frame: &mut Frame) {
-        let tab_list = &self.get_list();
-        let tab_content = &self.get_content();
+    /// inside an entered diff j/k move
+    /// between hunks instead of scrolling
+    pub fn scroll_up(&mut self) {
+        self.dismiss_overlays();
 
-        if !tab_list.main.is_empty()
-            && self.ui.selected_state.selected().is_none()
-        {
-            self.ui.selected_state.select(Some(0));
+        if self.entered_file().is_some() {
+            self.ui.selected_hunk =
+                self.ui.selected_hunk.saturating_sub(1);
+        } else {
+            self.ui.scroll_up();
         }
+    }
 
-        self.ui
-            .render(frame, tab_content, tab_list, self.is_loading);
+    pub fn scroll_down(&mut self) {
+        self.dismiss_overlays();
+
+        match self.entered_file() {
+            Some(idx) => {
+                let last =
+                    self.gai.files[idx].hunks.len().saturating_sub(1);
+                self.ui.selected_hunk =
+                    (self.ui.selected_hunk + 1).min(last);
+            }
+            None => self.ui.scroll_down(),
+        }
     }
 
-    pub fn on_tick(&mut self) {
-        self.ui.throbber_state.calc_next();
+    pub fn jump_top(&mut self) {
+        self.dismiss_overlays();
+
+        if self.entered_file().is_some() {
+            self.ui.selected_hunk = 0;
+        } else {
+            self.ui.jump_top();
+        }
     }
 
-    pub async fn send_request(&mut self, tx: mpsc::Sender<Response>) {
-        if self.is_loading {
+    pub fn jump_bottom(&mut self) {
+        self.dismiss_overlays();
+
+        match self.entered_file() {
+            Some(idx) => {
+                self.ui.selected_hunk =
+                    self.gai.files[idx].hunks.len().saturating_sub(1);
+            }
+            None => self.ui.jump_bottom(),
+        }
+    }
+
+    /// excluded hunks are left out of
+    /// the request and the applied commits
+    pub fn toggle_hunk(&mut self) {
+        let Some(idx) = self.entered_file() else {
             return;
+        };
+
+        if let Some(hunk) =
+            self.gai.files[idx].hunks.get_mut(self.ui.selected_hunk)
+        {
+            hunk.excluded = !hunk.excluded;
+            self.rebuild_request();
         }
+    }
 
-        let ai = &self.cfg.ai;
-        let provider = ai.provider;
-        let provider_cfg = ai
-            .providers
-            .get(&provider)
-            .expect("somehow did not find provider config")
-            .clone();
+    /// files in the main diffs list, the list
+    /// entries are annotated so go by these for paths
+    fn listed_files(&self) -> Vec<&GaiFile> {
+        self.gai
+            .files
+            .iter()
+            .filter(|g| {
+                !g.should_truncate && self.matches_search(&g.path)
+            })
+            .collect()
+    }
 
-        // inexpensive clone?
-        self.is_loading = true;
+    /// sent as a summary from the summarize_model
+    /// instead of the whole diff
+    fn is_summarized(&self, path: &str) -> bool {
+        self.request.large_files.iter().any(|f| f.path == path)
+    }
 
-        let mut req = Request::default();
-        req.build_prompt(&self.cfg, &self.gai);
-        req.build_diffs_string(self.gai.get_file_diffs_as_str());
+    /// the truncated files, listed under the main ones
+    fn truncated_files(&self) -> Vec<&GaiFile> {
+        self.gai
+            .files
+            .iter()
+            .filter(|g| {
+                g.should_truncate && self.matches_search(&g.path)
+            })
+            .collect()
+    }
 
-        tokio::spawn(async move {
-            let resp =
-                get_response(&req, provider, provider_cfg).await;
-            let _ = tx.send(resp).await;
-        });
+    /// files of whichever list has focus
+    fn focused_files(&self) -> Vec<&GaiFile> {
+        match self.ui.list_pane {
+            ListPane::Main => self.listed_files(),
+            ListPane::Secondary => self.truncated_files(),
+        }
     }
 
-    pub fn display_response(&mut self, resp: Response) {
-        self.response = Some(resp);
-        self.is_loading = false;
+    fn focused_selected(&self) -> Option<usize> {
+        match self.ui.list_pane {
+            ListPane::Main => self.ui.selected_state.selected(),
+            ListPane::Secondary => self.ui.secondary_state.selected(),
+        }
     }
 
-    pub fn apply_commits(&self) {
-        match self.ui.selected_tab {
-            SelectedTab::Diffs => {}
-            _ => {
-                if let Some(data) = &self.response
-                    && data.result.is_ok()
-                {
-                    let commits: Vec<GaiCommit> = data
-                        .result
-                        .to_owned()
-                        .unwrap()
-                        .commits
-                        .iter()
-                        .map(|response_commit| {
-                            GaiCommit::from_response(
-                                response_commit,
-                                self.gai.capitalize_prefix,
-                                self.gai.include_scope,
-                            )
-                        })
-                        .collect();
+    /// path of the file selected in the focused list
+    fn focused_path(&self) -> Option<String> {
+        let selected = self.focused_selected()?;
+        self.focused_files()
+            .get(selected)
+            .map(|g| g.path.to_owned())
+    }
 
-                    self.gai.apply_commits(&commits);
-                }
+    /// h / l go files, truncated files, content before
+    /// changing tabs, unwrapped content scrolls sideways
+    /// until it's back at the start
+    pub fn focus_left(&mut self) {
+        if !self.browsing() {
+            self.ui.focus_left();
+        } else if self.ui.focused_pane == FocusedPane::Content {
+            if self.ui.wrap || self.ui.h_scroll == 0 {
+                self.ui.focus_list();
+            } else {
+                self.ui.focus_left();
             }
+        } else if self.in_diffs()
+            && self.ui.list_pane == ListPane::Secondary
+        {
+            self.ui.focus_pane(ListPane::Main);
+        } else {
+            self.ui.focus_left();
         }
     }
 
-    pub fn remove_selected(&mut self) {
-        if let SelectedTab::Diffs = self.ui.selected_tab {
-            let selection_list = self.get_list().main;
-            let selected_state_idx =
-                self.ui.selected_state.selected();
-            if let Some(selected) = selected_state_idx
-                && selected < self.gai.files.len()
-            {
-                let selected_file = &selection_list[selected];
-                if let Some(pos) = self
-                    .gai
-                    .files
-                    .iter()
-                    .position(|g| g.path == *selected_file)
-                {
-                    self.gai.files.remove(pos);
-                }
-            }
+    pub fn focus_right(&mut self) {
+        if !self.browsing()
+            || self.ui.focused_pane == FocusedPane::Content
+        {
+            self.ui.focus_right();
+        } else if self.in_diffs()
+            && self.ui.list_pane == ListPane::Main
+            && !self.ui.compact
+            && !self.truncated_files().is_empty()
+        {
+            self.ui.focus_pane(ListPane::Secondary);
+        } else if self.focused_selected().is_some() {
+            self.ui.focus_content();
+        } else {
+            self.ui.focus_right();
         }
     }
 
-    pub fn truncate_selected(&mut self) {
-        if let SelectedTab::Diffs = self.ui.selected_tab {
-            let selected_state_idx =
-                self.ui.selected_state.selected();
-            if let Some(selected) = selected_state_idx
-                && selected < self.gai.files.len()
-            {
-                self.gai.files[selected].should_truncate =
-                    !self.gai.files[selected].should_truncate;
-            }
+    /// swaps the commits for the response body they
+    /// were parsed from, focused so j / k scroll it
+    pub fn toggle_raw_view(&mut self) {
+        if !matches!(self.ui.selected_tab, SelectedTab::Commits)
+            || self.response.is_none()
+        {
+            return;
+        }
+
+        self.ui.raw_view = !self.ui.raw_view;
+        self.ui.mode = UIMode::TabNavigation;
+        self.ui.focus_list();
+        if self.ui.raw_view {
+            self.ui.focus_content();
         }
     }
 
+    fn showing_raw(&self) -> bool {
+        self.ui.raw_view
+            && matches!(self.ui.selected_tab, SelectedTab::Commits)
+            && self.response.is_some()
+    }
+
+    /// the body pretty printed when it's json,
+    /// split requests have one per group
+    fn raw_content(&self) -> String {
+        let Some(resp) = &self.response else {
+            return String::new();
+        };
+
+        let provider = resp
+            .provider
+            .map(|p| p.to_string())
+            .unwrap_or_else(|| "the provider".to_owned());
+
+        let Some(raw) = &resp.raw else {
+            return format!(
+                "No raw response from {}, it isn't kept for every provider (v for the commits)",
+                provider
+            );
+        };
+
+        let raw = serde_json::from_str::<serde_json::Value>(raw)
+            .ok()
+            .and_then(|json| serde_json::to_string_pretty(&json).ok())
+            .unwrap_or_else(|| raw.to_owned());
+
+        format!(
+            "Raw response from {} (j / k to scroll, v for the commits)\n\n{}",
+            provider, raw
+        )
+    }
+
+    /// on a list or its content, not inside an entered one
+    fn browsing(&self) -> bool {
+        matches!(self.ui.mode, UIMode::TabNavigation)
+    }
+
+    fn in_diffs(&self) -> bool {
+        matches!(self.ui.selected_tab, SelectedTab::Diffs)
+    }
+
+    /// only narrows what's shown,
+    /// everything still gets sent
+    fn matches_search(&self, path: &str) -> bool {
+        self.ui.search.as_deref().is_none_or(|query| {
+            path.to_lowercase().contains(&query.to_lowercase())
+        })
+    }
+
     fn get_list(&self) -> TabList {
         match self.ui.selected_tab {
             SelectedTab::Diffs => {
-                let main = self
-                    .gai
-                    .files
+                let listed = self.listed_files();
+                let main = listed
                     .iter()
-                    .filter(|g| !g.should_truncate)
-                    .map(|g| g.path.to_owned())
+                    .map(|g| {
+                        let (added, removed) = g.line_stats();
+                        // shown, but not sent
+                        let note = if g.has_conflict_markers() {
+                            " (conflict markers)"
+                        } else if self.gai.skips_file(g) {
+                            " (whitespace only)"
+                        } else if self.is_summarized(&g.path) {
+                            " (summarized)"
+                        } else {
+                            ""
+                        };
+
+                        format!(
+                            "{} +{} -{}{}",
+                            g.display_path(),
+                            added,
+                            removed,
+                            note
+                        )
+                    })
+                    .collect();
+
+                let flagged = listed
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, g)| g.has_conflict_markers())
+                    .map(|(i, _)| i)
                     .collect();
 
                 let secondary: Vec<String> = self
-                    .gai
-                    .files
+                    .truncated_files()
                     .iter()
-                    .filter(|g| g.should_truncate)
-                    .map(|g| g.path.to_owned())
+                    .map(|g| {
+                        format!("{} (truncated)", g.display_path())
+                    })
                     .collect();
 
                 let (secondary, secondary_title) = if secondary
@@ -216,39 +1908,121 @@ impl App {
                     (Some(secondary), Some("Truncated".to_owned()))
                 };
 
+                let mut main_title = if self.gai.from_patch {
+                    "Files (patch)".to_owned()
+                } else if self.gai.only_staged {
+                    "Files (staged)".to_owned()
+                } else {
+                    "Files (working tree)".to_owned()
+                };
+
+                let (added, removed) = self
+                    .gai
+                    .files
+                    .iter()
+                    .map(|g| g.line_stats())
+                    .fold((0, 0), |(a, r), (added, removed)| {
+                        (a + added, r + removed)
+                    });
+                main_title.push_str(&format!(" +{added} -{removed}"));
+
+                // what the model gets, when it's not git's default
+                if self.gai.diff_context_lines != DIFF_CONTEXT_LINES {
+                    main_title.push_str(&format!(
+                        " -U{}",
+                        self.gai.diff_context_lines
+                    ));
+                }
+
+                // the truncated list is hidden, keep a count
+                if self.ui.compact
+                    && let Some(truncated) = &secondary
+                {
+                    main_title.push_str(&format!(
+                        " ({} truncated)",
+                        truncated.len()
+                    ));
+                }
+
+                if let Some(search) = &self.ui.search {
+                    let cursor =
+                        if matches!(self.ui.mode, UIMode::Search) {
+                            "_"
+                        } else {
+                            ""
+                        };
+                    main_title
+                        .push_str(&format!(" /{search}{cursor}"));
+                }
+
                 TabList {
                     main,
                     secondary,
-                    main_title: "Files".to_owned(),
+                    main_title,
                     secondary_title,
+                    flagged,
+                    badges: Vec::new(),
                 }
             }
 
             SelectedTab::Commits => {
+                // the final response sorts these
+                // back into group order
+                if self.is_loading && self.group_progress.is_some() {
+                    let (main, badges) = self
+                        .group_commits
+                        .iter()
+                        .map(|c| self.commit_item(c, false))
+                        .unzip();
+
+                    return TabList {
+                        main,
+                        secondary: None,
+                        main_title: "Commits".to_owned(),
+                        secondary_title: None,
+                        flagged: breaking_commits(
+                            &self.group_commits,
+                        ),
+                        badges,
+                    };
+                }
+
                 if let Some(resp) = &self.response
                     && resp.result.is_ok()
                 {
-                    let commit_cfg = &self.cfg.gai.commit_config;
                     // kinda jank,
                     // but guaranteed to not be
                     // err
                     let res = resp.result.clone().unwrap();
-                    let main: Vec<String> = res
+                    let invalid: Vec<usize> = self
+                        .invalid_commits()
+                        .into_iter()
+                        .map(|(i, _)| i)
+                        .collect();
+
+                    let (main, badges) = res
                         .commits
                         .iter()
-                        .map(|c| {
-                            c.get_commit_prefix(
-                                commit_cfg.capitalize_prefix,
-                                commit_cfg.include_scope,
-                            )
+                        .enumerate()
+                        .map(|(i, c)| {
+                            self.commit_item(c, invalid.contains(&i))
                         })
-                        .collect();
+                        .unzip();
+
+                    let main_title = match resp.provider {
+                        Some(provider) => {
+                            format!("Commits ({})", provider)
+                        }
+                        None => "Commits".to_owned(),
+                    };
 
                     TabList {
                         main,
                         secondary: None,
-                        main_title: "Commits".to_owned(),
+                        main_title,
                         secondary_title: None,
+                        flagged: breaking_commits(&res.commits),
+                        badges,
                     }
                 } else {
                     TabList {
@@ -256,41 +2030,218 @@ impl App {
                         secondary: None,
                         main_title: String::new(),
                         secondary_title: None,
+                        flagged: Vec::new(),
+                        badges: Vec::new(),
                     }
                 }
             }
         }
     }
 
+    /// a row of the commits list, the prefix as a badge
+    /// before the header. edited ones are shown as written
+    fn commit_item(
+        &self,
+        commit: &ResponseCommit,
+        invalid: bool,
+    ) -> (String, Option<Badge>) {
+        let lead = if invalid { "⚠ " } else { "" };
+
+        if let Some(edited) = &commit.message.edited {
+            let first = edited.lines().next().unwrap_or_default();
+            return (format!("{}{}", lead, first), None);
+        }
+
+        let prefix =
+            commit.get_commit_prefix(&self.cfg.gai.commit_config);
+        let badge = Badge {
+            range: lead.len()..lead.len() + prefix.len(),
+            color: self.cfg.tui.prefix_color(commit.message.prefix),
+        };
+
+        (
+            format!("{}{} {}", lead, prefix, commit.message.header),
+            Some(badge),
+        )
+    }
+
+    /// the commit as it'll be committed, with
+    /// anything spelled wrong picked out
+    fn response_content(
+        &self,
+        commit: &ResponseCommit,
+    ) -> TabContent {
+        let commit =
+            GaiCommit::preview(commit, &self.cfg.gai.commit_config);
+
+        let typos = if self.cfg.tui.spell_check {
+            let message = &commit.message;
+            self.spelling.check(&match &message.edited {
+                Some(edited) => edited.to_owned(),
+                None => {
+                    format!("{}\n{}", message.header, message.body)
+                }
+            })
+        } else {
+            Vec::new()
+        };
+
+        let gitmoji = self
+            .cfg
+            .gai
+            .commit_config
+            .gitmoji_for(commit.message.prefix)
+            .map(str::to_owned);
+
+        let diffs = self
+            .gai
+            .commit_hunks(&commit.files, &commit.hunk_ids)
+            .into_iter()
+            .map(|(file, hunks)| {
+                let mut label = file.display_path();
+                if let Some(size) = file.binary_size {
+                    label.push_str(&format!(
+                        " (binary, {} bytes changed)",
+                        size
+                    ));
+                }
+
+                (label, hunks.into_iter().cloned().collect())
+            })
+            .collect();
+
+        let prefix_color =
+            self.cfg.tui.prefix_color(commit.message.prefix);
+
+        TabContent::Response {
+            commit: Box::new(commit),
+            typos,
+            gitmoji,
+            prefix_color,
+            diffs,
+            word_diff: self.ui.word_diff,
+        }
+    }
+
     fn get_content(&self) -> TabContent {
-        let selection_list = self.get_list().main;
         let selected_tab = self.ui.selected_tab;
         let selected_state_idx = self.ui.selected_state.selected();
 
         match selected_tab {
-            SelectedTab::Diffs => selected_state_idx
-                .filter(|&selected| selected < selection_list.len())
+            SelectedTab::Diffs => self
+                .focused_selected()
                 .and_then(|selected| {
-                    self.gai
-                        .files
-                        .iter()
-                        .find(|gai| {
-                            gai.path == selection_list[selected]
-                        })
-                        .map(|gai| {
-                            if gai.should_truncate {
-                                TabContent::Description(
-                                    "Truncated File".to_owned(),
+                    self.focused_files().get(selected).map(|gai| {
+                        if gai.should_truncate {
+                            TabContent::Description(
+                                self.gai.truncated_preview(gai),
+                            )
+                        } else if let Some(size) = gai.binary_size {
+                            TabContent::Description(format!(
+                                "Binary file ({} bytes changed)",
+                                size
+                            ))
+                        } else if let Some(old) = &gai.old_path
+                            && gai.hunks.is_empty()
+                        {
+                            TabContent::Description(format!(
+                                "Renamed from {}, contents unchanged",
+                                old
+                            ))
+                        } else {
+                            TabContent::Diff {
+                                hunks: gai.hunks.clone(),
+                                selected: matches!(
+                                    self.ui.mode,
+                                    UIMode::Content
                                 )
-                            } else {
-                                TabContent::Diff(gai.hunks.clone())
+                                .then_some(self.ui.selected_hunk),
+                                word_diff: self.ui.word_diff,
                             }
-                        })
+                        }
+                    })
                 })
-                .unwrap_or(TabContent::Description(
-                    "Select a file to view its diffs".to_owned(),
-                )),
+                .unwrap_or_else(|| {
+                    TabContent::Description(
+                        if self.gai.has_changes() {
+                            "Select a file to view its diffs"
+                                .to_owned()
+                        } else {
+                            "No changes to commit".to_owned()
+                        },
+                    )
+                }),
             SelectedTab::Commits => {
+                if let Some(err) = &self.apply_error {
+                    return TabContent::Description(err.to_owned());
+                }
+
+                if self.showing_raw() {
+                    return TabContent::Description(
+                        self.raw_content(),
+                    );
+                }
+
+                if let Some(dry_run) = &self.dry_run_output {
+                    return TabContent::Description(format!(
+                        "Dry run, nothing was changed (Enter to scroll, j/k in the list to close)\n\n{}",
+                        dry_run
+                    ));
+                }
+
+                if self.is_loading
+                    && let Some(idx) = self.regenerating
+                {
+                    return TabContent::Stream {
+                        label: format!(
+                            "Regenerating commit {} with {}",
+                            idx + 1,
+                            self.cfg.ai.provider
+                        ),
+                        text: self.partial_response.to_owned(),
+                    };
+                }
+
+                if self.is_loading
+                    && !self.partial_response.is_empty()
+                {
+                    return TabContent::Stream {
+                        label: format!(
+                            "Streaming response from {}",
+                            self.cfg.ai.provider
+                        ),
+                        text: self.partial_response.to_owned(),
+                    };
+                }
+
+                if self.is_loading
+                    && let Some((done, total)) = self.summary_progress
+                    && done < total
+                {
+                    return TabContent::Description(format!(
+                        "Summarizing large files ({}/{})... ({}s), esc to cancel",
+                        done,
+                        total,
+                        self.elapsed_secs()
+                    ));
+                }
+
+                if self.is_loading
+                    && let Some((done, total)) = self.group_progress
+                {
+                    if let Some(selected) = selected_state_idx
+                        && let Some(commit) =
+                            self.group_commits.get(selected)
+                    {
+                        return self.response_content(commit);
+                    }
+
+                    return TabContent::Description(format!(
+                        "{}/{} groups complete",
+                        done, total
+                    ));
+                }
+
                 if let Some(resp) = &self.response {
                     let res = match &resp.result {
                         Ok(r) => r,
@@ -304,8 +2255,8 @@ impl App {
                     if let Some(selected) = selected_state_idx
                         && selected < res.commits.len()
                     {
-                        return TabContent::Response(
-                            res.commits[selected].to_owned(),
+                        return self.response_content(
+                            &res.commits[selected],
                         );
                     }
 
@@ -313,30 +2264,116 @@ impl App {
                         "Select a Commit to View".to_owned(),
                     )
                 } else {
-                    let model = self
+                    let provider_cfg = self
                         .cfg
                         .ai
                         .providers
                         .get(&self.cfg.ai.provider)
                         .expect(
                             "somehow failed to find provider config",
-                        )
-                        .model
-                        .to_owned();
+                        );
+                    let model = provider_cfg.model.to_owned();
 
                     if self.is_loading {
+                        if let Some((attempt, max)) =
+                            self.retry_status
+                        {
+                            return TabContent::Description(format!(
+                                "Retrying ({}/{})... ({}s), esc to cancel",
+                                attempt,
+                                max,
+                                self.elapsed_secs()
+                            ));
+                        }
+
                         return TabContent::Description(format!(
-                            "Awaiting response from {} using {}",
-                            self.cfg.ai.provider, model
+                            "Awaiting response from {} using {} ({}s), esc to cancel",
+                            self.cfg.ai.provider,
+                            model,
+                            self.elapsed_secs()
                         ));
                     }
 
+                    let mut estimate = format!(
+                        "Estimated prompt size: ~{} tokens",
+                        self.request.estimate_tokens()
+                    );
+
+                    if let Some(cost) = self
+                        .request
+                        .estimate_cost(provider_cfg.input_price)
+                    {
+                        estimate
+                            .push_str(&format!(" (~${:.4})", cost));
+                    }
+
+                    if let Err(e) =
+                        self.request.check_context(&self.cfg.ai)
+                    {
+                        estimate.push_str(&format!("\n\n{:#}", e));
+                    }
+
                     TabContent::Description(format!(
-                        "Press 'p' to send a request to {}",
-                        model
+                        "Press 'p' to send a request to {}\n\n{}",
+                        model, estimate
                     ))
                 }
             }
         }
     }
 }
+
+/// the configured providers in a stable order, whether
+/// each has a key is only checked once
+fn provider_tabs(cfg: &Config) -> Vec<ProviderTab> {
+    Provider::iter()
+        .filter_map(|provider| {
+            let provider_cfg = cfg.ai.providers.get(&provider)?;
+
+            Some(ProviderTab {
+                provider,
+                ready: provider.has_credentials(provider_cfg),
+            })
+        })
+        .collect()
+}
+
+fn breaking_commits(commits: &[ResponseCommit]) -> Vec<usize> {
+    commits
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.is_breaking())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn open_in_editor(text: &str) -> Result<String> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+
+    let path = temp_file::create("GAI_EDITMSG", text.as_bytes())?;
+    let edited = run_editor(&editor, &path);
+    let _ = fs::remove_file(&path);
+
+    edited
+}
+
+fn run_editor(editor: &str, path: &Path) -> Result<String> {
+    // $EDITOR can carry args like "code --wait"
+    let mut parts = editor.split_whitespace();
+    let program =
+        parts.next().ok_or_else(|| anyhow!("$EDITOR is empty"))?;
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("can't run {}: {}", editor, e))?;
+
+    if !status.success() {
+        return Err(anyhow!("{} exited with {}", editor, status));
+    }
+
+    Ok(fs::read_to_string(path)?)
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use crossterm::event::{
-    Event as CrosstermEvent, KeyEvent, KeyEventKind,
+    Event as CrosstermEvent, KeyEvent, KeyEventKind, MouseEvent,
 };
 use futures::{FutureExt, StreamExt};
 use tokio::{sync::mpsc, task::JoinHandle, time::interval};
@@ -14,6 +14,7 @@ pub enum Event {
     Error,
     AppTick,
     Key(KeyEvent),
+    Mouse(MouseEvent),
 }
 
 #[derive(Debug)]
@@ -50,12 +51,17 @@ impl EventHandler {
 
                     maybe_event = crossterm_event => {
                         match maybe_event {
-                            Some(Ok(evt)) => {
-                                if let CrosstermEvent::Key(key) = evt
-                                    && key.kind == KeyEventKind::Press {
+                            Some(Ok(evt)) => match evt {
+                                CrosstermEvent::Key(key)
+                                    if key.kind == KeyEventKind::Press => {
                                         let _ = tx.send(Event::Key(key));
                                     }
-                            }
+                                // only sent when capture is enabled
+                                CrosstermEvent::Mouse(mouse) => {
+                                    let _ = tx.send(Event::Mouse(mouse));
+                                }
+                                _ => {}
+                            },
                             Some(Err(_)) => {
                                 let _ = tx.send(Event::Error);
                             }
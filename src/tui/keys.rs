@@ -1,27 +1,438 @@
-use crate::tui::app::Action;
+use crate::{
+    config::Keymap,
+    tui::{
+        app::Action,
+        ui::{UI, UIMode},
+    },
+};
 use crossterm::event::{KeyCode, KeyEvent};
 
-pub fn get_tui_action(key: KeyEvent) -> Option<Action> {
-    match key.code {
-        KeyCode::Esc => Some(Action::Quit),
-        KeyCode::Char('q' | 'Q') => Some(Action::Quit),
-        KeyCode::Char('k') | KeyCode::Up => Some(Action::ScrollUp),
-        KeyCode::Char('j') | KeyCode::Down => {
-            Some(Action::ScrollDown)
+pub struct KeyBinding {
+    pub codes: &'static [KeyCode],
+    /// how the keys show up in the help overlay
+    pub label: &'static str,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+/// every normal mode binding, get_tui_action
+/// and the help overlay both read from this
+pub const KEY_BINDINGS: &[KeyBinding] = &[
+    KeyBinding {
+        codes: &[
+            KeyCode::Char('q'),
+            KeyCode::Char('Q'),
+            KeyCode::Esc,
+        ],
+        label: "q / esc",
+        action: Action::Quit,
+        description: "Quit (esc cancels a request or clears an active filter first)",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('k'), KeyCode::Up],
+        label: "k / up",
+        action: Action::ScrollUp,
+        description: "Select the previous item or scroll up",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('j'), KeyCode::Down],
+        label: "j / down",
+        action: Action::ScrollDown,
+        description: "Select the next item or scroll down",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('h'), KeyCode::Left],
+        label: "h / left",
+        action: Action::FocusLeft,
+        description: "Back to the list or the files list, then the previous tab, or scroll unwrapped lines",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('l'), KeyCode::Right],
+        label: "l / right",
+        action: Action::FocusRight,
+        description: "Go to the truncated list, the content, then the next tab, or scroll unwrapped lines",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Tab],
+        label: "tab",
+        action: Action::NextTab,
+        description: "Go to the next tab",
+    },
+    KeyBinding {
+        codes: &[KeyCode::BackTab],
+        label: "shift+tab",
+        action: Action::PreviousTab,
+        description: "Go to the previous tab",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Enter],
+        label: "enter",
+        action: Action::Enter,
+        description: "Enter or leave the selected diff/commit",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char(' ')],
+        label: "space",
+        action: Action::ToggleHunk,
+        description: "Exclude or include the selected hunk",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('w')],
+        label: "w",
+        action: Action::ToggleWrap,
+        description: "Wrap long lines, or scroll them with h / l",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('W')],
+        label: "W",
+        action: Action::ToggleWordDiff,
+        description: "Highlight the words that changed in diffs",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('v')],
+        label: "v",
+        action: Action::ToggleRawView,
+        description: "Show the raw response in place of the commits",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('/')],
+        label: "/",
+        action: Action::StartSearch,
+        description: "Filter the files in the diffs tab",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('d')],
+        label: "d",
+        action: Action::RemoveCurrentSelected,
+        description: "Remove the selected file from the request",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('t')],
+        label: "t",
+        action: Action::TruncateCurrentSelected,
+        description: "Truncate the selected file, or restore a truncated one",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('u')],
+        label: "u",
+        action: Action::Undo,
+        description: "Undo the last remove or truncate",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('s')],
+        label: "s",
+        action: Action::ToggleStaged,
+        description: "Toggle staged and working tree diffs",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('a')],
+        label: "a",
+        action: Action::ToggleUntracked,
+        description: "Include or leave out untracked files",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('p')],
+        label: "p",
+        action: Action::SendRequest,
+        description: "Send the request (uses a cached response)",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('P')],
+        label: "P",
+        action: Action::SendFreshRequest,
+        description: "Send a fresh request, skipping the cache",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('r')],
+        label: "r",
+        action: Action::RegenerateSelected,
+        description: "Regenerate only the selected commit",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('R')],
+        label: "R",
+        action: Action::RegenerateAll,
+        description: "Throw out the commits and regenerate them all",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('c')],
+        label: "c",
+        action: Action::CommitSelectedOnly,
+        description: "Commit just the selected file, right away",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('K')],
+        label: "K",
+        action: Action::MoveCommitUp,
+        description: "Move the selected commit up",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('J')],
+        label: "J",
+        action: Action::MoveCommitDown,
+        description: "Move the selected commit down",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('m')],
+        label: "m",
+        action: Action::MergeWithNext,
+        description: "Merge the selected commit with the next one",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('e')],
+        label: "e",
+        action: Action::EditSelected,
+        description: "Edit the selected commit in $EDITOR",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('y')],
+        label: "y",
+        action: Action::CopySelected,
+        description: "Copy the selected commit message",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('x')],
+        label: "x",
+        action: Action::ApplyCommits,
+        description: "Apply the commits",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('n')],
+        label: "n",
+        action: Action::DryRunApply,
+        description: "Dry run, show what applying would do",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('X')],
+        label: "X",
+        action: Action::ForceApplyCommits,
+        description: "Apply the commits, even invalid ones",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('b')],
+        label: "b",
+        action: Action::ApplyToNewBranch,
+        description: "Apply the commits onto a new branch",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('M')],
+        label: "M",
+        action: Action::PickModel,
+        description: "Pick a model for the current provider",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('1')],
+        label: "1",
+        action: Action::DiffTab,
+        description: "Go to the diffs tab",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('2')],
+        label: "2",
+        action: Action::OpenAITab,
+        description: "Switch to OpenAI",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('3')],
+        label: "3",
+        action: Action::ClaudeTab,
+        description: "Switch to Claude",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('4')],
+        label: "4",
+        action: Action::GeminiTab,
+        description: "Switch to Gemini",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('5')],
+        label: "5",
+        action: Action::OllamaTab,
+        description: "Switch to Ollama",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('6')],
+        label: "6",
+        action: Action::MistralTab,
+        description: "Switch to Mistral",
+    },
+    KeyBinding {
+        codes: &[KeyCode::Char('?')],
+        label: "?",
+        action: Action::ToggleHelp,
+        description: "Show or hide this help",
+    },
+];
+
+pub struct KeySequence {
+    pub keys: &'static [char],
+    pub label: &'static str,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+/// on top of KEY_BINDINGS with the vim keymap,
+/// hjkl are already part of the defaults
+pub const VIM_SEQUENCES: &[KeySequence] = &[
+    KeySequence {
+        keys: &['g', 'g'],
+        label: "gg",
+        action: Action::JumpTop,
+        description: "Jump to the top of the list",
+    },
+    KeySequence {
+        keys: &['G'],
+        label: "G",
+        action: Action::JumpBottom,
+        description: "Jump to the bottom of the list",
+    },
+    KeySequence {
+        keys: &[':', 'q'],
+        label: ":q",
+        action: Action::Quit,
+        description: "Quit",
+    },
+];
+
+pub fn get_tui_action(
+    key: KeyEvent,
+    ui: &mut UI,
+    is_loading: bool,
+) -> Option<Action> {
+    // the overlay swallows everything else
+    if ui.show_help {
+        return match key.code {
+            KeyCode::Char('?') | KeyCode::Esc => {
+                Some(Action::ToggleHelp)
+            }
+            _ => None,
+        };
+    }
+
+    // nothing to do until the hook is done
+    if let Some(hook) = &ui.hook {
+        return match key.code {
+            _ if hook.run.is_none() => None,
+            KeyCode::Char('r') if hook.failed() && hook.can_reset => {
+                Some(Action::ResetApplied)
+            }
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => {
+                Some(Action::CloseHook)
+            }
+            _ => None,
+        };
+    }
+
+    if ui.summary.is_some() {
+        return match key.code {
+            KeyCode::Char('y') => Some(Action::CopyApplied),
+            KeyCode::Char('l') => Some(Action::ToggleSummaryLog),
+            KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter => {
+                Some(Action::Quit)
+            }
+            _ => None,
+        };
+    }
+
+    if ui.branch_prompt.is_some() {
+        return get_branch_action(key);
+    }
+
+    if ui.confirm_apply.is_some() {
+        return match key.code {
+            KeyCode::Char('y') | KeyCode::Char('Y') => {
+                Some(Action::ConfirmApply)
+            }
+            KeyCode::Esc | KeyCode::Char('n') => {
+                Some(Action::CancelApply)
+            }
+            _ => None,
+        };
+    }
+
+    if ui.model_picker.is_some() {
+        return match key.code {
+            KeyCode::Char('k') | KeyCode::Up => {
+                Some(Action::ModelPickerUp)
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                Some(Action::ModelPickerDown)
+            }
+            KeyCode::Enter => Some(Action::ChooseModel),
+            KeyCode::Char('s') => Some(Action::SaveModel),
+            KeyCode::Esc | KeyCode::Char('q') => {
+                Some(Action::CancelModelPicker)
+            }
+            _ => None,
+        };
+    }
+
+    if matches!(ui.mode, UIMode::Search) {
+        return get_search_action(key);
+    }
+
+    // esc stops a request, then drops
+    // an active filter before quitting
+    if key.code == KeyCode::Esc && is_loading {
+        return Some(Action::CancelRequest);
+    }
+
+    if key.code == KeyCode::Esc && ui.search.is_some() {
+        return Some(Action::ClearSearch);
+    }
+
+    if matches!(ui.keymap, Keymap::Vim)
+        && let KeyCode::Char(c) = key.code
+    {
+        ui.pending_keys.push(c);
+        let typed: Vec<char> = ui.pending_keys.chars().collect();
+
+        if let Some(sequence) =
+            VIM_SEQUENCES.iter().find(|s| s.keys == typed.as_slice())
+        {
+            ui.pending_keys.clear();
+            return Some(sequence.action);
+        }
+
+        // wait for the rest of it
+        if VIM_SEQUENCES.iter().any(|s| s.keys.starts_with(&typed)) {
+            return None;
         }
-        KeyCode::Char('h') | KeyCode::Left => Some(Action::FocusLeft),
-        KeyCode::Char('l') | KeyCode::Right => {
-            Some(Action::FocusRight)
+
+        ui.pending_keys.clear();
+
+        // a broken sequence is dropped, like vim
+        if typed.len() > 1 {
+            return None;
         }
-        KeyCode::Char('d') => Some(Action::RemoveCurrentSelected),
-        KeyCode::Char('t') => Some(Action::TruncateCurrentSelected),
-        KeyCode::Char('p') => Some(Action::SendRequest),
-        KeyCode::Char('x') => Some(Action::ApplyCommits),
-        KeyCode::Char('1') => Some(Action::DiffTab),
-        KeyCode::Char('2') => Some(Action::OpenAITab),
-        KeyCode::Char('3') => Some(Action::ClaudeTab),
-        KeyCode::Char('4') => Some(Action::GeminiTab),
-        KeyCode::Enter => Some(Action::Enter),
+    } else {
+        ui.pending_keys.clear();
+    }
+
+    KEY_BINDINGS
+        .iter()
+        .find(|binding| binding.codes.contains(&key.code))
+        .map(|binding| binding.action)
+}
+
+/// everything typed goes to the branch name
+fn get_branch_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Esc => Some(Action::CancelBranch),
+        KeyCode::Enter => Some(Action::ConfirmBranch),
+        KeyCode::Backspace => Some(Action::BranchBackspace),
+        KeyCode::Char(c) => Some(Action::BranchInput(c)),
+        _ => None,
+    }
+}
+
+/// everything typed goes to the query
+fn get_search_action(key: KeyEvent) -> Option<Action> {
+    match key.code {
+        KeyCode::Esc => Some(Action::ClearSearch),
+        KeyCode::Enter => Some(Action::ConfirmSearch),
+        KeyCode::Backspace => Some(Action::SearchBackspace),
+        KeyCode::Char(c) => Some(Action::SearchInput(c)),
         _ => None,
     }
 }
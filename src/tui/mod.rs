@@ -1,10 +1,21 @@
 use crate::{
-    ai::{request::Request, response::Response},
+    ai::{
+        provider::Provider,
+        request::Request,
+        response::{Response, ResponseEvent},
+    },
     config::Config,
     git::repo::GaiGit,
     tui::app::{Action, App},
 };
 use anyhow::Result;
+use crossterm::{
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{EnterAlternateScreen, enable_raw_mode},
+};
+use std::io::stdout;
 use tokio::sync::mpsc;
 
 pub mod app;
@@ -23,14 +34,20 @@ pub async fn run_tui(
 ) -> Result<()> {
     let mut app = App::new(req, cfg, gai, response);
 
-    let (resp_tx, mut resp_rx) = mpsc::channel(1);
+    let (resp_tx, mut resp_rx) = mpsc::channel(32);
 
     if app.cfg.tui.auto_request {
-        app.send_request(resp_tx.clone()).await;
+        app.send_request(resp_tx.clone(), false).await;
     }
 
     let mut terminal = ratatui::init();
 
+    let mouse = app.cfg.tui.mouse;
+    set_panic_hook(mouse);
+    if mouse {
+        execute!(stdout(), EnableMouseCapture)?;
+    }
+
     let mut event_handler = EventHandler::new(100);
 
     while app.running {
@@ -42,38 +59,104 @@ pub async fn run_tui(
             }
 
             Some(resp) = resp_rx.recv() => {
-                app.display_response(resp);
+                app.on_response_event(resp);
             }
         }
+
+        if app.edit_requested {
+            app.edit_requested = false;
+
+            // hand the terminal (and stdin) over to the editor
+            event_handler.stop().await?;
+            restore_terminal(mouse);
+
+            if let Err(e) = app.edit_selected() {
+                app.ui.notify(format!(
+                    "Couldn't edit the message: {}",
+                    e
+                ));
+            }
+
+            enable_raw_mode()?;
+            execute!(stdout(), EnterAlternateScreen)?;
+            if mouse {
+                execute!(stdout(), EnableMouseCapture)?;
+            }
+            terminal.clear()?;
+            event_handler = EventHandler::new(100);
+        }
     }
 
     event_handler.stop().await?;
-    ratatui::restore();
+    restore_terminal(mouse);
 
     if app.applied_commits {
-        println!("Applied Commits");
+        match &app.applied_branch {
+            Some(branch) => {
+                println!("Applied Commits to {}", branch)
+            }
+            None => println!("Applied Commits"),
+        }
+    } else if let Some(branch) = &app.applied_branch {
+        println!("Switched to {}", branch);
+    }
+
+    if app.reset_applied {
+        println!(
+            "post_apply_hook failed, soft reset the applied commits, their changes are staged"
+        );
+    }
+
+    // anything we couldn't put on the clipboard
+    for message in &app.uncopied {
+        eprintln!("{}", message.trim_end());
+        eprintln!();
     }
 
     Ok(())
 }
 
+/// back to a normal shell, no alternate
+/// screen, raw mode or mouse capture
+fn restore_terminal(mouse: bool) {
+    if mouse {
+        let _ = execute!(stdout(), DisableMouseCapture);
+    }
+    ratatui::restore();
+}
+
+/// ratatui's own hook restores everything but the
+/// mouse and cursor, the panic is printed after ours
+/// runs so it (and the backtrace) lands on the normal screen
+fn set_panic_hook(mouse: bool) {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal(mouse);
+        let _ = execute!(stdout(), Show);
+        hook(info);
+    }));
+}
+
 async fn handle_event(
     app: &mut App,
     event: Event,
-    response_tx: mpsc::Sender<Response>,
+    response_tx: mpsc::Sender<ResponseEvent>,
 ) {
     match event {
         Event::Key(key) => {
-            if let Some(action) = keys::get_tui_action(key) {
+            if let Some(action) =
+                keys::get_tui_action(key, &mut app.ui, app.is_loading)
+            {
                 handle_action(app, action, response_tx).await;
             }
         }
+        Event::Mouse(mouse) => app.on_mouse(mouse),
         Event::AppTick => {
             app.on_tick();
         }
         Event::Error => {
             // ignoring for now
-            app.running = false;
+            app.quit();
         }
     }
 }
@@ -81,28 +164,98 @@ async fn handle_event(
 async fn handle_action(
     app: &mut App,
     action: Action,
-    response_tx: mpsc::Sender<Response>,
+    response_tx: mpsc::Sender<ResponseEvent>,
 ) {
     let ui = &mut app.ui;
 
     match action {
-        Action::Quit => app.running = false,
-        Action::ScrollUp => ui.scroll_up(),
-        Action::ScrollDown => ui.scroll_down(),
-        Action::FocusLeft => ui.focus_left(),
-        Action::FocusRight => ui.focus_right(),
+        Action::Quit => app.quit(),
+        Action::ScrollUp => app.scroll_up(),
+        Action::ScrollDown => app.scroll_down(),
+        Action::JumpTop => app.jump_top(),
+        Action::JumpBottom => app.jump_bottom(),
+        Action::FocusLeft => app.focus_left(),
+        Action::FocusRight => app.focus_right(),
+        Action::NextTab => ui.focus_right(),
+        Action::PreviousTab => ui.focus_left(),
+        Action::ToggleHelp => ui.show_help = !ui.show_help,
+        Action::ToggleWrap => ui.toggle_wrap(),
+        Action::ToggleWordDiff => ui.toggle_word_diff(),
+        Action::ToggleRawView => app.toggle_raw_view(),
         Action::Enter => ui.enter_ui(),
         Action::DiffTab => ui.goto_tab(1),
-        Action::OpenAITab => ui.goto_tab(2),
-        Action::ClaudeTab => ui.goto_tab(3),
-        Action::GeminiTab => ui.goto_tab(4),
+        Action::OpenAITab => app.switch_provider(Provider::OpenAI),
+        Action::ClaudeTab => app.switch_provider(Provider::Claude),
+        Action::GeminiTab => app.switch_provider(Provider::Gemini),
+        Action::OllamaTab => app.switch_provider(Provider::Ollama),
+        Action::MistralTab => app.switch_provider(Provider::Mistral),
         Action::SendRequest => {
-            app.send_request(response_tx).await;
+            app.send_request(response_tx, false).await;
         }
-        Action::ApplyCommits => {
-            app.apply_commits();
-            app.applied_commits = true;
-            app.running = false;
+        Action::SendFreshRequest => {
+            app.send_request(response_tx, true).await;
+        }
+        Action::RegenerateAll => {
+            app.regenerate_all(response_tx).await;
+        }
+        Action::CancelRequest => app.cancel_request(),
+        Action::RegenerateSelected => {
+            app.regenerate_selected(response_tx);
+        }
+        Action::CommitSelectedOnly => {
+            app.commit_selected_only(response_tx);
+        }
+        Action::ApplyCommits | Action::ForceApplyCommits => {
+            let force = matches!(action, Action::ForceApplyCommits);
+
+            // a hook is never run without seeing it first
+            if !app.cfg.gai.confirm_apply
+                && app.cfg.gai.hook_command().is_none()
+            {
+                apply(app, force, response_tx);
+            } else {
+                app.request_apply(force);
+            }
+        }
+        Action::ConfirmApply => {
+            if let Some(confirm) = app.ui.confirm_apply.take() {
+                apply(app, confirm.force, response_tx);
+            }
+        }
+        Action::CancelApply => app.ui.confirm_apply = None,
+        Action::ApplyToNewBranch => app.request_branch_apply(),
+        Action::BranchInput(c) => app.branch_input(Some(c)),
+        Action::BranchBackspace => app.branch_input(None),
+        Action::ConfirmBranch => {
+            if app.switch_branch() {
+                apply(app, false, response_tx);
+            }
+        }
+        Action::CancelBranch => app.ui.branch_prompt = None,
+        Action::CloseHook => app.finish_apply(),
+        Action::CopyApplied => app.copy_applied(),
+        Action::ToggleSummaryLog => {
+            if let Some(summary) = &mut app.ui.summary {
+                summary.show_log = !summary.show_log;
+            }
+        }
+        Action::ResetApplied => app.reset_applied(),
+        Action::PickModel => app.pick_model(response_tx),
+        Action::ModelPickerUp | Action::ModelPickerDown => {
+            if let Some(picker) = &mut app.ui.model_picker {
+                if matches!(action, Action::ModelPickerUp) {
+                    picker.state.select_previous();
+                } else {
+                    picker.state.select_next();
+                }
+            }
+        }
+        Action::ChooseModel => app.choose_model(false),
+        Action::SaveModel => app.choose_model(true),
+        Action::CancelModelPicker => app.ui.model_picker = None,
+        Action::DryRunApply => app.dry_run(),
+        Action::EditSelected => {
+            app.edit_requested = true;
         }
         Action::RemoveCurrentSelected => {
             app.remove_selected();
@@ -110,7 +263,41 @@ async fn handle_action(
         Action::TruncateCurrentSelected => {
             app.truncate_selected();
         }
+        Action::Undo => app.undo(),
+        Action::ToggleStaged => {
+            app.toggle_staged();
+        }
+        Action::ToggleUntracked => app.toggle_untracked(),
+        Action::ToggleHunk => {
+            app.toggle_hunk();
+        }
+        Action::CopySelected => {
+            app.copy_selected();
+        }
+        Action::MoveCommitUp => app.move_selected(-1),
+        Action::MoveCommitDown => app.move_selected(1),
+        Action::MergeWithNext => app.merge_with_next(),
+        Action::StartSearch => ui.start_search(),
+        Action::SearchInput(c) => ui.search_input(c),
+        Action::SearchBackspace => ui.search_backspace(),
+        Action::ConfirmSearch => ui.confirm_search(),
+        Action::ClearSearch => ui.clear_search(),
+    }
+}
 
-        _ => {}
+fn apply(
+    app: &mut App,
+    force: bool,
+    response_tx: mpsc::Sender<ResponseEvent>,
+) {
+    match app.apply_commits(force) {
+        Ok(applied) => {
+            app.applied_commits = true;
+            // the summary follows the hook's popup
+            if !app.run_post_apply_hook(applied, response_tx) {
+                app.finish_apply();
+            }
+        }
+        Err(e) => app.apply_error = Some(format!("{:#}", e)),
     }
 }
@@ -2,27 +2,36 @@
 
 use ratatui::{
     buffer::Buffer,
-    layout::{Constraint, Layout, Rect},
-    style::{Modifier, Style, Stylize, palette::tailwind},
-    text::Line,
+    layout::{Constraint, Layout, Margin, Rect},
+    style::{Color, Modifier, Style, Stylize, palette::tailwind},
+    text::{Line, Span},
     widgets::{
         Block, Borders, List, ListItem, ListState, Padding,
-        Paragraph, StatefulWidget, Widget, Wrap,
+        Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+        StatefulWidget, Widget, Wrap,
     },
 };
+use std::ops::Range;
 use strum::{Display, EnumIter, FromRepr};
 use throbber_widgets_tui::{Throbber, ThrobberState};
 
 use crate::{
-    ai::response::{PrefixType, ResponseCommit},
-    git::repo::{DiffType, HunkDiff},
-    tui::ui::UIMode,
+    ai::response::ResponseCommit,
+    git::{
+        repo::{DiffType, HunkDiff},
+        spelling::{self, Typo},
+        word_diff,
+    },
 };
 
 const SELECTED_STYLE: Style = Style::new()
     .bg(tailwind::SLATE.c800)
     .add_modifier(Modifier::BOLD);
 
+const BREAKING_STYLE: Style = Style::new()
+    .fg(tailwind::RED.c400)
+    .add_modifier(Modifier::BOLD);
+
 #[derive(Default, Clone, Copy, Display, FromRepr, EnumIter)]
 pub enum SelectedTab {
     #[default]
@@ -41,8 +50,32 @@ pub enum SelectedTab {
 /// compared to what i was doing before
 pub enum TabContent {
     Description(String),
-    Diff(Vec<HunkDiff>),
-    Response(ResponseCommit),
+    /// selected is the hunk picked
+    /// once the diff is entered
+    Diff {
+        hunks: Vec<HunkDiff>,
+        selected: Option<usize>,
+        /// highlight the words that changed
+        /// within paired lines
+        word_diff: bool,
+    },
+    Response {
+        commit: Box<ResponseCommit>,
+        typos: Vec<Typo>,
+        /// shown in front of the prefix
+        gitmoji: Option<String>,
+        /// the prefix's badge, from tui.prefix_colors
+        prefix_color: Color,
+        /// what the commit covers, by file
+        diffs: Vec<(String, Vec<HunkDiff>)>,
+        word_diff: bool,
+    },
+    /// partial text from a request
+    /// that is still streaming in
+    Stream {
+        label: String,
+        text: String,
+    },
 }
 
 /// when we want to display
@@ -55,6 +88,21 @@ pub struct TabList {
 
     pub main_title: String,
     pub secondary_title: Option<String>,
+
+    /// indexes into main drawn with BREAKING_STYLE,
+    /// breaking commits or files with conflict markers
+    pub flagged: Vec<usize>,
+
+    /// one per main item when any are set,
+    /// ex. the commits' prefixes
+    pub badges: Vec<Option<Badge>>,
+}
+
+/// part of a list item drawn as a colored badge
+pub struct Badge {
+    /// bytes into the item
+    pub range: Range<usize>,
+    pub color: Color,
 }
 
 impl SelectedTab {
@@ -65,15 +113,19 @@ impl SelectedTab {
         tab_content: &TabContent,
         tab_list: &TabList,
         selected_state: &mut ListState,
+        secondary_state: Option<&mut ListState>,
         is_loading: bool,
         throbber_state: &mut ThrobberState,
-        mode: &UIMode,
+        content_focused: bool,
         content_scroll: u16,
-    ) {
-        let scroll = if matches!(mode, UIMode::Content) {
-            content_scroll
+        wrap: bool,
+        h_scroll: u16,
+        compact: bool,
+    ) -> Rect {
+        let (scroll, h_scroll) = if content_focused {
+            (content_scroll, h_scroll)
         } else {
-            0
+            (0, 0)
         };
 
         self.render_layout(
@@ -82,11 +134,14 @@ impl SelectedTab {
             tab_content,
             tab_list,
             selected_state,
+            secondary_state,
             is_loading,
             throbber_state,
-            scroll,
-            mode,
-        );
+            (scroll, h_scroll),
+            wrap,
+            content_focused,
+            compact,
+        )
     }
 
     /// Get the previous tab, if there is no previous tab return the current tab.
@@ -123,24 +178,54 @@ impl SelectedTab {
         content: &TabContent,
         tab_list: &TabList,
         selected_state: &mut ListState,
+        secondary_state: Option<&mut ListState>,
         is_loading: bool,
         throbber_state: &mut ThrobberState,
-        scroll: u16,
-        mode: &UIMode,
-    ) {
-        let horizontal = Layout::horizontal([
-            Constraint::Percentage(25),
-            Constraint::Percentage(75),
-        ]);
-        let [list_area, paragraph_area] = horizontal.areas(area);
+        (scroll, h_scroll): (u16, u16),
+        wrap: bool,
+        content_focused: bool,
+        compact: bool,
+    ) -> Rect {
+        // stacked when there's no room for side by side
+        let [list_area, paragraph_area] = if compact {
+            Layout::vertical([
+                Constraint::Percentage(35),
+                Constraint::Percentage(65),
+            ])
+            .areas(area)
+        } else {
+            Layout::horizontal([
+                Constraint::Percentage(25),
+                Constraint::Percentage(75),
+            ])
+            .areas(area)
+        };
 
         let items: Vec<ListItem> = tab_list
             .main
             .iter()
-            .map(|item| ListItem::new(item.as_str()))
+            .enumerate()
+            .map(|(i, item)| {
+                let list_item = match tab_list.badges.get(i) {
+                    Some(Some(badge)) => {
+                        ListItem::new(badged_line(item, badge))
+                    }
+                    _ => ListItem::new(item.as_str()),
+                };
+                if tab_list.flagged.contains(&i) {
+                    list_item.style(BREAKING_STYLE)
+                } else {
+                    list_item
+                }
+            })
             .collect();
 
-        if let Some(secondary) = &tab_list.secondary {
+        // where the selectable list ended up, for mouse clicks
+        let mut main_list_area = list_area;
+
+        if let Some(secondary) =
+            tab_list.secondary.as_ref().filter(|_| !compact)
+        {
             let with_secondary = Layout::vertical([
                 Constraint::Percentage(50),
                 Constraint::Percentage(50),
@@ -148,6 +233,7 @@ impl SelectedTab {
 
             let [primary_area, secondary_area] =
                 with_secondary.areas(list_area);
+            main_list_area = primary_area;
 
             let primary_list = List::new(items)
                 .block(
@@ -157,7 +243,11 @@ impl SelectedTab {
                         .padding(Padding::horizontal(1))
                         .border_style(self.palette().c700),
                 )
-                .highlight_style(SELECTED_STYLE);
+                .highlight_style(if secondary_state.is_some() {
+                    Style::new()
+                } else {
+                    SELECTED_STYLE
+                });
 
             StatefulWidget::render(
                 primary_list,
@@ -186,7 +276,20 @@ impl SelectedTab {
                 )
                 .highlight_style(SELECTED_STYLE);
 
-            Widget::render(secondary_list, secondary_area, buf);
+            // only the focused list shows a selection
+            match secondary_state {
+                Some(state) => StatefulWidget::render(
+                    secondary_list,
+                    secondary_area,
+                    buf,
+                    state,
+                ),
+                None => Widget::render(
+                    secondary_list,
+                    secondary_area,
+                    buf,
+                ),
+            }
         } else {
             let list = List::new(items)
                 .block(
@@ -214,6 +317,7 @@ impl SelectedTab {
                         paragraph_area,
                         buf,
                         desc,
+                        "",
                         throbber_state,
                     );
                 } else {
@@ -221,30 +325,60 @@ impl SelectedTab {
                         paragraph_area,
                         buf,
                         desc,
-                        scroll,
-                        mode,
+                        (scroll, h_scroll),
+                        wrap,
+                        content_focused,
                     );
                 }
             }
-            TabContent::Diff(hunk_diffs) => {
+            TabContent::Diff {
+                hunks,
+                selected,
+                word_diff,
+            } => {
                 self.render_diff(
                     paragraph_area,
                     buf,
-                    hunk_diffs,
-                    scroll,
-                    mode,
+                    hunks,
+                    *selected,
+                    *word_diff,
+                    (scroll, h_scroll),
+                    wrap,
+                    content_focused,
                 );
             }
-            TabContent::Response(commit) => {
+            TabContent::Response {
+                commit,
+                typos,
+                gitmoji,
+                prefix_color,
+                diffs,
+                word_diff,
+            } => {
                 self.render_response(
                     paragraph_area,
                     buf,
                     commit,
-                    scroll,
-                    mode,
+                    typos,
+                    (gitmoji.as_deref(), *prefix_color),
+                    (diffs, *word_diff),
+                    (scroll, h_scroll),
+                    wrap,
+                    content_focused,
+                );
+            }
+            TabContent::Stream { label, text } => {
+                self.render_loading(
+                    paragraph_area,
+                    buf,
+                    label,
+                    text,
+                    throbber_state,
                 );
             }
         }
+
+        main_list_area
     }
 
     fn render_description(
@@ -252,27 +386,26 @@ impl SelectedTab {
         area: Rect,
         buf: &mut Buffer,
         desc: &str,
-        scroll: u16,
-        mode: &UIMode,
+        (scroll, h_scroll): (u16, u16),
+        wrap: bool,
+        content_focused: bool,
     ) {
-        let border_style = if matches!(mode, UIMode::Content) {
+        let border_style = if content_focused {
             self.palette().c400
         } else {
             self.palette().c700
         };
 
-        let paragraph = Paragraph::new(desc.to_owned())
-            .block(
-                Block::bordered()
-                    .title("Content")
-                    .borders(Borders::ALL)
-                    .padding(Padding::horizontal(1))
-                    .border_style(border_style),
-            )
-            .wrap(Wrap { trim: false })
-            .scroll((scroll, 0));
+        let paragraph = Paragraph::new(desc.to_owned()).block(
+            Block::bordered()
+                .title("Content")
+                .borders(Borders::ALL)
+                .padding(Padding::horizontal(1))
+                .border_style(border_style),
+        );
 
-        paragraph.render(area, buf);
+        wrap_or_scroll(paragraph, wrap, scroll, h_scroll)
+            .render(area, buf);
     }
 
     fn render_diff(
@@ -280,53 +413,70 @@ impl SelectedTab {
         area: Rect,
         buf: &mut Buffer,
         hunk_diffs: &[HunkDiff],
-        scroll: u16,
-        mode: &UIMode,
+        selected: Option<usize>,
+        word_diff: bool,
+        (scroll, h_scroll): (u16, u16),
+        wrap: bool,
+        content_focused: bool,
     ) {
-        let border_style = if matches!(mode, UIMode::Content) {
+        let border_style = if content_focused {
             self.palette().c400
         } else {
             self.palette().c700
         };
 
         let mut lines: Vec<Line> = Vec::new();
+        // keep the selected hunk at the top
+        let scroll =
+            diff_lines(&mut lines, hunk_diffs, selected, word_diff)
+                .map_or(scroll, |start| start as u16);
 
-        for hunk in hunk_diffs {
-            lines.push(
-                Line::from(hunk.header.clone())
-                    .bg(tailwind::BLUE.c900),
-            );
+        self.render_scrolled(
+            area,
+            buf,
+            "Content",
+            lines,
+            (scroll, h_scroll),
+            wrap,
+            border_style,
+        );
+    }
 
-            for line_diff in &hunk.line_diffs {
-                let styled_line = match line_diff.diff_type {
-                    DiffType::Additions => {
-                        Line::from(format!("+{}", line_diff.content))
-                            .bg(tailwind::GREEN.c950)
-                    }
-                    DiffType::Deletions => {
-                        Line::from(format!("-{}", line_diff.content))
-                            .bg(tailwind::RED.c950)
-                    }
-                    DiffType::Unchanged => {
-                        Line::from(format!(" {}", line_diff.content))
-                    }
-                };
-                lines.push(styled_line);
-            }
-        }
+    /// a bordered paragraph with a scrollbar
+    /// for the diffs, lines can be long
+    fn render_scrolled(
+        self,
+        area: Rect,
+        buf: &mut Buffer,
+        title: &str,
+        lines: Vec<Line>,
+        (scroll, h_scroll): (u16, u16),
+        wrap: bool,
+        border_style: Color,
+    ) {
+        let lines_len = lines.len();
+        let paragraph = Paragraph::new(lines).block(
+            Block::bordered()
+                .title(title.to_owned())
+                .borders(Borders::ALL)
+                .padding(Padding::horizontal(1))
+                .border_style(border_style),
+        );
 
-        let paragraph = Paragraph::new(lines)
-            .block(
-                Block::bordered()
-                    .title("Content")
-                    .borders(Borders::ALL)
-                    .padding(Padding::horizontal(1))
-                    .border_style(border_style),
-            )
-            .wrap(Wrap { trim: false })
-            .scroll((scroll, 0));
+        wrap_or_scroll(paragraph, wrap, scroll, h_scroll)
+            .render(area, buf);
 
-        paragraph.render(area, buf);
+        let mut scrollbar_state =
+            ScrollbarState::new(lines_len).position(scroll as usize);
+
+        StatefulWidget::render(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None),
+            area.inner(Margin::new(0, 1)),
+            buf,
+            &mut scrollbar_state,
+        );
     }
 
     fn render_response(
@@ -334,10 +484,14 @@ impl SelectedTab {
         area: Rect,
         buf: &mut Buffer,
         commit: &ResponseCommit,
-        scroll: u16,
-        mode: &UIMode,
+        typos: &[Typo],
+        (gitmoji, prefix_color): (Option<&str>, Color),
+        (diffs, word_diff): (&[(String, Vec<HunkDiff>)], bool),
+        (scroll, h_scroll): (u16, u16),
+        wrap: bool,
+        content_focused: bool,
     ) {
-        let border_style = if matches!(mode, UIMode::Content) {
+        let border_style = if content_focused {
             self.palette().c400
         } else {
             self.palette().c700
@@ -345,23 +499,8 @@ impl SelectedTab {
 
         let mut lines: Vec<Line> = Vec::new();
 
-        let prefix_color = match commit.message.prefix {
-            PrefixType::Feat => tailwind::GREEN,
-            PrefixType::Fix => tailwind::RED,
-            PrefixType::Refactor => tailwind::BLUE,
-            PrefixType::Style => tailwind::PURPLE,
-            PrefixType::Test => tailwind::YELLOW,
-            PrefixType::Docs => tailwind::CYAN,
-            PrefixType::Build => tailwind::ORANGE,
-            PrefixType::CI => tailwind::INDIGO,
-            PrefixType::Ops => tailwind::PINK,
-            PrefixType::Chore => tailwind::SLATE,
-            PrefixType::Merge => tailwind::VIOLET,
-            PrefixType::Revert => tailwind::ROSE,
-        };
-
         let prefix_str =
-            format!("{:?}", commit.message.prefix).to_lowercase();
+            format!(" {:?} ", commit.message.prefix).to_lowercase();
         let breaking_str =
             if commit.message.breaking { "!" } else { "" };
         let scope_str = if !commit.message.scope.is_empty() {
@@ -370,33 +509,89 @@ impl SelectedTab {
             String::new()
         };
 
-        lines.push(Line::from(vec![
-            prefix_str
-                .fg(prefix_color.c200)
-                .bg(prefix_color.c900)
-                .bold(),
-            scope_str.fg(tailwind::SLATE.c400).italic(),
-            breaking_str.fg(tailwind::RED.c500).bold(),
-        ]));
-        lines.push(Line::from(""));
+        if let Some(edited) = &commit.message.edited {
+            lines.push(
+                Line::from("Edited (not a conventional commit)")
+                    .fg(tailwind::AMBER.c400)
+                    .bold(),
+            );
+            lines.push(Line::from(""));
 
-        lines.push(
-            Line::from("Header").fg(tailwind::SLATE.c500).bold(),
-        );
-        lines.push(
-            Line::from(commit.message.header.clone())
-                .fg(tailwind::SLATE.c100),
-        );
-        lines.push(Line::from(""));
+            for edited_line in edited.lines() {
+                lines.push(
+                    spelled_line(edited_line, typos)
+                        .fg(tailwind::SLATE.c100),
+                );
+            }
+            lines.push(Line::from(""));
+        } else {
+            lines.push(Line::from(vec![
+                gitmoji
+                    .map(|emoji| format!("{} ", emoji))
+                    .unwrap_or_default()
+                    .into(),
+                prefix_str.fg(Color::Black).bg(prefix_color).bold(),
+                scope_str.fg(tailwind::SLATE.c400).italic(),
+                breaking_str.fg(tailwind::RED.c500).bold(),
+            ]));
+            lines.push(Line::from(""));
 
-        if !commit.message.body.is_empty() {
             lines.push(
-                Line::from("Body").fg(tailwind::SLATE.c500).bold(),
+                Line::from("Header").fg(tailwind::SLATE.c500).bold(),
             );
-            for body_line in commit.message.body.lines() {
+            lines.push(
+                spelled_line(&commit.message.header, typos)
+                    .fg(tailwind::SLATE.c100),
+            );
+            lines.push(Line::from(""));
+
+            if !commit.message.body.is_empty() {
                 lines.push(
-                    Line::from(body_line).fg(tailwind::SLATE.c300),
+                    Line::from("Body")
+                        .fg(tailwind::SLATE.c500)
+                        .bold(),
                 );
+                for body_line in commit.message.body.lines() {
+                    lines.push(
+                        spelled_line(body_line, typos)
+                            .fg(tailwind::SLATE.c300),
+                    );
+                }
+                lines.push(Line::from(""));
+            }
+
+            if !commit.message.footers.is_empty() {
+                lines.push(
+                    Line::from("Footers")
+                        .fg(tailwind::SLATE.c500)
+                        .bold(),
+                );
+                for footer in &commit.message.footers {
+                    let color = if footer.starts_with("BREAKING") {
+                        tailwind::RED.c400
+                    } else {
+                        tailwind::SLATE.c300
+                    };
+                    lines.push(Line::from(footer.as_str()).fg(color));
+                }
+                lines.push(Line::from(""));
+            }
+        }
+
+        if !typos.is_empty() {
+            lines.push(
+                Line::from("Possible Typos")
+                    .fg(tailwind::SLATE.c500)
+                    .bold(),
+            );
+            for typo in typos {
+                lines.push(Line::from(vec![
+                    format!("  {}", typo.word)
+                        .fg(tailwind::AMBER.c400)
+                        .underlined(),
+                    format!(" → {}", typo.suggestion)
+                        .fg(tailwind::SLATE.c300),
+                ]));
             }
             lines.push(Line::from(""));
         }
@@ -426,18 +621,58 @@ impl SelectedTab {
             }
         }
 
-        let paragraph = Paragraph::new(lines)
-            .block(
-                Block::bordered()
-                    .title("Commit Info")
-                    .borders(Borders::ALL)
-                    .padding(Padding::horizontal(1))
-                    .border_style(border_style),
-            )
-            .wrap(Wrap { trim: false })
-            .scroll((scroll, 0));
+        // with the borders
+        let info_len = lines.len() as u16 + 2;
+        let paragraph = Paragraph::new(lines).block(
+            Block::bordered()
+                .title("Commit Info")
+                .borders(Borders::ALL)
+                .padding(Padding::horizontal(1))
+                .border_style(border_style),
+        );
+
+        // nothing to split off, ex. every file was removed
+        if diffs.is_empty() {
+            paragraph
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0))
+                .render(area, buf);
+            return;
+        }
+
+        // the message on top up to half the height,
+        // scrolling moves the diffs below it
+        let info_height = info_len.min(area.height / 2);
+        let [info_area, diff_area] = Layout::vertical([
+            Constraint::Length(info_height),
+            Constraint::Min(0),
+        ])
+        .areas(area);
+
+        paragraph.wrap(Wrap { trim: false }).render(info_area, buf);
 
-        paragraph.render(area, buf);
+        let mut lines: Vec<Line> = Vec::new();
+        for (label, hunks) in diffs {
+            if !lines.is_empty() {
+                lines.push(Line::from(""));
+            }
+            lines.push(
+                Line::from(label.to_owned())
+                    .fg(tailwind::CYAN.c400)
+                    .bold(),
+            );
+            diff_lines(&mut lines, hunks, None, word_diff);
+        }
+
+        self.render_scrolled(
+            diff_area,
+            buf,
+            "Diffs",
+            lines,
+            (scroll, h_scroll),
+            wrap,
+            border_style,
+        );
     }
 
     fn render_loading(
@@ -445,6 +680,7 @@ impl SelectedTab {
         area: Rect,
         buf: &mut Buffer,
         message: &str,
+        partial: &str,
         throbber_state: &mut ThrobberState,
     ) {
         let block = Block::bordered()
@@ -456,6 +692,25 @@ impl SelectedTab {
         let inner_area = block.inner(area);
         block.render(area, buf);
 
+        let [throbber_area, partial_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .areas(inner_area);
+
+        if !partial.is_empty() {
+            // keep the tail in view as it grows
+            let height = partial_area.height as usize;
+            let total = partial.lines().count();
+            let scroll = total.saturating_sub(height) as u16;
+
+            Paragraph::new(partial.to_owned())
+                .fg(tailwind::SLATE.c400)
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0))
+                .render(partial_area, buf);
+        }
+
         let throbber = Throbber::default()
             .label(message)
             .style(Style::default().fg(tailwind::CYAN.c400))
@@ -469,7 +724,7 @@ impl SelectedTab {
 
         StatefulWidget::render(
             throbber,
-            inner_area,
+            throbber_area,
             buf,
             throbber_state,
         );
@@ -485,3 +740,184 @@ impl SelectedTab {
         }
     }
 }
+
+/// the hunks as diff lines onto lines, where the
+/// selected hunk starts when there is one
+fn diff_lines(
+    lines: &mut Vec<Line<'static>>,
+    hunk_diffs: &[HunkDiff],
+    selected: Option<usize>,
+    word_diff: bool,
+) -> Option<usize> {
+    let mut selected_start = None;
+
+    for (i, hunk) in hunk_diffs.iter().enumerate() {
+        let is_selected = selected == Some(i);
+        if is_selected {
+            selected_start = Some(lines.len());
+        }
+
+        let marker = if is_selected { "> " } else { "" };
+        let header = hunk.header.trim_end();
+
+        // named ansi colors so it follows the
+        // terminal theme, light or dark
+        if hunk.excluded {
+            let mut style = Style::new()
+                .fg(Color::DarkGray)
+                .add_modifier(Modifier::CROSSED_OUT);
+            if is_selected {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+
+            lines.push(Line::from(vec![
+                Span::raw(marker),
+                Span::styled(header.to_owned(), style),
+                Span::raw(" (excluded)").fg(Color::DarkGray),
+            ]));
+            continue;
+        }
+
+        let mut header_style = Style::new().fg(Color::Cyan);
+        if is_selected {
+            header_style = header_style
+                .add_modifier(Modifier::REVERSED | Modifier::BOLD);
+        }
+
+        lines.push(Line::from(vec![
+            Span::raw(marker),
+            Span::styled(header.to_owned(), header_style),
+        ]));
+
+        let (mut old_line, mut new_line) = hunk.line_starts();
+
+        let segments = if word_diff {
+            word_diff::hunk_segments(&hunk.line_diffs)
+        } else {
+            Vec::new()
+        };
+
+        for (j, line_diff) in hunk.line_diffs.iter().enumerate() {
+            let (prefix, color) = match line_diff.diff_type {
+                DiffType::Additions => ("+", Some(Color::Green)),
+                DiffType::Deletions => ("-", Some(Color::Red)),
+                DiffType::Unchanged => (" ", None),
+            };
+
+            // old/new line numbers, blank on
+            // the side the line doesn't exist in
+            let (has_old, has_new) = match line_diff.diff_type {
+                DiffType::Additions => (false, true),
+                DiffType::Deletions => (true, false),
+                DiffType::Unchanged => (true, true),
+            };
+            let old = if has_old {
+                old_line += 1;
+                (old_line - 1).to_string()
+            } else {
+                String::new()
+            };
+            let new = if has_new {
+                new_line += 1;
+                (new_line - 1).to_string()
+            } else {
+                String::new()
+            };
+            let gutter = format!("{:>4} {:>4} │", old, new);
+
+            let style =
+                color.map(|c| Style::new().fg(c)).unwrap_or_default();
+            let content = line_diff.content.trim_end_matches('\n');
+
+            let mut spans = vec![
+                Span::raw(gutter).fg(Color::DarkGray),
+                Span::styled(
+                    prefix,
+                    style.add_modifier(Modifier::BOLD),
+                ),
+            ];
+
+            match segments.get(j).and_then(Option::as_ref) {
+                // only what changed stands out
+                Some(segments) => {
+                    spans.extend(segments.iter().map(|s| {
+                        let text = s.text.trim_end_matches('\n');
+                        if s.changed {
+                            Span::styled(
+                                text.to_owned(),
+                                style
+                                    .add_modifier(Modifier::REVERSED),
+                            )
+                        } else {
+                            Span::styled(text.to_owned(), style)
+                        }
+                    }));
+                }
+                None => spans
+                    .push(Span::styled(content.to_owned(), style)),
+            }
+
+            lines.push(Line::from(spans));
+        }
+    }
+
+    selected_start
+}
+
+/// the badge padded and on its color, the rest of
+/// the item is left to whatever style it gets
+fn badged_line<'a>(item: &'a str, badge: &Badge) -> Line<'a> {
+    let Some(text) = item.get(badge.range.clone()) else {
+        return Line::from(item);
+    };
+
+    Line::from(vec![
+        Span::raw(&item[..badge.range.start]),
+        Span::styled(
+            format!(" {} ", text),
+            Style::new()
+                .fg(Color::Black)
+                .bg(badge.color)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(&item[badge.range.end..]),
+    ])
+}
+
+/// wrapped, or cut off and scrolled sideways
+fn wrap_or_scroll(
+    paragraph: Paragraph<'_>,
+    wrap: bool,
+    scroll: u16,
+    h_scroll: u16,
+) -> Paragraph<'_> {
+    if wrap {
+        paragraph.wrap(Wrap { trim: false }).scroll((scroll, 0))
+    } else {
+        paragraph.scroll((scroll, h_scroll))
+    }
+}
+
+/// the flagged words underlined, the rest is
+/// left to whatever style the line gets
+fn spelled_line<'a>(text: &'a str, typos: &[Typo]) -> Line<'a> {
+    let mut spans = Vec::new();
+    let mut last = 0;
+
+    for (start, word) in spelling::words(text) {
+        if !typos.iter().any(|t| t.word.eq_ignore_ascii_case(word)) {
+            continue;
+        }
+
+        spans.push(Span::raw(&text[last..start]));
+        spans.push(
+            Span::raw(word)
+                .fg(tailwind::AMBER.c400)
+                .add_modifier(Modifier::UNDERLINED),
+        );
+        last = start + word.len();
+    }
+    spans.push(Span::raw(&text[last..]));
+
+    Line::from(spans)
+}
@@ -1,23 +1,262 @@
 use ratatui::{
     Frame,
-    layout::{Constraint, Layout},
-    style::Color,
+    layout::{
+        Constraint::{self, Length, Percentage},
+        Flex, Layout, Margin, Position, Rect,
+    },
+    style::{Color, Modifier, Style, Stylize, palette::tailwind},
     text::{Line, Text},
-    widgets::{ListState, Tabs, Widget},
+    widgets::{
+        Block, Clear, List, ListItem, ListState, Padding, Paragraph,
+        StatefulWidget, Tabs, Widget,
+    },
 };
+use std::time::{Duration, Instant};
 use strum::IntoEnumIterator;
 use throbber_widgets_tui::ThrobberState;
 
-use crate::tui::tabs::{SelectedTab, TabContent, TabList};
+use crate::{
+    ai::provider::Provider,
+    config::Keymap,
+    git::hook::HookRun,
+    tui::{
+        keys::{KEY_BINDINGS, VIM_SEQUENCES},
+        tabs::{SelectedTab, TabContent, TabList},
+    },
+};
+
+/// below either of these the list goes above the
+/// content and the truncated files aren't shown
+const COMPACT_WIDTH: u16 = 100;
+const COMPACT_HEIGHT: u16 = 30;
 
 #[derive(Default)]
 pub struct UI {
     pub selected_tab: SelectedTab,
     pub selected_state: ListState,
+    /// the truncated files under the diffs list
+    pub secondary_state: ListState,
+    /// which of the two lists j / k move through
+    pub list_pane: ListPane,
+    /// the content pane scrolls without
+    /// changing the selection
+    pub focused_pane: FocusedPane,
 
     pub throbber_state: ThrobberState,
     pub mode: UIMode,
     pub content_scroll: u16,
+    /// horizontal scroll, only used while unwrapped
+    pub h_scroll: u16,
+    /// wrap long lines in diffs and descriptions,
+    /// kept across tab switches
+    pub wrap: bool,
+    /// word level highlights in diffs, tui.word_diff
+    /// is the starting point
+    pub word_diff: bool,
+    /// small terminal, checked every frame
+    pub compact: bool,
+    /// the commits tab shows the response
+    /// as it came back instead
+    pub raw_view: bool,
+    /// hunk picked inside an entered diff
+    pub selected_hunk: usize,
+
+    /// keybinding overlay, drawn over the tab
+    pub show_help: bool,
+    /// waiting on y before applying
+    pub confirm_apply: Option<ConfirmApply>,
+    /// models to pick from, open once they're fetched
+    pub model_picker: Option<ModelPicker>,
+    /// the post_apply_hook, shown until dismissed
+    pub hook: Option<HookStatus>,
+    /// what landed, shown once applying is done
+    pub summary: Option<ApplySummary>,
+    /// typing the branch to apply onto
+    pub branch_prompt: Option<BranchPrompt>,
+    /// listed in the header next to the tabs
+    pub providers: Vec<ProviderTab>,
+
+    /// filter for the diffs list, None when off
+    pub search: Option<String>,
+
+    pub keymap: Keymap,
+    /// typed so far of a multi key
+    /// sequence, ex. the first g of gg
+    pub pending_keys: String,
+
+    /// from the last render, for mouse clicks
+    pub header_area: Rect,
+    pub list_area: Rect,
+
+    /// short lived message shown in the footer
+    /// ex. "Copied!", cleared on tick once expired
+    pub notice: Option<(String, Instant)>,
+}
+
+/// what the apply confirmation lists
+pub struct ConfirmApply {
+    pub force: bool,
+    pub subjects: Vec<String>,
+    pub files: Vec<String>,
+    /// post_apply_hook, run after applying
+    pub hook: Option<String>,
+}
+
+/// applying onto a branch made on the fly
+#[derive(Default)]
+pub struct BranchPrompt {
+    pub name: String,
+    /// commits that will land on it
+    pub commits: usize,
+    /// name is an existing branch, enter
+    /// again checks it out instead
+    pub exists: bool,
+    pub error: Option<String>,
+}
+
+/// the commits apply_commits created
+pub struct ApplySummary {
+    /// short hash and subject, oldest first
+    pub commits: Vec<(String, String)>,
+    /// git log --oneline from HEAD, newest first,
+    /// the bool marks the ones just created
+    pub log: Vec<(String, String, bool)>,
+    pub show_log: bool,
+    pub branch: Option<String>,
+}
+
+pub struct HookStatus {
+    pub command: String,
+    pub started: Instant,
+    /// commits a soft reset would undo
+    pub applied: usize,
+    /// false without a HEAD to go back to
+    pub can_reset: bool,
+    /// None while it's still running
+    pub run: Option<Result<HookRun, String>>,
+}
+
+impl HookStatus {
+    /// finished with a non zero exit, or never started
+    pub fn failed(&self) -> bool {
+        match &self.run {
+            Some(Ok(run)) => !run.status.success(),
+            Some(Err(_)) => true,
+            None => false,
+        }
+    }
+}
+
+/// the line under the footer, rebuilt every frame
+pub struct StatusLine {
+    pub provider: Provider,
+    pub model: String,
+    pub files: usize,
+    pub added: usize,
+    pub removed: usize,
+    pub applied: bool,
+}
+
+impl StatusLine {
+    /// as many fields as fit in width, the least
+    /// important ones are dropped first
+    fn fields(&self, tab: SelectedTab, width: usize) -> Vec<String> {
+        let model = if self.model.is_empty() {
+            self.provider.to_string()
+        } else {
+            self.provider.name(&self.model)
+        };
+
+        // (drop order, text), the highest goes first
+        let mut fields = vec![
+            (0, model),
+            (1, tab.to_string()),
+            (
+                2,
+                format!(
+                    "{} file{}",
+                    self.files,
+                    if self.files == 1 { "" } else { "s" }
+                ),
+            ),
+            (4, format!("+{} -{}", self.added, self.removed)),
+        ];
+        if self.applied {
+            fields.push((3, "applied".to_owned()));
+        }
+
+        // with the " │ " in between
+        let len = |fields: &[(u8, String)]| {
+            fields
+                .iter()
+                .map(|(_, f)| f.chars().count())
+                .sum::<usize>()
+                + 3 * fields.len().saturating_sub(1)
+        };
+
+        while fields.len() > 1 && len(&fields) > width {
+            if let Some(i) = fields
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, (drop, _))| *drop)
+                .map(|(i, _)| i)
+            {
+                fields.remove(i);
+            }
+        }
+
+        fields.into_iter().map(|(_, f)| f).collect()
+    }
+}
+
+pub struct ProviderTab {
+    pub provider: Provider,
+    /// has a key to send with, dimmed otherwise
+    pub ready: bool,
+}
+
+pub struct ModelPicker {
+    pub provider: Provider,
+    pub models: Vec<String>,
+    pub state: ListState,
+}
+
+impl ModelPicker {
+    /// starts on the model in use, if it's listed
+    pub fn new(
+        provider: Provider,
+        models: Vec<String>,
+        current: &str,
+    ) -> Self {
+        let mut state = ListState::default();
+        state.select(Some(
+            models.iter().position(|m| m == current).unwrap_or(0),
+        ));
+
+        Self {
+            provider,
+            models,
+            state,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.models.get(self.state.selected()?).map(String::as_str)
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum ListPane {
+    #[default]
+    Main,
+    Secondary,
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum FocusedPane {
+    #[default]
+    List,
+    Content,
 }
 
 #[derive(Default)]
@@ -25,6 +264,8 @@ pub enum UIMode {
     #[default]
     TabNavigation,
     Content,
+    /// typing into the diffs filter
+    Search,
 
     // todo, special mode
     // to edit commit message
@@ -33,16 +274,96 @@ pub enum UIMode {
 }
 
 impl UI {
-    pub fn new() -> Self {
+    pub fn new(keymap: Keymap, providers: Vec<ProviderTab>) -> Self {
         let mut selected_state = ListState::default();
         selected_state.select_first();
 
         Self {
             selected_tab: SelectedTab::Diffs,
             selected_state,
+            secondary_state: ListState::default(),
+            list_pane: ListPane::Main,
+            focused_pane: FocusedPane::List,
             throbber_state: ThrobberState::default(),
             mode: UIMode::TabNavigation,
             content_scroll: 0,
+            h_scroll: 0,
+            wrap: true,
+            word_diff: false,
+            compact: false,
+            raw_view: false,
+            selected_hunk: 0,
+            notice: None,
+            search: None,
+            show_help: false,
+            confirm_apply: None,
+            model_picker: None,
+            hook: None,
+            summary: None,
+            branch_prompt: None,
+            providers,
+            keymap,
+            pending_keys: String::new(),
+            header_area: Rect::default(),
+            list_area: Rect::default(),
+        }
+    }
+
+    pub fn start_search(&mut self) {
+        if !matches!(self.selected_tab, SelectedTab::Diffs) {
+            return;
+        }
+
+        self.search.get_or_insert_with(String::new);
+        self.mode = UIMode::Search;
+    }
+
+    pub fn search_input(&mut self, c: char) {
+        if let Some(search) = &mut self.search {
+            search.push(c);
+            self.selected_state.select_first();
+        }
+    }
+
+    pub fn search_backspace(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.pop();
+            self.selected_state.select_first();
+        }
+    }
+
+    /// keeps the filter, back to the list
+    pub fn confirm_search(&mut self) {
+        if self.search.as_deref().is_some_and(str::is_empty) {
+            self.search = None;
+        }
+
+        self.mode = UIMode::TabNavigation;
+    }
+
+    pub fn clear_search(&mut self) {
+        self.search = None;
+        self.mode = UIMode::TabNavigation;
+        self.selected_state.select_first();
+    }
+
+    pub fn update_compact(&mut self, area: Rect) {
+        self.compact = area.width < COMPACT_WIDTH
+            || area.height < COMPACT_HEIGHT;
+    }
+
+    pub fn notify(&mut self, message: impl Into<String>) {
+        self.notice = Some((
+            message.into(),
+            Instant::now() + Duration::from_secs(2),
+        ));
+    }
+
+    pub fn clear_expired_notice(&mut self) {
+        if let Some((_, expires)) = &self.notice
+            && Instant::now() >= *expires
+        {
+            self.notice = None;
         }
     }
 
@@ -52,37 +373,106 @@ impl UI {
         tab_content: &TabContent,
         tab_list: &TabList,
         is_loading: bool,
+        status: &StatusLine,
     ) {
-        use Constraint::{Length, Min};
-        let vertical =
-            Layout::vertical([Length(1), Min(0), Length(2)])
-                .margin(5);
-        let [header_area, inner_area, footer_area] =
+        use Constraint::Min;
+        let vertical = Layout::vertical([
+            Length(1),
+            Min(0),
+            Length(2),
+            Length(1),
+        ])
+        .margin(if self.compact { 1 } else { 5 });
+        let [header_area, inner_area, footer_area, status_area] =
             vertical.areas(frame.area());
 
-        self.render_tabs(header_area, frame.buffer_mut());
+        let tabs_width = SelectedTab::iter()
+            .map(|tab| tab.title().width() as u16 + 1)
+            .sum();
+        // the providers get cut off first
+        let [tabs_area, _, providers_area] = Layout::horizontal([
+            Length(tabs_width),
+            Length(2),
+            Min(0),
+        ])
+        .areas(header_area);
+
+        self.render_tabs(tabs_area, frame.buffer_mut());
+        self.render_providers(
+            providers_area,
+            frame.buffer_mut(),
+            status.provider,
+        );
+        self.header_area = tabs_area;
 
-        self.selected_tab.render(
+        let content_focused = self.content_focused();
+        self.list_area = self.selected_tab.render(
             inner_area,
             frame.buffer_mut(),
             tab_content,
             tab_list,
             &mut self.selected_state,
+            match self.list_pane {
+                ListPane::Main => None,
+                ListPane::Secondary => {
+                    Some(&mut self.secondary_state)
+                }
+            },
             is_loading,
             &mut self.throbber_state,
-            &self.mode,
+            content_focused,
             self.content_scroll,
+            self.wrap,
+            self.h_scroll,
+            self.compact,
         );
 
         self.render_footer(footer_area, frame.buffer_mut());
+        self.render_status(status, status_area, frame.buffer_mut());
+
+        if self.show_help {
+            self.render_help(frame.area(), frame.buffer_mut());
+        }
+
+        if let Some(confirm) = &self.confirm_apply {
+            self.render_confirm_apply(
+                confirm,
+                frame.area(),
+                frame.buffer_mut(),
+            );
+        }
+
+        self.render_model_picker(frame.area(), frame.buffer_mut());
+
+        if let Some(prompt) = &self.branch_prompt {
+            self.render_branch_prompt(
+                prompt,
+                frame.area(),
+                frame.buffer_mut(),
+            );
+        }
+
+        if let Some(hook) = &self.hook {
+            self.render_hook(hook, frame.area(), frame.buffer_mut());
+        }
+
+        if let Some(summary) = &self.summary {
+            self.render_summary(
+                summary,
+                frame.area(),
+                frame.buffer_mut(),
+            );
+        }
     }
 
     pub fn scroll_up(&mut self) {
         match self.mode {
-            UIMode::TabNavigation => {
-                self.selected_state.select_previous()
+            UIMode::TabNavigation
+                if self.focused_pane == FocusedPane::List =>
+            {
+                self.focused_state().select_previous()
             }
-            UIMode::Content => {
+            UIMode::TabNavigation | UIMode::Content => {
                 self.content_scroll =
                     self.content_scroll.saturating_sub(1)
             }
@@ -92,10 +482,12 @@ impl UI {
 
     pub fn scroll_down(&mut self) {
         match self.mode {
-            UIMode::TabNavigation => {
-                self.selected_state.select_next()
+            UIMode::TabNavigation
+                if self.focused_pane == FocusedPane::List =>
+            {
+                self.focused_state().select_next()
             }
-            UIMode::Content => {
+            UIMode::TabNavigation | UIMode::Content => {
                 self.content_scroll =
                     self.content_scroll.saturating_add(1)
             }
@@ -103,34 +495,166 @@ impl UI {
         }
     }
 
+    pub fn jump_top(&mut self) {
+        match self.mode {
+            UIMode::TabNavigation
+                if self.focused_pane == FocusedPane::List =>
+            {
+                self.focused_state().select_first()
+            }
+            UIMode::TabNavigation | UIMode::Content => {
+                self.content_scroll = 0
+            }
+            _ => {}
+        }
+    }
+
+    /// content has no known end, only lists jump down
+    pub fn jump_bottom(&mut self) {
+        if matches!(self.mode, UIMode::TabNavigation)
+            && self.focused_pane == FocusedPane::List
+        {
+            self.focused_state().select_last();
+        }
+    }
+
+    pub fn focused_state(&mut self) -> &mut ListState {
+        match self.list_pane {
+            ListPane::Main => &mut self.selected_state,
+            ListPane::Secondary => &mut self.secondary_state,
+        }
+    }
+
+    /// moves j / k to the other list, the
+    /// caller checks that it has entries
+    pub fn focus_pane(&mut self, pane: ListPane) {
+        if pane == ListPane::Secondary
+            && self.secondary_state.selected().is_none()
+        {
+            self.secondary_state.select_first();
+        }
+
+        self.list_pane = pane;
+    }
+
+    /// j / k scroll the content, the list
+    /// keeps its selection
+    pub fn focus_content(&mut self) {
+        self.focused_pane = FocusedPane::Content;
+    }
+
+    /// back to the list, from the top of the content
+    pub fn focus_list(&mut self) {
+        self.focused_pane = FocusedPane::List;
+        self.content_scroll = 0;
+        self.h_scroll = 0;
+    }
+
+    /// entered or focused from the list
+    pub fn content_focused(&self) -> bool {
+        matches!(self.mode, UIMode::Content)
+            || self.focused_pane == FocusedPane::Content
+    }
+
+    /// unwrapped content scrolls sideways
+    /// instead of changing tabs
+    fn scrolls_horizontally(&self) -> bool {
+        !self.wrap && self.content_focused()
+    }
+
     pub fn focus_left(&mut self) {
+        if self.scrolls_horizontally() {
+            self.h_scroll = self.h_scroll.saturating_sub(4);
+            return;
+        }
+
         self.selected_tab = self.selected_tab.previous();
         self.mode = UIMode::TabNavigation;
+        self.list_pane = ListPane::Main;
+        self.focused_pane = FocusedPane::List;
         self.content_scroll = 0;
+        self.h_scroll = 0;
     }
 
     pub fn focus_right(&mut self) {
+        if self.scrolls_horizontally() {
+            self.h_scroll = self.h_scroll.saturating_add(4);
+            return;
+        }
+
         self.selected_tab = self.selected_tab.next();
         self.mode = UIMode::TabNavigation;
+        self.list_pane = ListPane::Main;
+        self.focused_pane = FocusedPane::List;
         self.content_scroll = 0;
+        self.h_scroll = 0;
     }
 
     pub fn goto_tab(&mut self, tab: usize) {
         self.selected_tab =
             self.selected_tab.find_tab(tab.saturating_sub(1));
         self.mode = UIMode::TabNavigation;
+        self.list_pane = ListPane::Main;
+        self.focused_pane = FocusedPane::List;
         self.content_scroll = 0;
+        self.h_scroll = 0;
+    }
+
+    /// tab under a column of the header, matches
+    /// the titles and divider render_tabs draws
+    pub fn tab_at(&self, column: u16) -> Option<usize> {
+        let mut x = self.header_area.x;
+
+        for (i, tab) in SelectedTab::iter().enumerate() {
+            let width = tab.title().width() as u16;
+            if (x..x + width).contains(&column) {
+                return Some(i);
+            }
+
+            // divider
+            x += width + 1;
+        }
+
+        None
+    }
+
+    /// row of the list under the cursor,
+    /// not checked against the list's length
+    pub fn list_row_at(
+        &self,
+        column: u16,
+        row: u16,
+    ) -> Option<usize> {
+        // inside the borders
+        let inner = self.list_area.inner(Margin::new(1, 1));
+        if !inner.contains(Position::new(column, row)) {
+            return None;
+        }
+
+        Some(self.selected_state.offset() + (row - inner.y) as usize)
+    }
+
+    pub fn toggle_wrap(&mut self) {
+        self.wrap = !self.wrap;
+        self.h_scroll = 0;
+    }
+
+    pub fn toggle_word_diff(&mut self) {
+        self.word_diff = !self.word_diff;
     }
 
     pub fn enter_ui(&mut self) {
         match self.selected_tab {
             SelectedTab::Diffs | SelectedTab::Commits => {
-                if self.selected_state.selected().is_none() {
+                if self.focused_state().selected().is_none() {
                     return;
                 }
 
+                self.selected_hunk = 0;
+                self.focused_pane = FocusedPane::List;
                 self.mode = if matches!(self.mode, UIMode::Content) {
                     self.content_scroll = 0;
+                    self.h_scroll = 0;
                     UIMode::TabNavigation
                 } else {
                     UIMode::Content
@@ -156,14 +680,514 @@ impl UI {
             .render(header_area, buf);
     }
 
+    fn provider_label(tab: &ProviderTab) -> String {
+        if tab.ready {
+            format!(" {} ", tab.provider)
+        } else {
+            format!(" ⚠ {} ", tab.provider)
+        }
+    }
+
+    /// the active one is highlighted, ones
+    /// without a key are dimmed with a ⚠
+    fn render_providers(
+        &self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+        active: Provider,
+    ) {
+        let palette = self.selected_tab.palette();
+
+        let titles = self.providers.iter().map(|tab| {
+            let label = Self::provider_label(tab);
+            if tab.ready {
+                label.fg(tailwind::SLATE.c200).bg(palette.c950)
+            } else {
+                label.fg(tailwind::SLATE.c500).dim()
+            }
+        });
+
+        let mut tabs = Tabs::new(titles)
+            .highlight_style((Color::default(), palette.c500))
+            .padding("", "")
+            .divider(" ");
+
+        if let Some(active) =
+            self.providers.iter().position(|t| t.provider == active)
+        {
+            tabs = tabs.select(active);
+        }
+
+        tabs.render(area, buf);
+    }
+
+    fn render_help(
+        &self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        let sequences = match self.keymap {
+            Keymap::Default => &[][..],
+            Keymap::Vim => VIM_SEQUENCES,
+        };
+
+        let bindings: Vec<(&str, &str)> = KEY_BINDINGS
+            .iter()
+            .map(|b| (b.label, b.description))
+            .chain(sequences.iter().map(|s| (s.label, s.description)))
+            .collect();
+
+        let label_width = bindings
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or_default();
+
+        let lines: Vec<Line> = bindings
+            .iter()
+            .map(|(label, description)| {
+                Line::from(vec![
+                    format!("{:<label_width$}  ", label)
+                        .fg(self.selected_tab.palette().c400)
+                        .bold(),
+                    (*description).into(),
+                ])
+            })
+            .collect();
+
+        // borders + padding
+        let height = lines.len() as u16 + 2;
+        let [popup] = Layout::vertical([Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::horizontal([Percentage(60)])
+            .flex(Flex::Center)
+            .areas(popup);
+
+        Clear.render(popup, buf);
+
+        Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .title(" Keybindings (? or esc to close) ")
+                    .padding(Padding::horizontal(1))
+                    .border_style(self.selected_tab.palette().c400),
+            )
+            .render(popup, buf);
+    }
+
+    fn render_confirm_apply(
+        &self,
+        confirm: &ConfirmApply,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        let palette = self.selected_tab.palette();
+
+        let [column] = Layout::horizontal([Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+        // borders and padding
+        let width = (column.width as usize).saturating_sub(4).max(1);
+
+        let mut lines: Vec<Line> = vec![
+            Line::from(format!(
+                "{} commit{} touching {} file{}",
+                confirm.subjects.len(),
+                if confirm.subjects.len() == 1 { "" } else { "s" },
+                confirm.files.len(),
+                if confirm.files.len() == 1 { "" } else { "s" },
+            ))
+            .bold(),
+            Line::from(""),
+        ];
+
+        // ahead of the list, a long one is what gets cut off
+        if confirm.force {
+            lines.push(
+                Line::from("Invalid commits will be applied as well")
+                    .fg(Color::Yellow),
+            );
+            lines.push(Line::from(""));
+        }
+
+        if let Some(hook) = &confirm.hook {
+            lines.push(Line::from("Then runs post_apply_hook:"));
+            // all of it, a cut off command could hide anything
+            for line in hook.lines() {
+                let chars: Vec<char> = line.chars().collect();
+                for chunk in chars.chunks(width) {
+                    lines.push(
+                        Line::from(chunk.iter().collect::<String>())
+                            .fg(palette.c300),
+                    );
+                }
+            }
+            lines.push(Line::from(
+                "If it fails, r soft resets these commits",
+            ));
+            lines.push(Line::from(""));
+        }
+
+        for (i, subject) in confirm.subjects.iter().enumerate() {
+            lines.push(Line::from(vec![
+                format!("{}. ", i + 1).fg(palette.c400),
+                subject.to_owned().into(),
+            ]));
+        }
+
+        lines.push(Line::from(""));
+        for file in &confirm.files {
+            lines.push(
+                Line::from(format!("  • {}", file)).fg(palette.c300),
+            );
+        }
+
+        // borders, capped so it still fits
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let [popup] = Layout::vertical([Length(height)])
+            .flex(Flex::Center)
+            .areas(column);
+
+        Clear.render(popup, buf);
+
+        Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .title(" Apply commits? (y to apply, esc to cancel) ")
+                    .padding(Padding::horizontal(1))
+                    .border_style(palette.c400),
+            )
+            .render(popup, buf);
+    }
+
+    fn render_branch_prompt(
+        &self,
+        prompt: &BranchPrompt,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        let palette = self.selected_tab.palette();
+
+        let mut lines: Vec<Line> = vec![
+            Line::from(vec![
+                "> ".fg(palette.c400),
+                prompt.name.to_owned().bold(),
+                "_".fg(palette.c400),
+            ]),
+            Line::from(""),
+        ];
+
+        if let Some(error) = &prompt.error {
+            lines.push(Line::from(error.to_owned()).fg(Color::Red));
+        } else if prompt.exists {
+            lines.push(
+                Line::from(format!(
+                    "{} already exists, enter checks it out and applies there",
+                    prompt.name.trim()
+                ))
+                .fg(Color::Yellow),
+            );
+        } else {
+            lines.push(Line::from(format!(
+                "Creates it from HEAD, switches to it and applies {} commit{} there",
+                prompt.commits,
+                if prompt.commits == 1 { "" } else { "s" },
+            )));
+        }
+
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let [popup] = Layout::vertical([Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::horizontal([Percentage(60)])
+            .flex(Flex::Center)
+            .areas(popup);
+
+        Clear.render(popup, buf);
+
+        Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .title(" Apply to a new branch (enter to apply, esc to cancel) ")
+                    .padding(Padding::horizontal(1))
+                    .border_style(palette.c400),
+            )
+            .render(popup, buf);
+    }
+
+    fn render_hook(
+        &self,
+        hook: &HookStatus,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        // the most of the output that fits
+        const OUTPUT_LINES: usize = 15;
+
+        let palette = self.selected_tab.palette();
+
+        let mut lines: Vec<Line> = vec![
+            Line::from(hook.command.to_owned()).fg(palette.c300),
+        ];
+        lines.push(Line::from(""));
+
+        let title = match &hook.run {
+            None => {
+                lines.push(Line::from(format!(
+                    "Running... ({}s)",
+                    hook.started.elapsed().as_secs()
+                )));
+
+                " post_apply_hook "
+            }
+            Some(Err(e)) => {
+                lines.push(Line::from(e.to_owned()).fg(Color::Red));
+
+                " post_apply_hook failed "
+            }
+            Some(Ok(run)) => {
+                let color = if run.status.success() {
+                    Color::Green
+                } else {
+                    Color::Red
+                };
+                lines.push(
+                    Line::from(format!(
+                        "Finished with {}",
+                        run.status
+                    ))
+                    .fg(color)
+                    .bold(),
+                );
+
+                let output: Vec<&str> = run.output.lines().collect();
+                if !output.is_empty() {
+                    lines.push(Line::from(""));
+                }
+                for line in &output
+                    [output.len().saturating_sub(OUTPUT_LINES)..]
+                {
+                    lines.push(Line::from(line.to_string()));
+                }
+
+                if run.status.success() {
+                    " post_apply_hook passed "
+                } else {
+                    " post_apply_hook failed "
+                }
+            }
+        };
+
+        if hook.run.is_some() {
+            lines.push(Line::from(""));
+            lines.push(Line::from(
+                if hook.failed() && hook.can_reset {
+                    format!(
+                        "r to soft reset the {} commit{}, their changes stay staged. q keeps them",
+                        hook.applied,
+                        if hook.applied == 1 { "" } else { "s" }
+                    )
+                } else {
+                    "q to quit".to_owned()
+                },
+            )
+            .fg(palette.c400));
+        }
+
+        // borders, capped so it still fits
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let [popup] = Layout::vertical([Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::horizontal([Percentage(70)])
+            .flex(Flex::Center)
+            .areas(popup);
+
+        Clear.render(popup, buf);
+
+        Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .padding(Padding::horizontal(1))
+                    .border_style(palette.c400),
+            )
+            .render(popup, buf);
+    }
+
+    fn render_summary(
+        &self,
+        summary: &ApplySummary,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        let palette = self.selected_tab.palette();
+        let mut lines: Vec<Line> = Vec::new();
+
+        if summary.show_log {
+            for (hash, subject, created) in &summary.log {
+                let marker = if *created { "* " } else { "  " };
+                let line = Line::from(vec![
+                    marker.into(),
+                    hash.to_owned().fg(Color::Yellow),
+                    format!(" {}", subject).into(),
+                ]);
+
+                lines.push(if *created {
+                    line.bold()
+                } else {
+                    line.fg(Color::DarkGray)
+                });
+            }
+        } else {
+            for (i, (hash, subject)) in
+                summary.commits.iter().enumerate()
+            {
+                lines.push(Line::from(vec![
+                    format!("{}. ", i + 1).fg(palette.c400),
+                    hash.to_owned().fg(Color::Yellow),
+                    format!(" {}", subject).into(),
+                ]));
+            }
+        }
+
+        lines.push(Line::from(""));
+        lines.push(
+            Line::from(format!(
+                "y to copy the hashes, l for the {}, q to quit",
+                if summary.show_log { "summary" } else { "log" }
+            ))
+            .fg(palette.c400),
+        );
+
+        let count = summary.commits.len();
+        let title = match &summary.branch {
+            Some(branch) => format!(
+                " Applied {} commit{} to {} ",
+                count,
+                if count == 1 { "" } else { "s" },
+                branch
+            ),
+            None => format!(
+                " Applied {} commit{} ",
+                count,
+                if count == 1 { "" } else { "s" }
+            ),
+        };
+
+        // borders, capped so it still fits
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let [popup] = Layout::vertical([Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::horizontal([Percentage(70)])
+            .flex(Flex::Center)
+            .areas(popup);
+
+        Clear.render(popup, buf);
+
+        Paragraph::new(lines)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .padding(Padding::horizontal(1))
+                    .border_style(palette.c400),
+            )
+            .render(popup, buf);
+    }
+
+    fn render_model_picker(
+        &mut self,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        let palette = self.selected_tab.palette();
+        let Some(picker) = &mut self.model_picker else {
+            return;
+        };
+
+        let items: Vec<ListItem> = picker
+            .models
+            .iter()
+            .map(|m| ListItem::new(m.as_str()))
+            .collect();
+
+        // borders, capped so it still fits
+        let height = (items.len() as u16 + 2).min(area.height);
+        let [popup] = Layout::vertical([Length(height)])
+            .flex(Flex::Center)
+            .areas(area);
+        let [popup] = Layout::horizontal([Percentage(60)])
+            .flex(Flex::Center)
+            .areas(popup);
+
+        Clear.render(popup, buf);
+
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(format!(
+                        " {} models (enter to use, s to save, esc to cancel) ",
+                        picker.provider
+                    ))
+                    .padding(Padding::horizontal(1))
+                    .border_style(palette.c400),
+            )
+            .highlight_style(
+                Style::new().bg(palette.c800).add_modifier(Modifier::BOLD),
+            );
+
+        StatefulWidget::render(list, popup, buf, &mut picker.state);
+    }
+
+    fn render_status(
+        &self,
+        status: &StatusLine,
+        area: ratatui::prelude::Rect,
+        buf: &mut ratatui::prelude::Buffer,
+    ) {
+        let fields =
+            status.fields(self.selected_tab, area.width.into());
+
+        Line::raw(fields.join(" │ "))
+            .fg(tailwind::SLATE.c400)
+            .render(area, buf);
+    }
+
     fn render_footer(
         &self,
         footer_area: ratatui::prelude::Rect,
         buf: &mut ratatui::prelude::Buffer,
     ) {
+        if matches!(self.mode, UIMode::Search) {
+            Text::from(Line::raw(
+                "type to filter files | enter to keep the filter | esc to clear",
+            ))
+            .centered()
+            .render(footer_area, buf);
+            return;
+        }
+
+        if let Some((notice, _)) = &self.notice {
+            Text::from(Line::raw(notice.as_str()).bold())
+                .centered()
+                .render(footer_area, buf);
+            return;
+        }
+
+        if self.compact {
+            Text::from(Line::raw(
+                "h / l tab | j / k select | / filter | ? help | q quit",
+            ))
+            .centered()
+            .render(footer_area, buf);
+            return;
+        }
+
         Text::from(vec![
-            Line::raw("h / l to change tab | j / k to select diffs/commits |"),
-            Line::raw("d to remove a diff | t to truncate | q to quit"),
+            Line::raw("h / l to change tab | j / k to select diffs/commits | / to filter files | ? for help |"),
+            Line::raw("d to remove a diff | t to truncate | space to exclude a hunk | s to toggle staged | e to edit | y to copy a commit | q to quit"),
         ])
         .centered()
         .render(footer_area, buf);